@@ -1,425 +1,4157 @@
-use std::collections::{HashSet, VecDeque};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BinaryHeap, HashMap, HashSet, VecDeque};
 use std::env;
-use std::io::{Error, ErrorKind};
+use std::io::{Error, ErrorKind, Write};
 use std::fs;
-use std::path::{Path, PathBuf};
+use std::path::{Component, Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
 
 use crate::data;
-use data::{Commit, ObjectType, PathVariant, RefVariant, RefValue};
+use crate::utils;
+use data::{Commit, ObjectType, Oid, PathVariant, RefTarget, RefVariant, RefValue, ReflogEntry, Repository, Tag};
 
-pub fn write_tree() -> std::io::Result<String> {
-  let path = data::generate_path(PathVariant::Root)?;
-  write_tree_recursive(&path)
+// Modes recorded in tree entries, as in git
+static FILE_MODE: &str = "100644";
+static EXECUTABLE_MODE: &str = "100755";
+static TREE_MODE: &str = "040000";
+// A symlink is stored as a blob holding the path it links to
+static SYMLINK_MODE: &str = "120000";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FileStatus {
+  New,
+  Modified,
+  Deleted,
 }
 
-pub fn read_tree(root_oid: &str) -> std::io::Result<()> {
-  let dir = env::current_dir().unwrap();
-  empty_current_directory()?;
-  let tree = get_tree(root_oid, &dir)?;
-  for tuple in tree {
-    let (path, oid) = tuple;
-    fs::create_dir_all(&path.parent().unwrap())?;
-    let contents = data::get_object(&oid, ObjectType::Blob)?;
-    fs::write(&path, contents)?;
-  }
+// Each variant carries the blob OIDs of the file before and/or after the change.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ChangeKind {
+  Added(Oid),
+  Removed(Oid),
+  Modified(Oid, Oid),
+}
 
-  Ok(())
+// A file's path along with the bytes to be written to it and the mode it is written with, which for a symlink makes the bytes its
+// target
+pub type FileContents = (PathBuf, Vec<u8>, String);
+
+pub enum MergeResult {
+  // The other commit is already part of HEAD's history
+  UpToDate,
+  // HEAD was part of the other commit's history, so it was simply moved to the other commit
+  FastForward,
+  // OID of the created merge commit
+  Merged(Oid),
+  // Paths which were changed on both sides. The working directory holds the merged tree with conflict markers written into these
+  // files, and the next commit will record the other commit as a second parent.
+  Conflicts(Vec<PathBuf>),
 }
 
-pub fn commit(message: &str) -> std::io::Result<String> {
-  let oid = write_tree()?;
-  let commit = match data::get_head() {
-    Some(head) => {
-      let head = head?;
-      format!("tree {}\nparent {}\n\n{}", oid, head, message)
-    },
-    None => format!("tree {}\n\n{}", oid, message)
-  };
+pub enum CherryPickResult {
+  // OID of the created commit
+  Picked(Oid),
+  // Paths which were changed both by the picked commit and since its parent. The working directory holds the applied tree with
+  // conflict markers written into these files, to be resolved and committed.
+  Conflicts(Vec<PathBuf>),
+}
 
-  let oid = data::hash_object(commit.as_bytes(), ObjectType::Commit)?;
-  data::set_head(&oid)?;
-  Ok(oid)
+// A problem found by fsck
+#[derive(Clone, Debug, PartialEq)]
+pub enum ObjectProblem {
+  // The object with the OID cannot be read or does not hash back to its OID, as described by the string
+  Corrupt(Oid, String),
+  // The object with the first OID refers to the second OID, which is not stored
+  Missing(Oid, Oid),
 }
 
-pub fn get_commit(oid: &str) -> std::io::Result<Commit> {
-  let mut tree = "";
-  let mut parent = None;
-  let commit = data::get_object(oid, ObjectType::Commit)?;
+// What a commit records in place of the defaults. The author otherwise comes from the environment or config, and the timestamp from
+// UGIT_COMMIT_DATE or the current time.
+#[derive(Clone, Debug, Default)]
+pub struct CommitOptions {
+  // Commits even when the tree matches HEAD's
+  pub allow_empty: bool,
+  // Formatted as "Name <email>"
+  pub author: Option<String>,
+  // Seconds since the Unix epoch
+  pub timestamp: Option<u64>,
+  // Replaces HEAD's commit with the new one, which takes over its parents
+  pub amend: bool,
+}
 
-  let mut lines = commit.lines();
-  for line in lines.by_ref() {
-    if line == "" {
-      break;
-    }
+pub struct ObjectCounts {
+  pub count: usize,
+  // Bytes the objects take up in the object store
+  pub size: u64,
+  // How many objects there are of each type, by the name in their type header
+  pub types: BTreeMap<&'static str, usize>,
+}
 
-    let object_parts: Vec<_> = line.splitn(2, " ").collect();
-    if object_parts[0] == "tree" {
-      tree = object_parts[1];
-    }
-    else if object_parts[0] == "parent" {
-      parent = Some(String::from(object_parts[1]));
-    }
-    else {
-      panic!("Unimplemented branch of get_commit: {}", object_parts[0]);
-    }
+pub struct Status {
+  // The branch HEAD points to, if it is not detached
+  pub branch: Option<String>,
+  // The commit HEAD resolves to, if there is one
+  pub head: Option<Oid>,
+  // Paths relative to the repository root, sorted
+  pub changes: Vec<(PathBuf, FileStatus)>,
+}
+
+// The operations of this module, for use against a Repository at an explicit location. The working directory is the one at root,
+// whichever directory is current.
+impl Repository {
+  pub fn write_tree(&self) -> std::io::Result<Oid> {
+    self.write_tree_with_progress(&mut |_| ())
   }
 
-  let mut message = String::from(lines.by_ref().next().unwrap());
-  for line in lines {
-    message = format!("{}\n{}", message, line);
+  // Calls progress with the number of files stored so far, after each file.
+  pub fn write_tree_with_progress(&self, progress: &mut dyn FnMut(usize)) -> std::io::Result<Oid> {
+    let ignore_rules = IgnoreRules::load(&self.root, &self.generate_path(PathVariant::Ugit))?;
+    write_tree_recursive(self, &self.root, &ignore_rules, progress)
   }
 
-  if tree == "" {
-    return Err(Error::new(ErrorKind::InvalidData, format!("Missing tree row of commit")));
+  // Computes the OID write_tree would return, along with the OID of every file by its path relative to the root, without writing
+  // any object. Comparing the working directory against a commit this way leaves the object database untouched.
+  pub fn get_working_tree_oid(&self) -> std::io::Result<(Oid, HashMap<PathBuf, Oid>)> {
+    let ignore_rules = IgnoreRules::load(&self.root, &self.generate_path(PathVariant::Ugit))?;
+    let mut files = HashMap::new();
+    let oid = snapshot_tree(self, &self.root, &ignore_rules, false, &mut files, &mut |_| ())?;
+    Ok((oid, files))
   }
 
-  Ok(
-    Commit {
-      tree: String::from(tree),
-      parent,
-      message,
-    }
-  )
-}
+  // Snapshots only the directory at path, which may be relative to the current directory, and returns the OID of its tree. The
+  // directory must lie within the repository, even once any symlinks along the way are followed.
+  pub fn write_subtree(&self, path: &Path) -> std::io::Result<Oid> {
+    self.write_subtree_with_progress(path, &mut |_| ())
+  }
 
-pub fn get_commits_to_root(start_oid: &str) -> std::io::Result<Vec<(String, Commit)>> {
-  let mut queue = VecDeque::new();
-  queue.push_back(String::from(start_oid));
-  let mut visited = HashSet::new();
-  let mut commit_list = Vec::new();
-  while let Some(oid) = queue.pop_front() {
-    if visited.contains(&oid) {
-      continue;
+  pub fn write_subtree_with_progress(&self, path: &Path, progress: &mut dyn FnMut(usize)) -> std::io::Result<Oid> {
+    let root = fs::canonicalize(&self.root)?;
+    let full_path = match fs::canonicalize(path) {
+      Ok(full_path) => full_path,
+      Err(err) => return Err(Error::new(err.kind(), format!("Cannot write a tree of [{}] -- {}", path.display(), err)))
+    };
+
+    if !full_path.starts_with(&root) {
+      return Err(Error::new(ErrorKind::InvalidInput, format!("Given path [{}] is outside of the repository", path.display())));
     }
-    else {
-      visited.insert(oid.clone());
+
+    let ignore_rules = IgnoreRules::load(&root, &self.generate_path(PathVariant::Ugit))?;
+    if ignore_rules.is_ignored(&full_path) {
+      return Err(Error::new(ErrorKind::InvalidInput, format!("Given path [{}] is ignored", path.display())));
     }
 
-    let commit = get_commit(&oid)?;
-    if let Some(ref parent) = commit.parent {
-      queue.push_back(parent.clone());
+    write_tree_recursive(self, &full_path, &ignore_rules, progress)
+  }
+
+  // Builds the tree to be committed from the staged index rather than from the working directory.
+  pub fn write_tree_from_index(&self) -> std::io::Result<Oid> {
+    let index = self.get_index()?;
+    let entries = index.iter().map(|(path, oid)| (path.as_str(), oid)).collect();
+    write_index_tree(self, &self.root, entries)
+  }
+
+  // Commits the staged index when anything has been staged, and a snapshot of the whole working directory otherwise.
+  // Unless allow_empty is set, a commit whose tree matches HEAD's is refused.
+  pub fn commit_with_options(&self, message: &str, options: &CommitOptions) -> std::io::Result<Oid> {
+    let tree = if self.get_index()?.is_empty() {
+      self.write_tree()?
     }
+    else {
+      self.write_tree_from_index()?
+    };
 
-    commit_list.push((oid, commit));
+    commit_to_head(self, &tree, message, options)
   }
 
-  Ok(commit_list)
-}
+  // Stores a commit of an existing tree with the given parents, without moving HEAD or any branch. Only the author and timestamp of
+  // options are used.
+  pub fn commit_tree(&self, tree: &Oid, parents: &[Oid], message: &str, options: &CommitOptions) -> std::io::Result<Oid> {
+    write_commit(self, tree, parents, message, options)
+  }
 
-pub fn checkout(oid: &str) -> std::io::Result<()> {
-  let commit = get_commit(oid)?;
-  read_tree(&commit.tree)?;
-  data::set_head(oid)
-}
+  pub fn get_commit(&self, oid: &Oid) -> std::io::Result<Commit> {
+    let mut tree = None;
+    let mut parents = Vec::new();
+    let mut author = None;
+    let mut timestamp = None;
+    let commit = self.get_object(oid, ObjectType::Commit)?;
+    let commit = match String::from_utf8(commit) {
+      Ok(commit) => commit,
+      Err(_) => return Err(Error::new(ErrorKind::InvalidData, format!("Commit contains invalid UTF-8 [{}]", oid)))
+    };
 
-pub fn create_tag(name: &str, oid: &str) -> std::io::Result<()> {
-  let path = data::generate_path(PathVariant::Ref(RefVariant::Tag(name)))?;
-  let ref_value = RefValue { symbolic: false, value: Some(String::from(oid)), path };
-  data::update_ref(&ref_value, true)
-}
+    let mut lines = commit.lines();
+    for line in lines.by_ref() {
+      if line == "" {
+        break;
+      }
 
-pub fn create_branch(name: &str, oid: &str) -> std::io::Result<()> {
-  let path = data::generate_path(PathVariant::Ref(RefVariant::Head(name)))?;
-  let ref_value = RefValue { symbolic: false, value: Some(String::from(oid)), path };
-  data::update_ref(&ref_value, true)
-}
+      let object_parts: Vec<_> = line.splitn(2, " ").collect();
+      if object_parts[0] == "tree" {
+        tree = Some(parse_oid(object_parts.get(1), oid)?);
+      }
+      else if object_parts[0] == "parent" {
+        parents.push(parse_oid(object_parts.get(1), oid)?);
+      }
+      // Both are formatted as "Name <email> timestamp timezone". A line without a signature is skipped like an unknown header.
+      else if let ("author", Some(signature)) = (object_parts[0], object_parts.get(1)) {
+        author = Some(String::from(utils::parse_signature(signature).0));
+      }
+      else if let ("committer", Some(signature)) = (object_parts[0], object_parts.get(1)) {
+        timestamp = utils::parse_signature(signature).1;
+      }
+      // Unknown headers are skipped, so that objects written by other versions of ugit can still be read
+    }
 
-pub fn try_resolve_as_ref(ref_or_oid: &str) -> std::io::Result<String> {
-  let oid = data::locate_ref_or_oid(ref_or_oid);
-  match oid {
-    Some(oid) => {
-      oid
-    },
-    None => Ok(String::from(ref_or_oid))
-  }
-}
+    // The message may be empty, leaving no lines after the headers at all
+    let message = lines.collect::<Vec<_>>().join("\n");
 
-fn write_tree_recursive(path: &Path) -> std::io::Result<String> {
-  if !path.is_dir() {
-    return Err(Error::new(ErrorKind::InvalidInput, format!("Given path [{}] does not point to a directory", path.display())));
+    let tree = match tree {
+      Some(tree) => tree,
+      None => return Err(Error::new(ErrorKind::InvalidData, format!("Missing tree row of commit [{}]", oid)))
+    };
+
+    Ok(
+      Commit {
+        tree,
+        parents,
+        author,
+        timestamp,
+        message,
+      }
+    )
   }
 
-  let mut entries: Vec<(&str, String, String)> = Vec::new();
-  for entry in fs::read_dir(path)? {
-    let entry = entry?;
-    let path = entry.path();
-    let object_type;
-    let oid;
-    if is_ignored(&path) {
-      continue;
-    }
-    else if path.is_file() {
-      let contents = fs::read(&path)?;
-      object_type = "blob";
-      oid = data::hash_object(&contents, ObjectType::Blob)?;
-    }
-    else if path.is_dir() {
-      object_type = "tree";
-      oid = write_tree_recursive(&path)?;
+  // Reads the (mode, type, oid, name) entries of a single tree, without descending into subtrees.
+  pub fn parse_tree(&self, oid: &Oid) -> std::io::Result<Vec<(String, String, Oid, String)>> {
+    let object = self.get_object(oid, ObjectType::Tree)?;
+    let object = match String::from_utf8(object) {
+      Ok(object) => object,
+      Err(_) => return Err(Error::new(ErrorKind::InvalidData, format!("Tree contains invalid UTF-8 [{}]", oid)))
+    };
+
+    // Trees written before entries were null-terminated hold one entry per line
+    let lines: Vec<&str> = if object.contains('\0') {
+      object.split_terminator('\0').collect()
     }
     else {
-      return Err(Error::new(ErrorKind::InvalidInput, format!("write_tree expects only files and directories [{}]", path.display())));
+      object.lines().collect()
+    };
+
+    let mut entries = Vec::new();
+    for line in lines {
+      // Entries are "mode type oid name". Trees written before modes were recorded lack the mode, and so begin with the type.
+      let first_part = line.split(' ').next().unwrap_or("");
+      let (mode, line) = if !first_part.is_empty() && first_part.bytes().all(|b| b.is_ascii_digit()) {
+        let object_parts: Vec<_> = line.splitn(2, ' ').collect();
+        (String::from(object_parts[0]), object_parts.get(1).copied().unwrap_or(""))
+      }
+      else {
+        (String::from(FILE_MODE), line)
+      };
+
+      let object_parts: Vec<String> = line.splitn(3, " ").map(|obj| String::from(obj)).collect();
+      if object_parts.len() < 3 {
+        return Err(Error::new(ErrorKind::InvalidData, format!("Malformed entry in tree [{}]", oid)));
+      }
+
+      // The name is joined onto the path the tree is read into, so it must not be able to reach outside of that directory
+      let name = &object_parts[2];
+      // A backslash only separates paths on Windows, and may be part of a name elsewhere
+      if name.is_empty() || name == "." || name == ".." || name.contains('/') || (cfg!(windows) && name.contains('\\')) {
+        return Err(Error::new(ErrorKind::InvalidData, format!("Invalid entry name [{}] in tree [{}]", name, oid)));
+      }
+
+      let entry_oid = parse_oid(Some(&object_parts[1].as_str()), oid)?;
+      entries.push((mode, object_parts[0].clone(), entry_oid, object_parts[2].clone()));
     }
 
-    let filename = String::from(path.file_name().unwrap().to_str().unwrap());
-    entries.push((object_type, oid, filename));
+    Ok(entries)
   }
 
-  let contents = entries
-      .iter()
-      .map(|entry| format!("{} {} {}", entry.0, entry.1, entry.2))
-      .collect::<Vec<_>>()
-      .join("\n");
+  // Replaces the working directory with the tree, removing every file it does not hold.
+  pub fn read_tree(&self, root_oid: &Oid) -> std::io::Result<()> {
+    self.read_tree_with_progress(root_oid, true, &mut |_| ())
+  }
 
-  let oid = data::hash_object(contents.as_bytes(), ObjectType::Tree)?;
-  Ok(oid)
-}
+  // Calls progress with the number of files read so far, after each file. Unless clean is set, the tree's files are written over
+  // the working directory rather than replacing it, so that files the tree does not hold are left in place.
+  pub fn read_tree_with_progress(&self, root_oid: &Oid, clean: bool, progress: &mut dyn FnMut(usize)) -> std::io::Result<()> {
+    let tree = get_tree_entries(self, root_oid, &self.root)?;
+    // Every blob is read before the working directory is touched, so that a missing object aborts without losing any files. A
+    // file which already holds its blob is neither read nor rewritten, which leaves its modification time alone.
+    let mut files = Vec::new();
+    let mut unchanged = HashSet::new();
+    for (path, oid, mode) in &tree {
+      if mode != SYMLINK_MODE && holds_blob(self, path, oid)? {
+        unchanged.insert(path.clone());
+      }
+      else {
+        files.push((path.clone(), self.get_object(oid, ObjectType::Blob)?, mode.clone()));
+      }
 
-fn get_tree(oid: &str, base_path: &PathBuf) -> std::io::Result<Vec<(PathBuf, String)>> {
-  let mut result = Vec::new();
-  let object = data::get_object(oid, ObjectType::Tree)?;
-  for line in object.lines() {
-    let object_parts: Vec<String> = line.splitn(3, " ").map(|obj| String::from(obj)).collect();
-    let object_type = object_parts[0].clone();
-    let oid = object_parts[1].clone();
-
-    let mut path = base_path.clone();
-    path.push(&object_parts[2]);
-    if object_type == "blob" {
-      result.push((path.clone(), oid));
+      progress(files.len() + unchanged.len());
     }
-    else if object_type == "tree" {
-      let mut recur_results = get_tree(&oid, &path)?;
-      result.append(&mut recur_results);
+
+    if clean {
+      update_working_tree(self, &files, &unchanged)?;
     }
     else {
-      return Err(Error::new(ErrorKind::InvalidInput, format!("Unimplemented object type [{}]", object_type)));
+      for (path, contents, mode) in &files {
+        write_entry(path, contents, mode)?;
+      }
+    }
+
+    // A file already holding its blob may still have been recorded with another mode
+    for (path, _, mode) in &tree {
+      if unchanged.contains(path) {
+        set_file_mode(path, mode)?;
+      }
     }
+
+    sync_index(self, root_oid)
   }
 
-  Ok(result)
-}
+  // Stages the file or directory at path, which may be relative to the current directory. Anything staged beneath path which is no
+  // longer in the working directory is unstaged.
+  pub fn add(&self, path: &Path) -> std::io::Result<()> {
+    let root = &self.root;
+    let key = get_index_key(root, path)?;
+    let full_path = root.join(&key);
+    let mut index = get_index_or_head(self)?;
+    let staged_count = index.len();
+    index.retain(|staged, _| !is_within(staged, &key));
+
+    let ignore_rules = IgnoreRules::load(root, &self.generate_path(PathVariant::Ugit))?;
+    match fs::symlink_metadata(&full_path) {
+      Ok(metadata) if metadata.is_dir() => {
+        for (path, _) in get_working_tree(self, &full_path, &ignore_rules)? {
+          let oid = self.hash_object(&read_entry(&root.join(&path))?, ObjectType::Blob)?;
+          index.insert(get_index_key(root, &root.join(&path))?, oid);
+        }
+      },
+      Ok(_) if !ignore_rules.is_ignored(&full_path) => {
+        let oid = self.hash_object(&read_entry(&full_path)?, ObjectType::Blob)?;
+        index.insert(key, oid);
+      },
+      Ok(_) => return Err(Error::new(ErrorKind::InvalidInput, format!("Path is ignored [{}]", path.display()))),
+      Err(_) if index.len() == staged_count => return Err(Error::new(ErrorKind::NotFound, format!("Path did not match any files [{}]", path.display()))),
+      // A staged file which was deleted from the working directory
+      Err(_) => ()
+    };
 
-// Dangerous function.
-fn empty_current_directory() -> std::io::Result<()> {
-  let mut root = env::current_dir().unwrap();
-  root.push(".ugit");
-  if !root.is_dir() {
-    root.pop();
-    panic!("Tried to empty a directory without a ugit repository: {}", root.display());
+    self.write_index(&index)
   }
 
-  root.pop();
-  for entry in fs::read_dir(root)? {
-    let entry = entry?.path();
-    if is_ignored(&entry) {
-      continue;
+  // Checking out a branch makes HEAD point to it, so that new commits advance the branch. Anything else detaches HEAD.
+  pub fn checkout(&self, name: &str) -> std::io::Result<()> {
+    let oid = resolve_name(self, name, true)?;
+    let commit = self.get_commit(&oid)?;
+    self.read_tree(&commit.tree)?;
+    if is_branch(self, name) {
+      self.set_head(RefTarget::Symbolic(format!("refs/heads/{}", name)), &format!("checkout: moving to {}", name))
     }
-    else if entry.is_file() {
-      fs::remove_file(entry)?;
-    }
-    else if entry.is_dir() {
-      fs::remove_dir_all(entry)?;
+    else {
+      self.set_head(RefTarget::Oid(oid), &format!("checkout: moving to {}", name))
     }
   }
 
-  Ok(())
-}
+  pub fn create_branch(&self, name: &str, oid: &Oid) -> std::io::Result<()> {
+    data::validate_ref_name(name)?;
+    let path = self.generate_path(PathVariant::Ref(RefVariant::Head(name)));
+    let ref_value = RefValue { value: Some(RefTarget::Oid(oid.clone())), path };
+    self.update_ref_with_message(&ref_value, true, &format!("branch: created from {}", oid))
+  }
 
-fn is_ignored(path: &Path) -> bool {
-  path.ends_with(".ugit") || path.ends_with("target")
-}
+  // Compares the working directory against the tree of the commit HEAD resolves to.
+  pub fn status(&self) -> std::io::Result<Status> {
+    let head_ref = self.get_ref(&self.generate_path(PathVariant::Head), false)?;
+    let branch = head_ref.symbolic_target().map(|target| String::from(target.trim_start_matches("refs/heads/")));
 
-#[cfg(test)]
-mod tests {
-  use serial_test::serial;
-  use super::*;
+    let head = match self.get_head() {
+      Some(head) => Some(head?),
+      None => None
+    };
 
-  #[derive(Clone, Debug)]
-  struct DirNode {
-    pub name: String,
-    pub children: Option<DirChildren>,
-  }
+    let committed: HashMap<PathBuf, Oid> = match head {
+      Some(ref head) => get_tree(self, &self.get_commit(head)?.tree, &PathBuf::new())?.into_iter().collect(),
+      None => HashMap::new()
+    };
 
-  #[derive(Clone, Debug)]
-  struct DirChildren(Vec<DirNode>);
-  impl DirChildren {
-    pub fn new(children: &[DirNode]) -> Self {
-      Self { 0: children.to_vec() }
-    }
-  }
+    let (_, working) = self.get_working_tree_oid()?;
 
-  impl IntoIterator for DirChildren {
-    type Item = DirNode;
-    type IntoIter = std::vec::IntoIter<Self::Item>;
-    fn into_iter(self) -> Self::IntoIter {
-      self.0.into_iter()
+    let mut changes = Vec::new();
+    for (path, oid) in &working {
+      match committed.get(path) {
+        None => changes.push((path.clone(), FileStatus::New)),
+        Some(committed_oid) if committed_oid != oid => changes.push((path.clone(), FileStatus::Modified)),
+        _ => ()
+      }
     }
-  }
 
-  impl DirNode {
-    pub fn foreach<F1, F2>(&self, dir_func: F1, else_func: F2)
-    where
-      F1: Fn(&DirNode) -> bool + Copy,
-      F2: Fn(&DirNode) -> bool + Copy,
-    {
-      self.foreach_recursive(&self, dir_func, else_func);
+    for path in committed.keys() {
+      if !working.contains_key(path) {
+        changes.push((path.clone(), FileStatus::Deleted));
+      }
     }
 
-    fn foreach_recursive<F1, F2>(&self, root: &DirNode, dir_func: F1, else_func: F2) -> bool
-    where
-      F1: Fn(&DirNode) -> bool + Copy,
-      F2: Fn(&DirNode) -> bool + Copy,
-    {
+    changes.sort_by(|a, b| a.0.cmp(&b.0));
+    Ok(Status { branch, head, changes })
+  }
 
-      if !dir_func(&root) {
-        return false;
+  // Merges the commit other_oid, referred to as name in the merge commit's message and in conflict markers, into HEAD. Nothing is
+  // merged if a path the merge would change differs from HEAD in the working directory, and files HEAD does not hold are left
+  // alone. Only the author and timestamp of options are used, for the merge commit.
+  pub fn merge(&self, other_oid: &Oid, name: &str, options: &CommitOptions) -> std::io::Result<MergeResult> {
+    let head = match self.get_head() {
+      Some(head) => head?,
+      None => return Err(Error::new(ErrorKind::NotFound, "Cannot merge without a commit to merge into"))
+    };
+
+    let base = self.merge_base(&head, other_oid)?;
+    if base == *other_oid {
+      return Ok(MergeResult::UpToDate);
+    }
+    else if base == head {
+      let head_tree = self.get_commit(&head)?.tree;
+      let other_tree = self.get_commit(other_oid)?.tree;
+      let mut files = Vec::new();
+      for (path, oid, mode) in get_tree_entries(self, &other_tree, Path::new(""))? {
+        files.push((path, self.get_object(&oid, ObjectType::Blob)?, mode));
       }
 
-      env::set_current_dir(&root.name).expect(format!("Cannot cd to {}", &root.name).as_str());
-      if let Some(children) = root.children.clone() {
-        for child in children.into_iter() {
-          let result = if child.children.is_some() {
-            self.foreach_recursive(&child, dir_func, else_func)
-          }
-          else {
-            else_func(&child)
-          };
+      apply_merged_files(self, &head_tree, &files, "merging")?;
+      sync_index(self, &other_tree)?;
+      let path = self.generate_path(PathVariant::Head);
+      let ref_value = RefValue { value: Some(RefTarget::Oid(other_oid.clone())), path };
+      self.update_ref_with_message(&ref_value, true, &format!("merge {}: fast-forward", name))?;
+      return Ok(MergeResult::FastForward);
+    }
 
-          if !result {
-            panic!("Issue within foreach at [{:?}]", child);
-          }
-        }
-      }
+    let base_tree = self.get_commit(&base)?.tree;
+    let head_tree = self.get_commit(&head)?.tree;
+    let other_tree = self.get_commit(other_oid)?.tree;
+    let (files, conflicts) = self.merge_trees(&base_tree, &head_tree, &other_tree, name)?;
+    write_merged_files(self, &head_tree, files, &conflicts, "merging")?;
 
-      env::set_current_dir("..").expect(format!("Cannot cd to dir above {}", &root.name).as_str());
-      true
+    let path = self.generate_path(PathVariant::MergeHead);
+    let ref_value = RefValue { value: Some(RefTarget::Oid(other_oid.clone())), path };
+    self.update_ref(&ref_value, false)?;
+    if !conflicts.is_empty() {
+      return Ok(MergeResult::Conflicts(conflicts));
     }
+
+    let options = CommitOptions { author: options.author.clone(), timestamp: options.timestamp, ..CommitOptions::default() };
+    let oid = self.commit_with_options(&format!("Merge {}", name), &options)?;
+    Ok(MergeResult::Merged(oid))
   }
 
-  impl Default for DirNode {
-    fn default() -> Self {
-      DirNode {
-        name: String::from("TEST"),
-        children: Some(DirChildren::new(&[
-          DirNode {
-            name: String::from("index.html"),
-            children: None,
-          },
-          DirNode {
-            name: String::from("style.css"),
-            children: None,
-          },
-          DirNode {
-            name: String::from("One"),
-            children: Some(DirChildren::new(&[
-              DirNode {
-                name: String::from("Two"),
-                children: Some(DirChildren::new(&[
-                  DirNode {
-                    name: String::from(".SuperSecretFile"),
-                    children: None,
-                  },
-                ])),
-              },
-            ])),
-          },
-        ])),
-      }
+  // Sets the changes in the working directory aside in a commit at refs/stash, whose parent is HEAD, then restores HEAD's tree.
+  // Only one stash is kept, so stashing again before popping is an error. Only the author and timestamp of options are used, for
+  // the stash commit.
+  pub fn stash(&self, options: &CommitOptions) -> std::io::Result<Oid> {
+    let head = match self.get_head() {
+      Some(head) => head?,
+      None => return Err(Error::new(ErrorKind::NotFound, "Cannot stash without a HEAD commit"))
+    };
+
+    let path = self.generate_path(PathVariant::Stash);
+    if path.is_file() {
+      return Err(Error::new(ErrorKind::AlreadyExists, "A stash already exists, pop it before stashing again"));
     }
-  }
 
-  #[test]
-  #[serial]
-  fn empty_current_directory_clears_everything_in_current_directory() {
-    let (_, cleanup) = create_test_directory();
-    assert!(fs::read_dir(".").unwrap().count() > 1);
+    let head_commit = self.get_commit(&head)?;
+    let tree = self.write_tree()?;
+    if head_commit.tree == tree {
+      return Err(Error::new(ErrorKind::InvalidInput, "No local changes to stash"));
+    }
 
-    empty_current_directory().expect("Some issue having to do with emptying the current directory");
-    // The iterator from read_dir will always include at least '.ugit'
-    assert_eq!(fs::read_dir(".").unwrap().count(), 1);
-    cleanup();
+    let message = format!("WIP on {}: {}", &head[..10.min(head.len())], head_commit.message.lines().next().unwrap_or(""));
+    let oid = self.commit_tree(&tree, &[head], &message, options)?;
+    let ref_value = RefValue { value: Some(RefTarget::Oid(oid.clone())), path };
+    self.update_ref_with_message(&ref_value, false, &format!("stash: {}", message))?;
+    self.read_tree(&head_commit.tree)?;
+    Ok(oid)
   }
 
-  #[test]
-  #[serial]
-  fn write_tree_returns_an_oid_of_the_entire_directory() {
-    let (dir_tree, cleanup) = create_test_directory();
-    let expected = "2104e4d38c58b6477d2f901aa07190d55e63fd1f93cf0f309014e272912040b6";
-    let oid = write_tree().expect("Issue when writing tree");
-    assert_eq!(expected, oid);
+  // Applies the stashed changes onto the working directory, merged with any commits made since stashing, and drops the stash.
+  // Files no tree holds are left alone. Returns the paths which conflicted, in which case the stash is kept. Nothing is applied if
+  // any path the stash would change differs from HEAD in the working directory, so that local changes are never overwritten.
+  pub fn stash_pop(&self) -> std::io::Result<Vec<PathBuf>> {
+    let path = self.generate_path(PathVariant::Stash);
+    let stash = match self.get_ref(&path, false)?.oid() {
+      Some(stash) => self.get_commit(stash)?,
+      None => return Err(Error::new(ErrorKind::NotFound, "There is no stash to pop"))
+    };
 
-    let dir_func = |node: &DirNode| {
-      let path = Path::new(&node.name);
-      let oid = write_tree_recursive(&path).expect("Issue when writing tree recursively");
-      let oid_file = data::generate_path(PathVariant::OID(&oid)).expect(format!("Issue when generating a path for OID {}", &oid).as_str());
-      let contents = fs::read_to_string(&oid_file).expect(format!("Issue with reading OID [{}]", oid).as_str());
-      // The file generated from write_tree_recursive represents the directory, and contains the oids, filenames, and directory names within it
-      if let Some(children) = node.children.clone() {
-        for child in children.into_iter() {
-          assert!(contents.contains(&child.name));
-        }
-      }
+    let head = match self.get_head() {
+      Some(head) => head?,
+      None => return Err(Error::new(ErrorKind::NotFound, "Cannot pop a stash without a HEAD commit"))
+    };
 
-      true
+    let base_tree = match stash.parents.first() {
+      Some(parent) => self.get_commit(parent)?.tree,
+      None => return Err(Error::new(ErrorKind::InvalidData, "The stash does not record the commit it was made on"))
     };
 
-    // Assure that each file in dir_tree has been hashed and copied to the ugit repository correctly
-    let file_func = |node: &DirNode| {
-      let original_contents = fs::read(&node.name)
-        .expect(format!("Issue when reading test file {}", node.name).as_str());
+    let head_tree = self.get_commit(&head)?.tree;
+    let (files, conflicts) = self.merge_trees(&base_tree, &head_tree, &stash.tree, "stash")?;
+    apply_merged_files(self, &head_tree, &files, "popping the stash")?;
+    if conflicts.is_empty() {
+      self.delete_ref(&path)?;
+    }
 
-      let oid = data::hash_object(&original_contents, ObjectType::Blob).expect("Issue when hashing object");
-      let oid_file = data::generate_path(PathVariant::OID(&oid)).expect(format!("Issue when generating a path for OID {}", &oid).as_str());
-      let contents = fs::read(&oid_file)
-        .expect("Issue when reading from OID");
+    Ok(conflicts)
+  }
 
-      let content_parts: Vec<_> = contents.splitn(2, |b| *b == 0).collect();
-      assert_eq!(content_parts[1], original_contents);
-      true
-    };
+  // Walks the history of the given commits as the free function of the same name does, reading commits from this repository.
+  pub fn iter_commits_and_parents(&self, starting_oids: Vec<Oid>) -> CommitWalk {
+    CommitWalk { repository: Some(self.clone()), queue: starting_oids.into_iter().collect(), visited: HashSet::new() }
+  }
 
-    let next_tree = DirNode {
-      name: String::from("."),
-      children: dir_tree.children.clone(),
-    };
+  // Finds the first commit which is an ancestor of both given commits (a commit counts as its own ancestor).
+  pub fn merge_base(&self, first_oid: &Oid, second_oid: &Oid) -> std::io::Result<Oid> {
+    let first_ancestors = self.iter_commits_and_parents(vec![first_oid.clone()]).collect::<std::io::Result<HashSet<Oid>>>()?;
+    let mut visited = HashSet::new();
+    let mut queue = VecDeque::new();
+    queue.push_back(second_oid.clone());
+    while let Some(oid) = queue.pop_front() {
+      if first_ancestors.contains(&oid) {
+        return Ok(oid);
+      }
+      else if visited.insert(oid.clone()) {
+        queue.extend(self.get_commit(&oid)?.parents);
+      }
+    }
 
-    next_tree.foreach(dir_func, file_func);
-    env::set_current_dir(&dir_tree.name).expect("Issue when cding to test directory");
-    cleanup();
+    Err(Error::new(ErrorKind::NotFound, format!("Commits {} and {} have no common ancestor", first_oid, second_oid)))
   }
 
-  #[test]
-  #[serial]
-  fn read_tree_replaces_repository_root_with_snapshot_taken_from_write_tree() {
-    let (dir_tree, cleanup) = create_test_directory();
-    let oid = write_tree().expect("Issue when writing tree");
-    empty_current_directory().expect("Issue when emptying root directory");
+  // Three-way merges the trees of two commits against the tree of their merge base, returning the contents of every merged file
+  // (by path relative to the repository root) along with the paths which conflict. A file changed on only one side is taken from
+  // that side. A file changed differently on both sides is written with conflict markers around both versions.
+  pub fn merge_trees(&self, base_oid: &Oid, head_oid: &Oid, other_oid: &Oid, name: &str) -> std::io::Result<(Vec<FileContents>, Vec<PathBuf>)> {
+    // A file's blob and mode are merged together, so that a change to either one is taken from the side which made it
+    let entries = |oid| -> std::io::Result<HashMap<PathBuf, (Oid, String)>> {
+      Ok(get_tree_entries(self, oid, Path::new(""))?.into_iter().map(|(path, oid, mode)| (path, (oid, mode))).collect())
+    };
+
+    let (base, head, other) = (entries(base_oid)?, entries(head_oid)?, entries(other_oid)?);
+
+    let mut paths: Vec<&PathBuf> = base.keys().chain(head.keys()).chain(other.keys()).collect::<HashSet<_>>().into_iter().collect();
+    paths.sort();
+
+    let mut files = Vec::new();
+    let mut conflicts = Vec::new();
+    for path in paths {
+      let (base_blob, head_blob, other_blob) = (base.get(path), head.get(path), other.get(path));
+      let merged_blob = if head_blob == other_blob || other_blob == base_blob {
+        head_blob
+      }
+      else if head_blob == base_blob {
+        other_blob
+      }
+      else {
+        let head_contents = match head_blob {
+          Some((oid, _)) => self.get_object(oid, ObjectType::Blob)?,
+          None => Vec::new()
+        };
+
+        let other_contents = match other_blob {
+          Some((oid, _)) => self.get_object(oid, ObjectType::Blob)?,
+          None => Vec::new()
+        };
+
+        let mut contents = b"<<<<<<< HEAD\n".to_vec();
+        contents.extend(&head_contents);
+        if !head_contents.is_empty() && !head_contents.ends_with(b"\n") {
+          contents.push(b'\n');
+        }
+
+        contents.extend(b"=======\n");
+        contents.extend(&other_contents);
+        if !other_contents.is_empty() && !other_contents.ends_with(b"\n") {
+          contents.push(b'\n');
+        }
+
+        contents.extend(format!(">>>>>>> {}\n", name).as_bytes());
+        // Conflict markers can only be written into a regular file, which keeps HEAD's mode if it has one
+        let mode = match head_blob.or(other_blob) {
+          Some((_, mode)) if mode != SYMLINK_MODE => mode.clone(),
+          _ => String::from(FILE_MODE)
+        };
+
+        files.push((path.clone(), contents, mode));
+        conflicts.push(path.clone());
+        continue;
+      };
+
+      // A file deleted by the merge is simply left out
+      if let Some((oid, mode)) = merged_blob {
+        files.push((path.clone(), self.get_object(oid, ObjectType::Blob)?, mode.clone()));
+      }
+    }
+
+    Ok((files, conflicts))
+  }
+}
+
+pub fn write_tree() -> std::io::Result<Oid> {
+  Repository::find()?.write_tree()
+}
+
+pub fn write_tree_with_progress(progress: &mut dyn FnMut(usize)) -> std::io::Result<Oid> {
+  Repository::find()?.write_tree_with_progress(progress)
+}
+
+pub fn write_subtree(path: &Path) -> std::io::Result<Oid> {
+  Repository::find()?.write_subtree(path)
+}
+
+pub fn write_subtree_with_progress(path: &Path, progress: &mut dyn FnMut(usize)) -> std::io::Result<Oid> {
+  Repository::find()?.write_subtree_with_progress(path, progress)
+}
+
+pub fn read_tree_with_progress(root_oid: &Oid, clean: bool, progress: &mut dyn FnMut(usize)) -> std::io::Result<()> {
+  Repository::find()?.read_tree_with_progress(root_oid, clean, progress)
+}
+
+pub fn add(path: &Path) -> std::io::Result<()> {
+  Repository::find()?.add(path)
+}
+
+// Unstages the file or directory at path, given relative to the current directory, and deletes it from the working directory
+// unless cached is set.
+pub fn remove(path: &Path, cached: bool) -> std::io::Result<()> {
+  let root = data::generate_path(PathVariant::Root)?;
+  let key = get_index_key(&root, path)?;
+  let mut index = data::get_index()?;
+  let removed: Vec<String> = index.keys().filter(|staged| is_within(staged, &key)).cloned().collect();
+  if removed.is_empty() {
+    return Err(Error::new(ErrorKind::NotFound, format!("Path is not staged [{}]", path.display())));
+  }
+
+  for staged in removed {
+    index.remove(&staged);
+    let file = root.join(&staged);
+    if !cached && fs::symlink_metadata(&file).is_ok() {
+      fs::remove_file(&file)?;
+    }
+  }
+
+  data::write_index(&index)
+}
+
+// Moves the file or directory at source to destination, both given relative to the current directory, and moves whatever of it is
+// staged along with it. A destination which is an existing directory receives source within it. Any other existing destination is
+// only replaced when force is set.
+pub fn move_path(source: &Path, destination: &Path, force: bool) -> std::io::Result<()> {
+  let root = data::generate_path(PathVariant::Root)?;
+  let source_key = get_index_key(&root, source)?;
+  if source_key.is_empty() {
+    return Err(Error::new(ErrorKind::InvalidInput, "Cannot move the root of the repository"));
+  }
+
+  let source_path = root.join(&source_key);
+  if fs::symlink_metadata(&source_path).is_err() {
+    return Err(Error::new(ErrorKind::NotFound, format!("Path does not exist [{}]", source.display())));
+  }
+
+  let mut destination_key = get_index_key(&root, destination)?;
+  if root.join(&destination_key).is_dir() {
+    // Can simply unwrap, as a non-empty key always ends in a file name
+    let name = source_path.file_name().unwrap().to_string_lossy();
+    destination_key = if destination_key.is_empty() { name.into_owned() } else { format!("{}/{}", destination_key, name) };
+  }
+
+  if is_within(&destination_key, &source_key) {
+    return Err(Error::new(ErrorKind::InvalidInput, format!("Cannot move [{}] to itself or within itself", source.display())));
+  }
+
+  // Can simply unwrap, as destination_key is never empty by now
+  let destination_path = root.join(&destination_key);
+  if !destination_path.parent().unwrap().is_dir() {
+    return Err(Error::new(ErrorKind::NotFound, format!("Destination's directory does not exist [{}]", destination.display())));
+  }
+
+  if fs::symlink_metadata(&destination_path).is_ok() && !force {
+    return Err(Error::new(ErrorKind::AlreadyExists, format!("Destination already exists [{}], use --force to replace it", destination.display())));
+  }
+
+  fs::rename(&source_path, &destination_path)?;
+
+  let index = data::get_index()?;
+  if index.is_empty() {
+    return Ok(());
+  }
+
+  let moved = index.into_iter()
+    .filter(|(staged, _)| !is_within(staged, &destination_key))
+    .map(|(staged, oid)| if is_within(&staged, &source_key) {
+      (format!("{}{}", destination_key, &staged[source_key.len()..]), oid)
+    }
+    else {
+      (staged, oid)
+    })
+    .collect();
+
+  data::write_index(&moved)
+}
+
+pub fn commit_with_options(message: &str, options: &CommitOptions) -> std::io::Result<Oid> {
+  Repository::find()?.commit_with_options(message, options)
+}
+
+// Commits a snapshot of the whole working directory, even when files have been staged. The index is then brought up to date with
+// the snapshot.
+pub fn commit_all(message: &str, options: &CommitOptions) -> std::io::Result<Oid> {
+  let repository = Repository::find()?;
+  let tree = repository.write_tree()?;
+  let oid = commit_to_head(&repository, &tree, message, options)?;
+  sync_index(&repository, &tree)?;
+  Ok(oid)
+}
+
+pub fn commit_tree(tree: &Oid, parents: &[Oid], message: &str, options: &CommitOptions) -> std::io::Result<Oid> {
+  Repository::find()?.commit_tree(tree, parents, message, options)
+}
+
+fn commit_to_head(repository: &Repository, oid: &Oid, message: &str, options: &CommitOptions) -> std::io::Result<Oid> {
+  let head = match repository.get_head() {
+    Some(head) => Some(head?),
+    None => None
+  };
+
+  // An amended commit is a sibling of the commit it replaces rather than its child
+  let mut parents: Vec<Oid> = match (options.amend, &head) {
+    (false, _) => head.iter().cloned().collect(),
+    (true, Some(head)) => repository.get_commit(head)?.parents,
+    (true, None) => return Err(Error::new(ErrorKind::InvalidInput, "There is no commit to amend, HEAD does not point to one"))
+  };
+
+  // A merge which stopped on conflicts left the other commit in MERGE_HEAD
+  let merge_head_path = repository.generate_path(PathVariant::MergeHead);
+  let merge_head = repository.get_ref(&merge_head_path, false)?.oid().cloned();
+  if let Some(ref merge_head) = merge_head {
+    if options.amend {
+      return Err(Error::new(ErrorKind::InvalidInput, "Cannot amend a commit while a merge is in progress"));
+    }
+
+    parents.push(merge_head.clone());
+  }
+
+  // Concluding a merge records the other parent even when the merged tree matches HEAD's. Amending may change only the message.
+  if let (false, false, Some(head), None) = (options.allow_empty, options.amend, &head, &merge_head) {
+    if repository.get_commit(head)?.tree == *oid {
+      return Err(Error::new(ErrorKind::InvalidInput, format!("Nothing to commit, the tree matches HEAD's [{}]", oid)));
+    }
+  }
+
+  // A cherry-pick which stopped on conflicts left the picked commit in CHERRY_PICK_HEAD, whose author the commit keeps
+  let cherry_pick_head_path = repository.generate_path(PathVariant::CherryPickHead);
+  let picked_author = match repository.get_ref(&cherry_pick_head_path, false)?.oid() {
+    Some(picked) if options.author.is_none() => repository.get_commit(picked)?.author,
+    _ => None
+  };
+
+  let options = CommitOptions { author: options.author.clone().or(picked_author), ..options.clone() };
+  let oid = write_commit(repository, oid, &parents, message, &options)?;
+  // Dereferencing HEAD advances the branch it points to, if any, rather than detaching HEAD
+  let path = repository.generate_path(PathVariant::Head);
+  let ref_value = RefValue { value: Some(RefTarget::Oid(oid.clone())), path };
+  let subject = message.lines().next().unwrap_or("");
+  let action = if options.amend { "commit (amend)" } else { "commit" };
+  repository.update_ref_with_message(&ref_value, true, &format!("{}: {}", action, subject))?;
+  if merge_head_path.is_file() {
+    fs::remove_file(&merge_head_path)?;
+  }
+
+  if cherry_pick_head_path.is_file() {
+    fs::remove_file(&cherry_pick_head_path)?;
+  }
+
+  Ok(oid)
+}
+
+// The tree and parents must already be stored, as a tree and commits respectively
+fn write_commit(repository: &Repository, tree: &Oid, parents: &[Oid], message: &str, options: &CommitOptions) -> std::io::Result<Oid> {
+  if repository.get_object_type(tree)? != ObjectType::Tree {
+    return Err(Error::new(ErrorKind::InvalidInput, format!("Can only commit a tree [{}]", tree)));
+  }
+
+  let mut commit = format!("tree {}\n", tree);
+  for parent in parents {
+    if repository.get_object_type(parent)? != ObjectType::Commit {
+      return Err(Error::new(ErrorKind::InvalidInput, format!("A parent must be a commit [{}]", parent)));
+    }
+
+    commit.push_str(&format!("parent {}\n", parent));
+  }
+
+  // Times are always recorded in UTC
+  let author = match options.author {
+    Some(ref author) => validate_author(author)?,
+    None => get_author(repository)?
+  };
+
+  let timestamp = match options.timestamp {
+    Some(timestamp) => timestamp,
+    None => get_commit_timestamp()?
+  };
+
+  commit.push_str(&format!("author {} {} +0000\n", author, timestamp));
+  commit.push_str(&format!("committer {} {} +0000\n", author, timestamp));
+  commit.push_str(&format!("\n{}", message));
+  repository.hash_object(commit.as_bytes(), ObjectType::Commit)
+}
+
+pub fn get_commit(oid: &Oid) -> std::io::Result<Commit> {
+  Repository::find()?.get_commit(oid)
+}
+
+// Walks breadth-first over every parent of the given commits, yielding each reachable commit once. Commits already visited are
+// skipped, so the walk terminates even if a commit's history loops back onto itself. Each commit is only read once the walk
+// reaches it, so stopping early reads none of the history beyond.
+pub fn iter_commits_and_parents(starting_oids: Vec<Oid>) -> CommitWalk {
+  CommitWalk { repository: None, queue: starting_oids.into_iter().collect(), visited: HashSet::new() }
+}
+
+pub struct CommitWalk {
+  // The repository found from the current directory is read when none was given
+  repository: Option<Repository>,
+  queue: VecDeque<Oid>,
+  visited: HashSet<Oid>,
+}
+
+impl Iterator for CommitWalk {
+  type Item = std::io::Result<Oid>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    while let Some(oid) = self.queue.pop_front() {
+      if !self.visited.insert(oid.clone()) {
+        continue;
+      }
+
+      let commit = match self.repository {
+        Some(ref repository) => repository.get_commit(&oid),
+        None => get_commit(&oid)
+      };
+
+      match commit {
+        Ok(commit) => {
+          self.queue.extend(commit.parents);
+          return Some(Ok(oid));
+        },
+        // The parents of a commit which can't be read are unknown, so the walk ends with it
+        Err(err) => {
+          self.queue.clear();
+          return Some(Err(err));
+        }
+      }
+    }
+
+    None
+  }
+}
+
+// Orders the commits walked by iter_commits_and_parents newest first by the time they were made, as git log --date-order does.
+// Commits made at the same time keep the order they were walked in, and those made before timestamps were recorded come last.
+pub fn iter_commits_by_date(starting_oids: Vec<Oid>) -> std::io::Result<impl Iterator<Item=Oid>> {
+  let mut commits = Vec::new();
+  for oid in iter_commits_and_parents(starting_oids) {
+    let oid = oid?;
+    commits.push((get_commit(&oid)?.timestamp, oid));
+  }
+
+  // A stable sort, so that ties stay in walk order
+  commits.sort_by(|(first, _), (second, _)| second.cmp(first));
+  Ok(commits.into_iter().map(|(_, oid)| oid))
+}
+
+// Orders the commits walked by iter_commits_and_parents so that each comes after all of its children, which the breadth-first walk
+// doesn't guarantee once branches of different lengths meet. Otherwise, commits keep the order they were walked in.
+pub fn iter_commits_topologically(starting_oids: Vec<Oid>) -> std::io::Result<impl Iterator<Item=Oid>> {
+  let oids = iter_commits_and_parents(starting_oids).collect::<std::io::Result<Vec<Oid>>>()?;
+  let mut parents = HashMap::new();
+  let mut children_left: HashMap<Oid, usize> = HashMap::new();
+  for oid in &oids {
+    let commit_parents = get_commit(oid)?.parents;
+    for parent in &commit_parents {
+      *children_left.entry(parent.clone()).or_insert(0) += 1;
+    }
+
+    parents.insert(oid.clone(), commit_parents);
+  }
+
+  let positions: HashMap<&Oid, usize> = oids.iter().enumerate().map(|(i, oid)| (oid, i)).collect();
+  let mut ready: BinaryHeap<Reverse<usize>> = oids.iter().enumerate()
+    .filter(|(_, oid)| !children_left.contains_key(*oid))
+    .map(|(i, _)| Reverse(i))
+    .collect();
+  let mut sorted = Vec::new();
+  while let Some(Reverse(i)) = ready.pop() {
+    for parent in &parents[&oids[i]] {
+      let left = children_left.get_mut(parent).unwrap();
+      *left -= 1;
+      if *left == 0 {
+        ready.push(Reverse(positions[parent]));
+      }
+    }
+
+    sorted.push(oids[i].clone());
+  }
+
+  Ok(sorted.into_iter())
+}
+
+pub fn checkout(name: &str) -> std::io::Result<()> {
+  Repository::find()?.checkout(name)
+}
+
+// Overwrites the file at path, given relative to the current directory, with its version in HEAD's tree. A directory restores every
+// file HEAD's tree holds beneath it. Nothing else in the working directory, including HEAD and the index, is changed.
+pub fn checkout_path(path: &Path) -> std::io::Result<()> {
+  let root = data::generate_path(PathVariant::Root)?;
+  let key = get_index_key(&root, path)?;
+  let head = match data::get_head() {
+    Some(head) => head?,
+    None => return Err(Error::new(ErrorKind::NotFound, "Cannot check out a path without a HEAD commit"))
+  };
+
+  let entries: Vec<_> = get_tree_entries(&Repository::find()?, &get_commit(&head)?.tree, Path::new(""))?
+    .into_iter()
+    .filter(|(entry_path, _, _)| is_within(&entry_path.to_string_lossy().replace('\\', "/"), &key))
+    .collect();
+
+  if entries.is_empty() {
+    return Err(Error::new(ErrorKind::NotFound, format!("Path is not in HEAD's tree [{}]", path.display())));
+  }
+
+  for (entry_path, oid, mode) in entries {
+    let contents = data::get_object(&oid, ObjectType::Blob)?;
+    let full_path = root.join(&entry_path);
+    clear_path(&full_path)?;
+
+    if mode == SYMLINK_MODE {
+      create_symlink(&full_path, &contents)?;
+    }
+    else {
+      fs::write(&full_path, contents)?;
+      set_file_mode(&full_path, &mode)?;
+    }
+  }
+
+  Ok(())
+}
+
+// An existing tag is only overwritten when force is set.
+pub fn create_tag(name: &str, oid: &Oid, force: bool) -> std::io::Result<()> {
+  data::validate_ref_name(name)?;
+  let path = get_new_tag_path(name, force)?;
+  let ref_value = RefValue { value: Some(RefTarget::Oid(oid.clone())), path };
+  data::update_ref_with_message(&ref_value, true, &format!("tag: created from {}", oid))
+}
+
+// Stores a tag object holding the message and tagger, and points the tag's ref at that object rather than at the commit.
+pub fn create_annotated_tag(name: &str, oid: &Oid, message: &str, force: bool) -> std::io::Result<Oid> {
+  data::validate_ref_name(name)?;
+  // Checked before the tag object is stored, so that a duplicate name leaves nothing behind
+  get_new_tag_path(name, force)?;
+  let tagger = get_author(&Repository::find()?)?;
+  let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+  let tag = format!("object {}\ntype commit\ntag {}\ntagger {} {} +0000\n\n{}", oid, name, tagger, timestamp, message);
+  let tag_oid = data::hash_object(tag.as_bytes(), ObjectType::Tag)?;
+  create_tag(name, &tag_oid, force)?;
+  Ok(tag_oid)
+}
+
+// Parses the OID a header of the object oid holds.
+fn parse_oid(value: Option<&&str>, oid: &Oid) -> std::io::Result<Oid> {
+  match value.map(|value| value.parse()) {
+    Some(Ok(value)) => Ok(value),
+    _ => Err(Error::new(ErrorKind::InvalidData, format!("Malformed OID [{}] in object [{}]", value.unwrap_or(&""), oid)))
+  }
+}
+
+pub fn get_tag(oid: &Oid) -> std::io::Result<Tag> {
+  let tag = data::get_object(oid, ObjectType::Tag)?;
+  let tag = match String::from_utf8(tag) {
+    Ok(tag) => tag,
+    Err(_) => return Err(Error::new(ErrorKind::InvalidData, format!("Tag contains invalid UTF-8 [{}]", oid)))
+  };
+
+  let mut object = None;
+  let mut name = String::new();
+  let mut tagger = None;
+  let mut timestamp = None;
+  let mut lines = tag.lines();
+  for line in lines.by_ref() {
+    if line.is_empty() {
+      break;
+    }
+
+    let object_parts: Vec<_> = line.splitn(2, ' ').collect();
+    match (object_parts[0], object_parts.get(1)) {
+      ("object", value) => object = Some(parse_oid(value, oid)?),
+      ("tag", Some(value)) => name = String::from(*value),
+      // Formatted like a commit's committer, as "Name <email> timestamp timezone"
+      ("tagger", Some(value)) => {
+        let (identity, tagged_at) = utils::parse_signature(value);
+        tagger = Some(String::from(identity));
+        timestamp = tagged_at;
+      },
+      _ => ()
+    };
+  }
+
+  let object = match object {
+    Some(object) => object,
+    None => return Err(Error::new(ErrorKind::InvalidData, format!("Missing object row of tag [{}]", oid)))
+  };
+
+  let message = lines.collect::<Vec<_>>().join("\n");
+  Ok(Tag { message, name, object, tagger, timestamp })
+}
+
+// Reads every stored object, checking that it hashes back to its OID and that the objects it refers to exist: the tree and parents
+// of a commit, the entries of a tree, and the object of a tag.
+pub fn fsck() -> std::io::Result<Vec<ObjectProblem>> {
+  let repository = Repository::find()?;
+  let mut problems = Vec::new();
+  for oid in repository.list_objects()? {
+    let object_type = match repository.verify_object(&oid) {
+      Ok(object_type) => object_type,
+      Err(err) => {
+        problems.push(ObjectProblem::Corrupt(oid, err.to_string()));
+        continue;
+      }
+    };
+
+    match get_references(&repository, &oid, object_type) {
+      Ok(references) => for reference in references {
+        if !repository.objects().contains(&reference) {
+          problems.push(ObjectProblem::Missing(oid.clone(), reference));
+        }
+      },
+      Err(err) => problems.push(ObjectProblem::Corrupt(oid, err.to_string()))
+    };
+  }
+
+  Ok(problems)
+}
+
+// Counts the objects in the repository, along with how much space they take up and how many there are of each type.
+pub fn count_objects() -> std::io::Result<ObjectCounts> {
+  let repository = Repository::find()?;
+  let objects = repository.objects();
+  let mut counts = ObjectCounts { count: 0, size: 0, types: BTreeMap::new() };
+  for oid in objects.list_objects()? {
+    counts.count += 1;
+    counts.size += objects.stored_size(&oid)?;
+    *counts.types.entry(data::type_header(repository.get_object_type(&oid)?)).or_insert(0) += 1;
+  }
+
+  Ok(counts)
+}
+
+// Deletes every object which cannot be reached from HEAD, a ref, MERGE_HEAD, a reflog entry or the index, returning their OIDs. With
+// dry_run set, the objects are only listed. A reachable object which cannot be read aborts the collection before anything is deleted,
+// as whatever it refers to cannot be known.
+pub fn gc(dry_run: bool) -> std::io::Result<Vec<Oid>> {
+  let repository = Repository::find()?;
+  let mut queue = Vec::new();
+  for (_, ref_value) in repository.iter_refs(false)? {
+    queue.extend(repository.get_ref(&ref_value.path, true)?.oid().cloned());
+    for entry in repository.read_reflog(&ref_value.path)? {
+      queue.push(entry.old);
+      queue.push(entry.new);
+    }
+  }
+
+  queue.extend(repository.get_ref(&repository.generate_path(PathVariant::MergeHead), true)?.oid().cloned());
+  queue.extend(repository.get_ref(&repository.generate_path(PathVariant::CherryPickHead), true)?.oid().cloned());
+  queue.extend(repository.get_index()?.values().cloned());
+
+  let mut reachable = HashSet::new();
+  while let Some(oid) = queue.pop() {
+    // Missing objects, such as the zeros a reflog records for a new ref, are left for fsck to report
+    if !repository.objects().contains(&oid) || !reachable.insert(oid.clone()) {
+      continue;
+    }
+
+    let object_type = repository.get_object_type(&oid)?;
+    queue.extend(get_references(&repository, &oid, object_type)?);
+  }
+
+  let objects = repository.objects();
+  let mut unreachable = Vec::new();
+  for oid in objects.list_objects()? {
+    if reachable.contains(&oid) {
+      continue;
+    }
+
+    if !dry_run {
+      objects.delete_object(&oid)?;
+    }
+
+    unreachable.push(oid);
+  }
+
+  Ok(unreachable)
+}
+
+// The objects an object refers to: the tree and parents of a commit, the entries of a tree, and the object of a tag.
+fn get_references(repository: &Repository, oid: &Oid, object_type: ObjectType) -> std::io::Result<Vec<Oid>> {
+  match object_type {
+    ObjectType::Blob => Ok(Vec::new()),
+    ObjectType::Commit => repository.get_commit(oid).map(|commit| {
+      let mut references = vec![commit.tree];
+      references.extend(commit.parents);
+      references
+    }),
+    ObjectType::Tag => get_tag(oid).map(|tag| vec![tag.object]),
+    ObjectType::Tree => parse_tree(oid).map(|entries| entries.into_iter().map(|(_, _, oid, _)| oid).collect()),
+  }
+}
+
+// Writes the history of every branch and tag as a stream git fast-import accepts. Each commit lists all of its files, after clearing
+// those of its parent, and is committed to the first branch it is reachable from. The branches and tags are then reset to their
+// commits. Annotated tags are exported as plain tags of the commit they tag.
+pub fn fast_export(out: &mut impl Write) -> std::io::Result<()> {
+  let mut marks: HashMap<Oid, usize> = HashMap::new();
+  let mut resets = Vec::new();
+  for (name, ref_value) in data::iter_refs(true)? {
+    let oid = match ref_value.oid() {
+      Some(oid) if name.starts_with("refs/heads/") || name.starts_with("refs/tags/") => data::peel_tag(oid.clone())?,
+      _ => continue
+    };
+
+    let mut history: Vec<Oid> = iter_commits_topologically(vec![oid.clone()])?.collect();
+    // Parents must be written before their children
+    history.reverse();
+    for commit_oid in history {
+      if !marks.contains_key(&commit_oid) {
+        export_commit(out, &name, &commit_oid, &mut marks)?;
+      }
+    }
+
+    resets.push((name, marks[&oid]));
+  }
+
+  for (name, mark) in resets {
+    writeln!(out, "reset {}\nfrom :{}\n", name, mark)?;
+  }
+
+  Ok(())
+}
+
+// Writes the blobs of the commit which haven't been written yet, followed by the commit itself. Every object written is given the
+// next mark, by which later commands refer to it.
+fn export_commit(out: &mut impl Write, ref_name: &str, oid: &Oid, marks: &mut HashMap<Oid, usize>) -> std::io::Result<()> {
+  let commit = get_commit(oid)?;
+  let files = get_tree_entries(&Repository::find()?, &commit.tree, Path::new(""))?;
+  for (_, blob_oid, _) in &files {
+    if !marks.contains_key(blob_oid) {
+      let contents = data::get_object(blob_oid, ObjectType::Blob)?;
+      let mark = marks.len() + 1;
+      writeln!(out, "blob\nmark :{}\ndata {}", mark, contents.len())?;
+      out.write_all(&contents)?;
+      writeln!(out)?;
+      marks.insert(blob_oid.clone(), mark);
+    }
+  }
+
+  // fast-import requires an email, which commits made without one lack
+  let author = match commit.author {
+    Some(ref author) if author.contains('<') => author.clone(),
+    Some(ref author) => format!("{} <>", author),
+    None => String::from("ugit <>")
+  };
+
+  let mark = marks.len() + 1;
+  writeln!(out, "commit {}\nmark :{}", ref_name, mark)?;
+  writeln!(out, "author {} {} +0000", author, commit.timestamp.unwrap_or(0))?;
+  writeln!(out, "committer {} {} +0000", author, commit.timestamp.unwrap_or(0))?;
+  writeln!(out, "data {}\n{}", commit.message.len(), commit.message)?;
+  for (i, parent) in commit.parents.iter().enumerate() {
+    writeln!(out, "{} :{}", if i == 0 { "from" } else { "merge" }, marks[parent])?;
+  }
+
+  writeln!(out, "deleteall")?;
+  for (path, blob_oid, mode) in &files {
+    let path = path.components().map(|component| component.as_os_str().to_string_lossy()).collect::<Vec<_>>().join("/");
+    writeln!(out, "M {} :{} {}", mode, marks[blob_oid], quote_export_path(&path))?;
+  }
+
+  writeln!(out)?;
+  marks.insert(oid.clone(), mark);
+  Ok(())
+}
+
+// fast-import reads a path up to the end of its line, so one containing a newline, or starting with a quote, must be quoted.
+fn quote_export_path(path: &str) -> String {
+  if !path.contains('\n') && !path.starts_with('"') {
+    return String::from(path);
+  }
+
+  let escaped = path.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n");
+  format!("\"{}\"", escaped)
+}
+
+pub fn create_branch(name: &str, oid: &Oid) -> std::io::Result<()> {
+  Repository::find()?.create_branch(name, oid)
+}
+
+// Refuses to delete the branch HEAD points to, as HEAD would be left pointing at nothing.
+pub fn delete_branch(name: &str) -> std::io::Result<()> {
+  data::validate_ref_name(name)?;
+  let head_path = data::generate_path(PathVariant::Head)?;
+  let head_ref = data::get_ref(&head_path, false)?;
+  if head_ref.symbolic_target() == Some(&format!("refs/heads/{}", name)) {
+    return Err(Error::new(ErrorKind::InvalidInput, format!("Cannot delete branch '{}', as HEAD points to it", name)));
+  }
+
+  data::delete_ref(&data::generate_path(PathVariant::Ref(RefVariant::Head(name)))?)
+}
+
+// Moves the branch old to the name new, keeping HEAD on it when HEAD pointed to old. The branch's reflog is not carried over.
+pub fn rename_branch(old: &str, new: &str) -> std::io::Result<()> {
+  data::validate_ref_name(old)?;
+  data::validate_ref_name(new)?;
+  let old_path = data::generate_path(PathVariant::Ref(RefVariant::Head(old)))?;
+  let new_path = data::generate_path(PathVariant::Ref(RefVariant::Head(new)))?;
+  if new_path.exists() {
+    return Err(Error::new(ErrorKind::AlreadyExists, format!("Branch '{}' already exists", new)));
+  }
+
+  let target = match data::get_ref_by_name(RefVariant::Head(old), false)?.value {
+    Some(target) => target,
+    None => return Err(Error::new(ErrorKind::NotFound, format!("Branch '{}' does not exist", old)))
+  };
+
+  let message = format!("branch: renamed {} to {}", old, new);
+  let ref_value = RefValue { value: Some(target), path: new_path };
+  data::update_ref_with_message(&ref_value, false, &message)?;
+  data::delete_ref(&old_path)?;
+
+  let head_ref = data::get_ref(&data::generate_path(PathVariant::Head)?, false)?;
+  if head_ref.symbolic_target() == Some(&format!("refs/heads/{}", old)) {
+    data::set_head(RefTarget::Symbolic(format!("refs/heads/{}", new)), &message)?;
+  }
+
+  Ok(())
+}
+
+fn get_new_tag_path(name: &str, force: bool) -> std::io::Result<PathBuf> {
+  let path = data::generate_path(PathVariant::Ref(RefVariant::Tag(name)))?;
+  if path.is_file() && !force {
+    return Err(Error::new(ErrorKind::AlreadyExists, format!("Tag '{}' already exists, use --force to replace it", name)));
+  }
+
+  Ok(path)
+}
+
+// Returns the name of every tag, sorted.
+// Only the tags whose names match pattern are listed when one is given, with '*' and '?' matching as they do in .ugitignore.
+pub fn list_tags(pattern: Option<&str>) -> std::io::Result<Vec<String>> {
+  let mut tags: Vec<String> = data::iter_refs(false)?
+    .into_iter()
+    .filter_map(|(name, _)| name.strip_prefix("refs/tags/").map(String::from))
+    .filter(|name| match pattern {
+      Some(pattern) => utils::glob_match(pattern, name),
+      None => true
+    })
+    .collect();
+
+  tags.sort();
+  Ok(tags)
+}
+
+// Maps the OID of each commit a ref points to onto the names log annotates it with: HEAD first, as "HEAD -> main" when it points
+// to a branch, then branches, then tags as "tag: v1". An annotated tag is listed against the commit it tags.
+pub fn get_ref_decorations() -> std::io::Result<HashMap<Oid, Vec<String>>> {
+  let head = data::get_ref(&data::generate_path(PathVariant::Head)?, false)?;
+  let head_branch = head.symbolic_target().map(String::from);
+
+  let mut decorations: HashMap<Oid, Vec<String>> = HashMap::new();
+  for (name, ref_value) in data::iter_refs(true)? {
+    let oid = match ref_value.oid() {
+      Some(oid) => data::peel_tag(oid.clone())?,
+      None => continue
+    };
+
+    let decoration = if name == "HEAD" {
+      match head_branch {
+        Some(ref branch) => format!("HEAD -> {}", branch.trim_start_matches("refs/heads/")),
+        None => name
+      }
+    }
+    // Already shown alongside HEAD
+    else if head_branch.as_ref() == Some(&name) {
+      continue;
+    }
+    else if let Some(branch) = name.strip_prefix("refs/heads/") {
+      String::from(branch)
+    }
+    else if let Some(tag) = name.strip_prefix("refs/tags/") {
+      format!("tag: {}", tag)
+    }
+    else {
+      name
+    };
+
+    decorations.entry(oid).or_default().push(decoration);
+  }
+
+  Ok(decorations)
+}
+
+pub fn delete_tag(name: &str) -> std::io::Result<()> {
+  data::validate_ref_name(name)?;
+  data::delete_ref(&data::generate_path(PathVariant::Ref(RefVariant::Tag(name)))?)
+}
+
+// An annotated tag resolves to the commit it tags.
+pub fn try_resolve_as_ref(ref_or_oid: &str) -> std::io::Result<Oid> {
+  resolve_name(&Repository::find()?, ref_or_oid, true)
+}
+
+// Like try_resolve_as_ref, but an annotated tag resolves to the tag object itself rather than the commit it tags.
+pub fn resolve_object(ref_or_oid: &str) -> std::io::Result<Oid> {
+  resolve_name(&Repository::find()?, ref_or_oid, false)
+}
+
+// Annotated tags are peeled down to the commit they tag when peel is set. A name which is neither a ref nor the prefix of an
+// object's OID must be a full OID itself.
+fn resolve_name(repository: &Repository, ref_or_oid: &str, peel: bool) -> std::io::Result<Oid> {
+  let oid = repository.locate_ref_or_oid(ref_or_oid, peel);
+  let oid = match oid {
+    Some(oid) => {
+      oid?
+    },
+    // Not a ref, so it may be an abbreviated OID
+    None if utils::is_hex(ref_or_oid) && ref_or_oid.len() < repository.object_format.oid_length() => {
+      let oid = repository.resolve_oid_prefix(ref_or_oid)?;
+      if peel {
+        repository.peel_tag(oid)?
+      }
+      else {
+        oid
+      }
+    },
+    None => ref_or_oid.parse()?
+  };
+
+  Ok(oid)
+}
+
+// Names oid after the nearest tag reachable from it, walking its ancestors breadth-first. The tag's name is enough when it points at
+// oid itself; otherwise it is followed by the number of commits reachable from oid but not from the tagged one, and oid
+// abbreviated, as "v1-2-gabcdef1234". When several tags point at the same commit, the first by name is used.
+pub fn describe(oid: &Oid) -> std::io::Result<String> {
+  let mut tags: HashMap<Oid, String> = HashMap::new();
+  for (name, ref_value) in data::iter_refs(true)? {
+    if let (Some(tag), Some(tag_oid)) = (name.strip_prefix("refs/tags/"), ref_value.oid()) {
+      // Refs are listed sorted, so the first tag by name is kept
+      tags.entry(data::peel_tag(tag_oid.clone())?).or_insert_with(|| String::from(tag));
+    }
+  }
+
+  for ancestor in iter_commits_and_parents(vec![oid.clone()]) {
+    let ancestor = ancestor?;
+    if let Some(tag) = tags.get(&ancestor) {
+      if ancestor == *oid {
+        return Ok(tag.clone());
+      }
+
+      let tagged = iter_commits_and_parents(vec![ancestor]).collect::<std::io::Result<HashSet<Oid>>>()?;
+      let mut distance = 0;
+      for commit in iter_commits_and_parents(vec![oid.clone()]) {
+        if !tagged.contains(&commit?) {
+          distance += 1;
+        }
+      }
+      return Ok(format!("{}-{}-g{}", tag, distance, &oid[..10.min(oid.len())]));
+    }
+  }
+
+  Err(Error::new(ErrorKind::NotFound, format!("No tag can describe [{}]", oid)))
+}
+
+// Resolves a ref, HEAD, or a full or abbreviated OID to a full OID. Unlike try_resolve_as_ref, a name matching none of those is
+// an error rather than being passed through.
+pub fn rev_parse(name: &str) -> std::io::Result<Oid> {
+  match data::locate_ref_or_oid(name, true) {
+    Some(oid) => oid,
+    None if utils::is_hex(name) && name.len() < Repository::find()?.object_format.oid_length() => {
+      data::peel_tag(data::resolve_oid_prefix(name)?)
+    },
+    None => Err(Error::new(ErrorKind::NotFound, format!("Unknown revision [{}]", name)))
+  }
+}
+
+// Like rev_parse, but the OID must also name an object the repository holds, as a ref may point to an object since deleted.
+pub fn verify_revision(name: &str) -> std::io::Result<Oid> {
+  let oid = rev_parse(name)?;
+  if !Repository::find()?.objects().contains(&oid) {
+    return Err(Error::new(ErrorKind::NotFound, format!("[{}] resolves to an object which does not exist [{}]", name, oid)));
+  }
+
+  Ok(oid)
+}
+
+pub fn status() -> std::io::Result<Status> {
+  Repository::find()?.status()
+}
+
+// Lists the files which differ between two trees, sorted by path. Trees are compared file by file, so a path which is a directory
+// in one tree and a file in the other shows up as the directory's files being removed and the file being added (or vice versa).
+pub fn diff_trees(from_oid: &Oid, to_oid: &Oid) -> std::io::Result<Vec<(PathBuf, ChangeKind)>> {
+  let repository = Repository::find()?;
+  let from: HashMap<PathBuf, Oid> = get_tree(&repository, from_oid, &PathBuf::new())?.into_iter().collect();
+  let to: HashMap<PathBuf, Oid> = get_tree(&repository, to_oid, &PathBuf::new())?.into_iter().collect();
+
+  let mut changes = Vec::new();
+  for (path, oid) in &to {
+    match from.get(path) {
+      None => changes.push((path.clone(), ChangeKind::Added(oid.clone()))),
+      Some(from_oid) if from_oid != oid => changes.push((path.clone(), ChangeKind::Modified(from_oid.clone(), oid.clone()))),
+      _ => ()
+    }
+  }
+
+  for (path, oid) in &from {
+    if !to.contains_key(path) {
+      changes.push((path.clone(), ChangeKind::Removed(oid.clone())));
+    }
+  }
+
+  changes.sort_by(|a, b| a.0.cmp(&b.0));
+  Ok(changes)
+}
+
+// Lists the changes a commit introduced on top of its first parent. A root commit is compared against an empty tree, so all of
+// its files show as added.
+pub fn diff_commit(commit: &Commit) -> std::io::Result<Vec<(PathBuf, ChangeKind)>> {
+  if let Some(parent) = commit.parents.first() {
+    return diff_trees(&get_commit(parent)?.tree, &commit.tree);
+  }
+
+  let mut changes: Vec<_> = get_tree(&Repository::find()?, &commit.tree, &PathBuf::new())?
+    .into_iter()
+    .map(|(path, oid)| (path, ChangeKind::Added(oid)))
+    .collect();
+
+  changes.sort_by(|a, b| a.0.cmp(&b.0));
+  Ok(changes)
+}
+
+// Finds the most recent commit to change the file at path, given relative to the current directory, walking back from HEAD. A commit
+// changed the file unless one of its parents holds the same blob for it, in which case the walk carries on from that parent.
+pub fn blame(path: &Path) -> std::io::Result<Oid> {
+  let key = get_index_key(&data::generate_path(PathVariant::Root)?, path)?;
+  let mut oid = match data::get_head() {
+    Some(head) => head?,
+    None => return Err(Error::new(ErrorKind::NotFound, "Cannot blame a path without a HEAD commit"))
+  };
+
+  let blob = match find_blob(&get_commit(&oid)?.tree, &key)? {
+    Some(blob) => blob,
+    None => return Err(Error::new(ErrorKind::NotFound, format!("Path is not a file in HEAD's tree [{}]", path.display())))
+  };
+
+  loop {
+    let mut unchanged_parent = None;
+    for parent in get_commit(&oid)?.parents {
+      if find_blob(&get_commit(&parent)?.tree, &key)?.as_ref() == Some(&blob) {
+        unchanged_parent = Some(parent);
+        break;
+      }
+    }
+
+    match unchanged_parent {
+      Some(parent) => oid = parent,
+      None => return Ok(oid)
+    };
+  }
+}
+
+// Looks up the blob a tree holds for an index key, descending only into the subtrees along the way.
+fn find_blob(tree_oid: &Oid, key: &str) -> std::io::Result<Option<Oid>> {
+  let (name, rest) = match key.split_once('/') {
+    Some((name, rest)) => (name, Some(rest)),
+    None => (key, None)
+  };
+
+  for (_, object_type, oid, entry_name) in parse_tree(tree_oid)? {
+    if entry_name != name {
+      continue;
+    }
+
+    return match (object_type.as_str(), rest) {
+      ("blob", None) => Ok(Some(oid)),
+      ("tree", Some(rest)) => find_blob(&oid, rest),
+      _ => Ok(None)
+    };
+  }
+
+  Ok(None)
+}
+
+// Accepts either a commit, in which case the OID of its tree is returned, or a tree.
+pub fn resolve_tree_oid(oid: &Oid) -> std::io::Result<Oid> {
+  match data::get_object_type(oid)? {
+    ObjectType::Commit => Ok(get_commit(oid)?.tree),
+    _ => Ok(oid.clone())
+  }
+}
+
+// Lists the (mode, type, oid, path) entries of a tree, or of a commit's tree. Only the tree's own entries are listed, unless
+// recursive is set, in which case every file beneath it is listed by its path relative to the tree.
+pub fn ls_tree(oid: &Oid, recursive: bool) -> std::io::Result<Vec<(String, String, Oid, PathBuf)>> {
+  let tree = resolve_tree_oid(oid)?;
+  if recursive {
+    Ok(get_tree_entries(&Repository::find()?, &tree, Path::new(""))?
+      .into_iter()
+      .map(|(path, oid, mode)| (mode, String::from("blob"), oid, path))
+      .collect())
+  }
+  else {
+    Ok(parse_tree(&tree)?
+      .into_iter()
+      .map(|(mode, object_type, oid, name)| (mode, object_type, oid, PathBuf::from(name)))
+      .collect())
+  }
+}
+
+// The author is taken from the UGIT_AUTHOR_NAME and UGIT_AUTHOR_EMAIL environment variables, falling back to the user.name and
+// user.email config values.
+fn get_author(repository: &Repository) -> std::io::Result<String> {
+  let name = match env::var("UGIT_AUTHOR_NAME") {
+    Ok(name) => name,
+    Err(_) => repository.get_config("user.name")?.unwrap_or_else(|| String::from("Unknown"))
+  };
+
+  let email = match env::var("UGIT_AUTHOR_EMAIL") {
+    Ok(email) => email,
+    Err(_) => repository.get_config("user.email")?.unwrap_or_else(|| String::from("unknown"))
+  };
+
+  Ok(format!("{} <{}>", name, email))
+}
+
+// An author given explicitly must be a name followed by an email in angle brackets, on a single line.
+fn validate_author(author: &str) -> std::io::Result<String> {
+  let valid = match author.find(" <") {
+    Some(i) => i > 0 && author.ends_with('>') && !author.contains('\n'),
+    None => false
+  };
+
+  if !valid {
+    return Err(Error::new(ErrorKind::InvalidInput, format!("Author must be given as \"Name <email>\" [{}]", author)));
+  }
+
+  Ok(String::from(author))
+}
+
+// The UGIT_COMMIT_DATE environment variable, holding seconds since the Unix epoch, overrides the current time.
+fn get_commit_timestamp() -> std::io::Result<u64> {
+  match env::var("UGIT_COMMIT_DATE") {
+    Ok(date) => date.trim().parse().map_err(|_| {
+      Error::new(ErrorKind::InvalidInput, format!("UGIT_COMMIT_DATE must be a number of seconds since the Unix epoch [{}]", date))
+    }),
+    Err(_) => Ok(SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs())
+  }
+}
+
+// Moves HEAD, or the branch HEAD points to, to the given commit. Unlike checkout, the working directory is left untouched.
+pub fn reset(oid: &Oid) -> std::io::Result<()> {
+  if let Err(err) = get_commit(oid) {
+    return match err.kind() {
+      ErrorKind::InvalidData => Err(Error::new(ErrorKind::InvalidInput, format!("Cannot reset to {}, as it is not a commit", oid))),
+      _ => Err(err)
+    };
+  }
+
+  let path = data::generate_path(PathVariant::Head)?;
+  let ref_value = RefValue { value: Some(RefTarget::Oid(oid.clone())), path };
+  data::update_ref_with_message(&ref_value, true, &format!("reset: moving to {}", oid))
+}
+
+// Returns the reflog of HEAD, a branch, a tag, or a ref given by its full name such as "refs/heads/main", oldest entry first.
+pub fn get_reflog(name: &str) -> std::io::Result<Vec<ReflogEntry>> {
+  let path = if name == "HEAD" || name == "@" {
+    data::generate_path(PathVariant::Head)?
+  }
+  else if name.starts_with("refs/") {
+    // Each part of a full name is checked, so that the path joined from it stays beneath .ugit/refs
+    for part in name.split('/').skip(1) {
+      data::validate_ref_name(part)?;
+    }
+
+    data::generate_path(PathVariant::Ugit)?.join(name)
+  }
+  else {
+    data::validate_ref_name(name)?;
+    if is_branch(&Repository::find()?, name) {
+      data::generate_path(PathVariant::Ref(RefVariant::Head(name)))?
+    }
+    else {
+      data::generate_path(PathVariant::Ref(RefVariant::Tag(name)))?
+    }
+  };
+
+  if !path.is_file() {
+    return Err(Error::new(ErrorKind::NotFound, format!("Ref does not exist [{}]", name)));
+  }
+
+  data::read_reflog(&path)
+}
+
+pub fn merge(other_oid: &Oid, name: &str) -> std::io::Result<MergeResult> {
+  Repository::find()?.merge(other_oid, name, &CommitOptions::default())
+}
+
+// Applies the changes the commit oid made to its first parent onto HEAD, and commits them with the commit's message and author. The
+// changes are merged with the parent as the base, so that a file changed since the parent conflicts as it would in a merge. Nothing
+// is applied if a path the changes touch differs from HEAD in the working directory.
+pub fn cherry_pick(oid: &Oid, name: &str) -> std::io::Result<CherryPickResult> {
+  let head = match data::get_head() {
+    Some(head) => head?,
+    None => return Err(Error::new(ErrorKind::NotFound, "Cannot cherry-pick without a commit to apply the changes to"))
+  };
+
+  // The commit would otherwise record the commit being merged as a parent
+  if data::generate_path(PathVariant::MergeHead)?.is_file() {
+    return Err(Error::new(ErrorKind::InvalidInput, "Cannot cherry-pick while a merge is in progress"));
+  }
+
+  let cherry_pick_head_path = data::generate_path(PathVariant::CherryPickHead)?;
+  if cherry_pick_head_path.is_file() {
+    return Err(Error::new(ErrorKind::InvalidInput, "Cannot cherry-pick while another cherry-pick is in progress"));
+  }
+
+  let commit = get_commit(oid)?;
+  // A commit without parents introduced every file it holds
+  let base_tree = match commit.parents.first() {
+    Some(parent) => get_commit(parent)?.tree,
+    None => data::hash_object(b"", ObjectType::Tree)?
+  };
+
+  let head_tree = get_commit(&head)?.tree;
+  let (files, conflicts) = merge_trees(&base_tree, &head_tree, &commit.tree, name)?;
+  write_merged_files(&Repository::find()?, &head_tree, files, &conflicts, "cherry-picking")?;
+  if !conflicts.is_empty() {
+    // Kept so that the commit concluding the cherry-pick takes the picked commit's message and author
+    let ref_value = RefValue { value: Some(RefTarget::Oid(oid.clone())), path: cherry_pick_head_path };
+    data::update_ref(&ref_value, false)?;
+    return Ok(CherryPickResult::Conflicts(conflicts));
+  }
+
+  let options = CommitOptions { author: commit.author, ..CommitOptions::default() };
+  Ok(CherryPickResult::Picked(commit_with_options(&commit.message, &options)?))
+}
+
+// Returns the commit a cherry-pick which stopped on conflicts is applying, whose message and author the next commit takes.
+pub fn get_picked_commit() -> std::io::Result<Option<Commit>> {
+  let path = data::generate_path(PathVariant::CherryPickHead)?;
+  match data::get_ref(&path, false)?.oid() {
+    Some(oid) => Ok(Some(get_commit(oid)?)),
+    None => Ok(None)
+  }
+}
+
+// Writes the files merged onto head_tree over the working directory, as apply_merged_files does. When files are being staged, the
+// merged files are staged for the next commit. Conflicting files keep head_tree's version until they are resolved and staged.
+fn write_merged_files(repository: &Repository, head_tree: &Oid, files: Vec<FileContents>, conflicts: &[PathBuf], action: &str) -> std::io::Result<()> {
+  apply_merged_files(repository, head_tree, &files, action)?;
+  let root = &repository.root;
+  if !repository.get_index()?.is_empty() {
+    let head_files: HashMap<PathBuf, Oid> = get_tree(repository, head_tree, &PathBuf::new())?.into_iter().collect();
+    let mut index = BTreeMap::new();
+    for (path, contents, _) in &files {
+      let oid = if conflicts.contains(path) {
+        match head_files.get(path) {
+          Some(oid) => oid.clone(),
+          None => continue
+        }
+      }
+      else {
+        repository.hash_object(contents, ObjectType::Blob)?
+      };
+
+      index.insert(get_index_key(root, &root.join(path))?, oid);
+    }
+
+    repository.write_index(&index)?;
+  }
+
+  Ok(())
+}
+
+pub fn stash() -> std::io::Result<Oid> {
+  Repository::find()?.stash(&CommitOptions::default())
+}
+
+pub fn stash_pop() -> std::io::Result<Vec<PathBuf>> {
+  Repository::find()?.stash_pop()
+}
+
+pub fn merge_base(first_oid: &Oid, second_oid: &Oid) -> std::io::Result<Oid> {
+  Repository::find()?.merge_base(first_oid, second_oid)
+}
+
+pub fn merge_trees(base_oid: &Oid, head_oid: &Oid, other_oid: &Oid, name: &str) -> std::io::Result<(Vec<FileContents>, Vec<PathBuf>)> {
+  Repository::find()?.merge_trees(base_oid, head_oid, other_oid, name)
+}
+
+fn is_branch(repository: &Repository, name: &str) -> bool {
+  repository.generate_path(PathVariant::Ref(RefVariant::Head(name))).is_file()
+}
+
+fn write_tree_recursive(repository: &Repository, path: &Path, ignore_rules: &IgnoreRules, progress: &mut dyn FnMut(usize)) -> std::io::Result<Oid> {
+  snapshot_tree(repository, path, ignore_rules, true, &mut HashMap::new(), progress)
+}
+
+// Builds the tree of the directory at path, recording in files the OID of every file beneath it, relative to the repository root.
+// Objects are only written to the object database when persist is set, otherwise their OIDs are merely computed. progress is called
+// with the number of files recorded, after each one.
+fn snapshot_tree(repository: &Repository, path: &Path, ignore_rules: &IgnoreRules, persist: bool, files: &mut HashMap<PathBuf, Oid>, progress: &mut dyn FnMut(usize)) -> std::io::Result<Oid> {
+  if !path.is_dir() {
+    return Err(Error::new(ErrorKind::InvalidInput, format!("Given path [{}] does not point to a directory", path.display())));
+  }
+
+  let mut entries: Vec<(&str, &str, Oid, String)> = Vec::new();
+  for entry in fs::read_dir(path)? {
+    let entry = entry?;
+    let path = entry.path();
+    let mode;
+    let object_type;
+    let oid;
+    // Symlinks are not followed, so a link to a directory is stored as a link rather than as a copy of the directory
+    let file_type = fs::symlink_metadata(&path)?.file_type();
+    if ignore_rules.is_ignored(&path) {
+      continue;
+    }
+    else if file_type.is_symlink() {
+      mode = SYMLINK_MODE;
+      object_type = "blob";
+      oid = store_object(repository, &read_symlink(&path)?, ObjectType::Blob, persist)?;
+    }
+    else if file_type.is_file() {
+      let contents = fs::read(&path)?;
+      mode = get_file_mode(&path)?;
+      object_type = "blob";
+      oid = store_object(repository, &contents, ObjectType::Blob, persist)?;
+    }
+    else if file_type.is_dir() {
+      mode = TREE_MODE;
+      object_type = "tree";
+      oid = snapshot_tree(repository, &path, ignore_rules, persist, files, progress)?;
+    }
+    else {
+      return Err(Error::new(ErrorKind::InvalidInput, format!("write_tree expects only files and directories [{}]", path.display())));
+    }
+
+    if object_type == "blob" {
+      files.insert(path.strip_prefix(&repository.root).unwrap_or(&path).to_path_buf(), oid.clone());
+      progress(files.len());
+    }
+
+    let filename = String::from(path.file_name().unwrap().to_str().unwrap());
+    entries.push((mode, object_type, oid, filename));
+  }
+
+  let contents = format_tree(entries);
+  store_object(repository, contents.as_bytes(), ObjectType::Tree, persist)
+}
+
+fn store_object(repository: &Repository, contents: &[u8], object_type: ObjectType, persist: bool) -> std::io::Result<Oid> {
+  if persist {
+    repository.hash_object(contents, object_type)
+  }
+  else {
+    Ok(repository.get_oid(contents, object_type))
+  }
+}
+
+// Stores a tree object made of (mode, type, oid, name) entries.
+fn hash_tree(repository: &Repository, entries: Vec<(&str, &str, Oid, String)>) -> std::io::Result<Oid> {
+  repository.hash_object(format_tree(entries).as_bytes(), ObjectType::Tree)
+}
+
+fn format_tree(mut entries: Vec<(&str, &str, Oid, String)>) -> String {
+  // read_dir's order depends on the platform and filesystem, so entries are sorted to make the tree's OID reproducible
+  entries.sort_by(|a, b| a.3.cmp(&b.3));
+
+  // Each entry ends with a null byte rather than a newline, which the name of a file may contain
+  entries
+      .iter()
+      .map(|entry| format!("{} {} {} {}\0", entry.0, entry.1, entry.2, entry.3))
+      .collect()
+}
+
+// Writes the tree of the directory dir out of index entries, given as paths relative to dir. The working directory is only
+// consulted for the mode of each file.
+fn write_index_tree(repository: &Repository, dir: &Path, entries: Vec<(&str, &Oid)>) -> std::io::Result<Oid> {
+  let mut subtrees: BTreeMap<&str, Vec<(&str, &Oid)>> = BTreeMap::new();
+  let mut tree_entries = Vec::new();
+  for (path, oid) in entries {
+    let parts: Vec<_> = path.splitn(2, '/').collect();
+    if parts.len() == 2 {
+      subtrees.entry(parts[0]).or_default().push((parts[1], oid));
+    }
+    else {
+      tree_entries.push((get_entry_mode(&dir.join(path)), "blob", oid.clone(), String::from(path)));
+    }
+  }
+
+  for (name, entries) in subtrees {
+    let oid = write_index_tree(repository, &dir.join(name), entries)?;
+    tree_entries.push((TREE_MODE, "tree", oid, String::from(name)));
+  }
+
+  hash_tree(repository, tree_entries)
+}
+
+// Replaces the index with the files of the given tree, if files are being staged at all.
+fn sync_index(repository: &Repository, tree_oid: &Oid) -> std::io::Result<()> {
+  if repository.get_index()?.is_empty() {
+    return Ok(());
+  }
+
+  let root = &repository.root;
+  let mut index = BTreeMap::new();
+  for (path, oid) in get_tree(repository, tree_oid, &PathBuf::new())? {
+    index.insert(get_index_key(root, &root.join(path))?, oid);
+  }
+
+  repository.write_index(&index)
+}
+
+// Returns the index, or, when nothing is staged yet, the files of HEAD's tree, so that staging a file does not leave the other
+// tracked files out of the next commit.
+fn get_index_or_head(repository: &Repository) -> std::io::Result<BTreeMap<String, Oid>> {
+  let index = repository.get_index()?;
+  if !index.is_empty() {
+    return Ok(index);
+  }
+
+  let root = &repository.root;
+  let mut index = BTreeMap::new();
+  if let Some(head) = repository.get_head() {
+    for (path, oid) in get_tree(repository, &repository.get_commit(&head?)?.tree, &PathBuf::new())? {
+      index.insert(get_index_key(root, &root.join(path))?, oid);
+    }
+  }
+
+  Ok(index)
+}
+
+// Index keys are paths relative to the repository root, separated by '/'. The given path may be relative to the current directory.
+fn get_index_key(root: &Path, path: &Path) -> std::io::Result<String> {
+  // The current directory is only looked up when it is needed, so that absolute paths work even if it has been removed
+  let joined = if path.is_relative() {
+    env::current_dir()?.join(path)
+  }
+  else {
+    path.to_path_buf()
+  };
+
+  let mut absolute = PathBuf::new();
+  for component in joined.components() {
+    match component {
+      Component::CurDir => (),
+      Component::ParentDir => {
+        absolute.pop();
+      },
+      component => absolute.push(component)
+    };
+  }
+
+  match absolute.strip_prefix(root) {
+    Ok(relative) => Ok(relative.components().map(|component| component.as_os_str().to_string_lossy()).collect::<Vec<_>>().join("/")),
+    Err(_) => Err(Error::new(ErrorKind::InvalidInput, format!("Path is outside of the repository [{}]", path.display())))
+  }
+}
+
+// Whether the index key staged is key itself or lies beneath it. The empty key is the repository root.
+fn is_within(staged: &str, key: &str) -> bool {
+  key.is_empty() || staged == key || staged.starts_with(&format!("{}/", key))
+}
+
+// Returns the contents a file is stored with: a symlink's target, or a regular file's bytes.
+fn read_entry(path: &Path) -> std::io::Result<Vec<u8>> {
+  if fs::symlink_metadata(path)?.file_type().is_symlink() {
+    read_symlink(path)
+  }
+  else {
+    fs::read(path)
+  }
+}
+
+// A file missing from the working directory is recorded as a regular file.
+fn get_entry_mode(path: &Path) -> &'static str {
+  match fs::symlink_metadata(path) {
+    Ok(metadata) if metadata.file_type().is_symlink() => SYMLINK_MODE,
+    Ok(_) => get_file_mode(path).unwrap_or(FILE_MODE),
+    Err(_) => FILE_MODE
+  }
+}
+
+// Maps the path of every file beneath path, relative to the repository root, to the OID its contents would be stored under.
+// Nothing is written to the object database.
+fn get_working_tree(repository: &Repository, path: &Path, ignore_rules: &IgnoreRules) -> std::io::Result<HashMap<PathBuf, Oid>> {
+  let mut files = HashMap::new();
+  snapshot_tree(repository, path, ignore_rules, false, &mut files, &mut |_| ())?;
+  Ok(files)
+}
+
+fn get_tree(repository: &Repository, oid: &Oid, base_path: &PathBuf) -> std::io::Result<Vec<(PathBuf, Oid)>> {
+  let entries = get_tree_entries(repository, oid, base_path)?;
+  Ok(entries.into_iter().map(|(path, oid, _)| (path, oid)).collect())
+}
+
+// Like get_tree, but also returns the mode each file was recorded with.
+fn get_tree_entries(repository: &Repository, oid: &Oid, base_path: &Path) -> std::io::Result<Vec<(PathBuf, Oid, String)>> {
+  let mut result = Vec::new();
+  for (mode, object_type, oid, name) in repository.parse_tree(oid)? {
+    let mut path = base_path.to_path_buf();
+    path.push(&name);
+    if object_type == "blob" {
+      result.push((path.clone(), oid, mode));
+    }
+    else if object_type == "tree" {
+      let mut recur_results = get_tree_entries(repository, &oid, &path)?;
+      result.append(&mut recur_results);
+    }
+    else {
+      return Err(Error::new(ErrorKind::InvalidInput, format!("Unimplemented object type [{}]", object_type)));
+    }
+  }
+
+  Ok(result)
+}
+
+pub fn parse_tree(oid: &Oid) -> std::io::Result<Vec<(String, String, Oid, String)>> {
+  Repository::find()?.parse_tree(oid)
+}
+
+// Writes the merged files, given by path relative to the repository root, whose contents or mode differ from head_tree over the
+// working directory, and removes the files of head_tree the merge drops. Files head_tree does not hold are left alone. Nothing is
+// written if any path this would change differs from head_tree in the working directory, so that local changes are never
+// overwritten by the action named in the error.
+fn apply_merged_files(repository: &Repository, head_tree: &Oid, files: &[FileContents], action: &str) -> std::io::Result<()> {
+  let head_entries: HashMap<PathBuf, (Oid, String)> = get_tree_entries(repository, head_tree, Path::new(""))?
+    .into_iter()
+    .map(|(path, oid, mode)| (path, (oid, mode)))
+    .collect();
+
+  let kept: HashSet<&PathBuf> = files.iter().map(|(path, _, _)| path).collect();
+  let removed: Vec<&PathBuf> = head_entries.keys().filter(|path| !kept.contains(path)).collect();
+  let changed: Vec<&FileContents> = files
+    .iter()
+    .filter(|(path, contents, mode)| head_entries.get(path) != Some(&(repository.get_oid(contents, ObjectType::Blob), mode.clone())))
+    .collect();
+
+  let (_, working) = repository.get_working_tree_oid()?;
+  for path in changed.iter().map(|(path, _, _)| path).chain(removed.iter().copied()) {
+    if working.get(path) != head_entries.get(path).map(|(oid, _)| oid) {
+      return Err(Error::new(ErrorKind::InvalidInput, format!("Local changes to [{}] would be overwritten by {}", path.display(), action)));
+    }
+  }
+
+  for path in removed {
+    let path = repository.root.join(path);
+    if fs::symlink_metadata(&path).is_ok() {
+      fs::remove_file(&path)?;
+    }
+
+    // Directories left empty go with the file, while those still holding untracked files stay
+    for dir in path.ancestors().skip(1).take_while(|dir| *dir != repository.root) {
+      if fs::remove_dir(dir).is_err() {
+        break;
+      }
+    }
+  }
+
+  for (path, contents, mode) in changed {
+    write_entry(&repository.root.join(path), contents, mode)?;
+  }
+
+  Ok(())
+}
+
+// Only the executable bit is recorded. On platforms without Unix permissions, every file is a regular file.
+#[cfg(unix)]
+fn get_file_mode(path: &Path) -> std::io::Result<&'static str> {
+  use std::os::unix::fs::PermissionsExt;
+  if fs::metadata(path)?.permissions().mode() & 0o111 != 0 {
+    Ok(EXECUTABLE_MODE)
+  }
+  else {
+    Ok(FILE_MODE)
+  }
+}
+
+#[cfg(not(unix))]
+fn get_file_mode(_path: &Path) -> std::io::Result<&'static str> {
+  Ok(FILE_MODE)
+}
+
+fn read_symlink(path: &Path) -> std::io::Result<Vec<u8>> {
+  let target = fs::read_link(path)?;
+  match target.to_str() {
+    Some(target) => Ok(target.as_bytes().to_vec()),
+    None => Err(Error::new(ErrorKind::InvalidData, format!("Symlink target is not valid UTF-8 [{}]", path.display())))
+  }
+}
+
+#[cfg(unix)]
+fn create_symlink(path: &Path, target: &[u8]) -> std::io::Result<()> {
+  let target = String::from_utf8_lossy(target);
+  std::os::unix::fs::symlink(target.as_ref(), path)
+}
+
+// Without Unix symlinks, the link is written as a plain file holding its target, as git does
+#[cfg(not(unix))]
+fn create_symlink(path: &Path, target: &[u8]) -> std::io::Result<()> {
+  fs::write(path, target)
+}
+
+#[cfg(unix)]
+fn set_file_mode(path: &Path, mode: &str) -> std::io::Result<()> {
+  use std::os::unix::fs::PermissionsExt;
+  let permissions = if mode == EXECUTABLE_MODE {
+    0o755
+  }
+  else {
+    0o644
+  };
+
+  fs::set_permissions(path, fs::Permissions::from_mode(permissions))
+}
+
+#[cfg(not(unix))]
+fn set_file_mode(_path: &Path, _mode: &str) -> std::io::Result<()> {
+  Ok(())
+}
+
+// Makes the working directory hold exactly the given files and the unchanged files (besides ignored paths): any other file is
+// removed, the given files are replaced, and the unchanged files are left as they are.
+fn update_working_tree(repository: &Repository, files: &[FileContents], unchanged: &HashSet<PathBuf>) -> std::io::Result<()> {
+  let keep: HashSet<PathBuf> = files.iter().map(|(path, _, _)| path.clone()).chain(unchanged.iter().cloned()).collect();
+  empty_current_directory(repository, &keep)?;
+  for (path, contents, mode) in files {
+    write_entry(path, contents, mode)?;
+  }
+
+  Ok(())
+}
+
+// Replaces whatever is at path with a file or symlink, depending on mode. Anything already there is removed first, so that a
+// symlink is never written through.
+fn write_entry(path: &Path, contents: &[u8], mode: &str) -> std::io::Result<()> {
+  clear_path(path)?;
+  if mode == SYMLINK_MODE {
+    create_symlink(path, contents)
+  }
+  else {
+    fs::write(path, contents)?;
+    set_file_mode(path, mode)
+  }
+}
+
+// Whether path is a regular file, not a symlink or directory, whose contents are the blob oid.
+fn holds_blob(repository: &Repository, path: &Path, oid: &Oid) -> std::io::Result<bool> {
+  match fs::symlink_metadata(path) {
+    Ok(metadata) if metadata.is_file() => Ok(repository.get_oid(&fs::read(path)?, ObjectType::Blob) == *oid),
+    _ => Ok(false)
+  }
+}
+
+// Makes way for a file to be written at path: whatever is there is removed, rather than written through if it is a symlink, and
+// the directories leading to it are created.
+fn clear_path(path: &Path) -> std::io::Result<()> {
+  match fs::symlink_metadata(path) {
+    Ok(metadata) if metadata.is_dir() => fs::remove_dir_all(path),
+    Ok(_) => fs::remove_file(path),
+    Err(_) => fs::create_dir_all(path.parent().unwrap())
+  }
+}
+
+// Dangerous function. Removes everything in the repository root except ignored paths and the files in keep.
+fn empty_current_directory(repository: &Repository, keep: &HashSet<PathBuf>) -> std::io::Result<()> {
+  let ignore_rules = IgnoreRules::load(&repository.root, &repository.generate_path(PathVariant::Ugit))?;
+  empty_directory(&repository.root, keep, &ignore_rules)?;
+  Ok(())
+}
+
+// Returns whether the directory was left empty.
+fn empty_directory(path: &Path, keep: &HashSet<PathBuf>, ignore_rules: &IgnoreRules) -> std::io::Result<bool> {
+  let mut is_empty = true;
+  for entry in fs::read_dir(path)? {
+    let entry = entry?.path();
+    let is_dir = fs::symlink_metadata(&entry)?.is_dir();
+    if ignore_rules.is_ignored(&entry) || (keep.contains(&entry) && !is_dir) {
+      is_empty = false;
+    }
+    else if is_dir {
+      if empty_directory(&entry, keep, ignore_rules)? {
+        fs::remove_dir(&entry)?;
+      }
+      else {
+        is_empty = false;
+      }
+    }
+    else {
+      fs::remove_file(&entry)?;
+    }
+  }
+
+  Ok(is_empty)
+}
+
+// Decides which paths are left out of snapshots, and left alone when the working directory is emptied. Besides the .ugit directory
+// itself, every line of the .ugitignore file at the repository root which is neither blank nor a #comment is a glob pattern. A
+// pattern containing a '/' is matched against the whole path relative to the root, while any other pattern is matched against
+// the name of each file and directory.
+struct IgnoreRules {
+  root: PathBuf,
+  // The repository directory. Any directory of the same name is ignored, wherever it is found.
+  git_dir: PathBuf,
+  patterns: Vec<String>,
+}
+
+impl IgnoreRules {
+  fn load(root: &Path, git_dir: &Path) -> std::io::Result<Self> {
+    let mut patterns = Vec::new();
+    let path = root.join(".ugitignore");
+    if path.is_file() {
+      for line in fs::read_to_string(&path)?.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+          continue;
+        }
+
+        patterns.push(String::from(line.trim_end_matches('/')));
+      }
+    }
+
+    Ok(Self { root: root.to_path_buf(), git_dir: git_dir.to_path_buf(), patterns })
+  }
+
+  fn is_ignored(&self, path: &Path) -> bool {
+    if path.file_name() == self.git_dir.file_name() || path.ends_with("target") {
+      return true;
+    }
+
+    let path = if path.is_relative() {
+      env::current_dir().unwrap().join(path)
+    }
+    else {
+      path.to_path_buf()
+    };
+
+    let relative_path = match path.strip_prefix(&self.root) {
+      Ok(relative_path) => relative_path.to_string_lossy().replace('\\', "/"),
+      Err(_) => return false
+    };
+
+    let name = match path.file_name() {
+      Some(name) => name.to_string_lossy(),
+      None => return false
+    };
+
+    self.patterns.iter().any(|pattern| {
+      if pattern.contains('/') {
+        utils::glob_match(pattern.trim_start_matches('/'), &relative_path)
+      }
+      else {
+        utils::glob_match(pattern, &name)
+      }
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+#![allow(non_snake_case)]
+  use serial_test::serial;
+  use super::*;
+  use data::ObjectFormat;
+
+  #[derive(Clone, Debug)]
+  struct DirNode {
+    pub name: String,
+    pub children: Option<DirChildren>,
+  }
+
+  #[derive(Clone, Debug)]
+  struct DirChildren(Vec<DirNode>);
+  impl DirChildren {
+    pub fn new(children: &[DirNode]) -> Self {
+      Self { 0: children.to_vec() }
+    }
+  }
+
+  impl IntoIterator for DirChildren {
+    type Item = DirNode;
+    type IntoIter = std::vec::IntoIter<Self::Item>;
+    fn into_iter(self) -> Self::IntoIter {
+      self.0.into_iter()
+    }
+  }
+
+  impl DirNode {
+    pub fn foreach<F1, F2>(&self, dir_func: F1, else_func: F2)
+    where
+      F1: Fn(&DirNode) -> bool + Copy,
+      F2: Fn(&DirNode) -> bool + Copy,
+    {
+      self.foreach_recursive(&self, dir_func, else_func);
+    }
+
+    fn foreach_recursive<F1, F2>(&self, root: &DirNode, dir_func: F1, else_func: F2) -> bool
+    where
+      F1: Fn(&DirNode) -> bool + Copy,
+      F2: Fn(&DirNode) -> bool + Copy,
+    {
+
+      if !dir_func(&root) {
+        return false;
+      }
+
+      env::set_current_dir(&root.name).expect(format!("Cannot cd to {}", &root.name).as_str());
+      if let Some(children) = root.children.clone() {
+        for child in children.into_iter() {
+          let result = if child.children.is_some() {
+            self.foreach_recursive(&child, dir_func, else_func)
+          }
+          else {
+            else_func(&child)
+          };
+
+          if !result {
+            panic!("Issue within foreach at [{:?}]", child);
+          }
+        }
+      }
+
+      env::set_current_dir("..").expect(format!("Cannot cd to dir above {}", &root.name).as_str());
+      true
+    }
+  }
+
+  impl Default for DirNode {
+    fn default() -> Self {
+      DirNode {
+        name: String::from("TEST"),
+        children: Some(DirChildren::new(&[
+          DirNode {
+            name: String::from("index.html"),
+            children: None,
+          },
+          DirNode {
+            name: String::from("style.css"),
+            children: None,
+          },
+          DirNode {
+            name: String::from("One"),
+            children: Some(DirChildren::new(&[
+              DirNode {
+                name: String::from("Two"),
+                children: Some(DirChildren::new(&[
+                  DirNode {
+                    name: String::from(".SuperSecretFile"),
+                    children: None,
+                  },
+                ])),
+              },
+            ])),
+          },
+        ])),
+      }
+    }
+  }
+
+  #[test]
+  #[serial]
+  fn empty_current_directory_clears_everything_in_current_directory() {
+    let (_, cleanup) = create_test_directory();
+    assert!(fs::read_dir(".").unwrap().count() > 1);
+
+    empty_current_directory(&Repository::find().unwrap(), &HashSet::new()).expect("Some issue having to do with emptying the current directory");
+    // The iterator from read_dir will always include at least '.ugit'
+    assert_eq!(fs::read_dir(".").unwrap().count(), 1);
+    cleanup();
+  }
+
+  #[test]
+  #[serial]
+  fn write_tree_returns_an_oid_of_the_entire_directory() {
+    let (dir_tree, cleanup) = create_test_directory();
+    let expected = "4a57d5bfce7b63ca9643c5639953d8bff87aa32cc85723795f3c95cefe0bbdef";
+    let oid = write_tree().expect("Issue when writing tree");
+    assert_eq!(oid, expected);
+
+    let dir_func = |node: &DirNode| {
+      let path = Path::new(&node.name);
+      let repository = Repository::find().unwrap();
+      let ignore_rules = IgnoreRules::load(&repository.root, &repository.generate_path(PathVariant::Ugit)).expect("Issue when loading ignore rules");
+      let oid = write_tree_recursive(&repository, &path, &ignore_rules, &mut |_| ()).expect("Issue when writing tree recursively");
+      let contents = data::get_object(&oid, ObjectType::Tree).expect(format!("Issue with reading OID [{}]", oid).as_str());
+      let contents = String::from_utf8(contents).expect("Tree contains invalid UTF-8");
+      // The file generated from write_tree_recursive represents the directory, and contains the oids, filenames, and directory names within it
+      if let Some(children) = node.children.clone() {
+        for child in children.into_iter() {
+          assert!(contents.contains(&child.name));
+        }
+      }
+
+      true
+    };
+
+    // Assure that each file in dir_tree has been hashed and copied to the ugit repository correctly
+    let file_func = |node: &DirNode| {
+      let original_contents = fs::read(&node.name)
+        .expect(format!("Issue when reading test file {}", node.name).as_str());
+
+      let oid = data::hash_object(&original_contents, ObjectType::Blob).expect("Issue when hashing object");
+      let contents = data::get_object(&oid, ObjectType::Blob).expect("Issue when reading from OID");
+      assert_eq!(contents, original_contents);
+      true
+    };
+
+    let next_tree = DirNode {
+      name: String::from("."),
+      children: dir_tree.children.clone(),
+    };
+
+    next_tree.foreach(dir_func, file_func);
+    env::set_current_dir(&dir_tree.name).expect("Issue when cding to test directory");
+    cleanup();
+  }
+
+  #[test]
+  #[serial]
+  fn read_tree_replaces_repository_root_with_snapshot_taken_from_write_tree() {
+    let (dir_tree, cleanup) = create_test_directory();
+    let repository = Repository::find().unwrap();
+    let oid = write_tree().expect("Issue when writing tree");
+    empty_current_directory(&repository, &HashSet::new()).expect("Issue when emptying root directory");
     assert_eq!(fs::read_dir(".").unwrap().count(), 1);
 
-    read_tree(&oid).expect("Issue when restoring from write_tree snapshot");
-    let dir_func = |node: &DirNode| {
-      let path = Path::new(&node.name);
-      assert!(path.is_dir());
-      true
+    repository.read_tree(&oid).expect("Issue when restoring from write_tree snapshot");
+    let dir_func = |node: &DirNode| {
+      let path = Path::new(&node.name);
+      assert!(path.is_dir());
+      true
+    };
+
+    let file_func = |node: &DirNode| {
+      let path = Path::new(&node.name);
+      assert!(path.is_file());
+      true
+    };
+
+    let next_tree = DirNode {
+      name: String::from("."),
+      children: dir_tree.children.clone(),
+    };
+
+    next_tree.foreach(dir_func, file_func);
+    env::set_current_dir(&dir_tree.name).expect("Issue when cding to test directory");
+    cleanup();
+  }
+
+  #[test]
+  #[serial]
+  fn init_commit_and_log_work_end_to_end() {
+    let (_, cleanup) = create_test_directory();
+    let first_oid = commit("First", false).expect("Issue when committing");
+    fs::write("style.css", "body {}").expect("Issue when writing test file");
+    let second_oid = commit("Second\nWith a body", false).expect("Issue when committing");
+
+    let head = try_resolve_as_ref("@").expect("Issue when resolving HEAD");
+    assert_eq!(head, second_oid);
+    let oids: Vec<_> = iter_commits_and_parents(vec![head]).collect::<std::io::Result<_>>().expect("Issue when walking commits");
+    assert_eq!(oids, vec![second_oid.clone(), first_oid.clone()]);
+    assert_eq!(get_commit(&second_oid).unwrap().message, "Second\nWith a body");
+    assert_eq!(get_commit(&first_oid).unwrap().message, "First");
+    cleanup();
+  }
+
+  #[test]
+  #[serial]
+  fn first_commit_creates_the_branch_HEAD_points_to_after_init() {
+    let (_, cleanup) = create_test_directory();
+    let oid = commit("First", false).expect("Issue when committing");
+
+    let branch_path = data::generate_path(PathVariant::Ref(RefVariant::Head("main"))).unwrap();
+    assert_eq!(fs::read_to_string(branch_path).unwrap(), oid);
+    assert_eq!(status().expect("Issue when getting status").branch, Some(String::from("main")));
+    cleanup();
+  }
+
+  #[test]
+  #[serial]
+  fn iter_commits_by_date_orders_commits_newest_first() {
+    let (_, cleanup) = create_test_directory();
+    let at = |timestamp| CommitOptions { allow_empty: true, timestamp: Some(timestamp), ..CommitOptions::default() };
+    let first = commit_with_options("First", &at(30)).expect("Issue when committing");
+    let second = commit_with_options("Second", &at(10)).expect("Issue when committing");
+    let third = commit_with_options("Third", &at(20)).expect("Issue when committing");
+    let fourth = commit_with_options("Fourth", &at(20)).expect("Issue when committing");
+
+    let walked: Vec<Oid> = iter_commits_and_parents(vec![fourth.clone()]).collect::<std::io::Result<_>>().unwrap();
+    assert_eq!(walked, vec![fourth.clone(), third.clone(), second.clone(), first.clone()]);
+    let by_date: Vec<Oid> = iter_commits_by_date(vec![fourth.clone()]).unwrap().collect();
+    assert_eq!(by_date, vec![first, fourth, third, second]);
+    cleanup();
+  }
+
+  #[test]
+  fn commit_on_a_branch_advances_the_branch_and_leaves_HEAD_symbolic() {
+    let (repository, cleanup) = create_in_memory_repository("branch");
+    fs::write(repository.root.join("index.html"), "<html>").expect("Issue when writing test file");
+    let first_oid = repository.commit_with_options("First", &test_commit_options()).expect("Issue when committing");
+
+    fs::write(repository.root.join("index.html"), "<html></html>").expect("Issue when writing test file");
+    let second_oid = repository.commit_with_options("Second", &test_commit_options()).expect("Issue when committing");
+    assert_eq!(repository.get_commit(&second_oid).unwrap().parents, vec![first_oid]);
+
+    let branch_path = repository.generate_path(PathVariant::Ref(RefVariant::Head("main")));
+    assert_eq!(fs::read_to_string(branch_path).unwrap(), second_oid);
+    let head_path = repository.generate_path(PathVariant::Head);
+    assert_eq!(fs::read_to_string(head_path).unwrap(), "ref:refs/heads/main");
+    cleanup();
+  }
+
+  #[test]
+  fn status_classifies_new_modified_and_deleted_files() {
+    let (repository, cleanup) = create_in_memory_test_directory("status");
+    let oid = repository.commit_with_options("First", &test_commit_options()).expect("Issue when committing");
+    repository.create_branch("main", &oid).expect("Issue when creating branch");
+    repository.set_head(RefTarget::Symbolic(String::from("refs/heads/main")), "checkout: moving to main").expect("Issue when setting HEAD");
+
+    fs::write(repository.root.join("index.html"), "<html></html>").expect("Issue when writing test file");
+    fs::write(repository.root.join("One/new.txt"), "new").expect("Issue when writing test file");
+    fs::remove_file(repository.root.join("style.css")).expect("Issue when removing test file");
+
+    let status = repository.status().expect("Issue when getting status");
+    assert_eq!(status.branch, Some(String::from("main")));
+    assert_eq!(status.head, Some(oid));
+    assert_eq!(status.changes, vec![
+      (PathBuf::from("One/new.txt"), FileStatus::New),
+      (PathBuf::from("index.html"), FileStatus::Modified),
+      (PathBuf::from("style.css"), FileStatus::Deleted),
+    ]);
+    cleanup();
+  }
+
+  #[test]
+  fn status_reports_no_changes_for_a_clean_detached_HEAD() {
+    let (repository, cleanup) = create_in_memory_test_directory("status-detached");
+    let oid = repository.commit_with_options("First", &test_commit_options()).expect("Issue when committing");
+    repository.set_head(RefTarget::Oid(oid.clone()), "checkout: moving to First").expect("Issue when setting HEAD");
+
+    let status = repository.status().expect("Issue when getting status");
+    assert_eq!(status.branch, None);
+    assert_eq!(status.head, Some(oid));
+    assert!(status.changes.is_empty());
+    cleanup();
+  }
+
+  #[test]
+  #[serial]
+  fn diff_trees_lists_added_removed_and_modified_files() {
+    let (_, cleanup) = create_test_directory();
+    let repository = Repository::find().unwrap();
+    let from = write_tree().expect("Issue when writing tree");
+    let index_oid = repository.get_oid(b"", ObjectType::Blob);
+
+    fs::write("index.html", "<html></html>").expect("Issue when writing test file");
+    fs::write("new.txt", "new").expect("Issue when writing test file");
+    fs::remove_dir_all("One").expect("Issue when removing test directory");
+    // A directory replaced by a file of the same name
+    fs::write("One", "").expect("Issue when writing test file");
+    let to = write_tree().expect("Issue when writing tree");
+
+    let changes = diff_trees(&from, &to).expect("Issue when diffing trees");
+    assert_eq!(changes, vec![
+      (PathBuf::from("One"), ChangeKind::Added(index_oid.clone())),
+      (PathBuf::from("One/Two/.SuperSecretFile"), ChangeKind::Removed(index_oid.clone())),
+      (PathBuf::from("index.html"), ChangeKind::Modified(index_oid, repository.get_oid(b"<html></html>", ObjectType::Blob))),
+      (PathBuf::from("new.txt"), ChangeKind::Added(repository.get_oid(b"new", ObjectType::Blob))),
+    ]);
+    assert!(diff_trees(&to, &to).unwrap().is_empty());
+    cleanup();
+  }
+
+  #[test]
+  #[serial]
+  fn diff_commit_compares_against_the_first_parent_or_an_empty_tree() {
+    let (_, cleanup) = create_test_directory();
+    let root_oid = commit("Root", false).expect("Issue when committing");
+    fs::write("index.html", "changed").expect("Issue when writing test file");
+    let child_oid = commit("Child", false).expect("Issue when committing");
+
+    let root_changes = diff_commit(&get_commit(&root_oid).unwrap()).unwrap();
+    assert!(!root_changes.is_empty());
+    assert!(root_changes.iter().all(|(_, change)| matches!(change, ChangeKind::Added(_))));
+    assert!(root_changes.windows(2).all(|pair| pair[0].0 < pair[1].0));
+
+    let child_changes = diff_commit(&get_commit(&child_oid).unwrap()).unwrap();
+    assert_eq!(child_changes.len(), 1);
+    assert_eq!(child_changes[0].0, PathBuf::from("index.html"));
+    assert!(matches!(child_changes[0].1, ChangeKind::Modified(_, _)));
+    cleanup();
+  }
+
+  #[test]
+  fn get_commit_ignores_unknown_headers() {
+    let (repository, cleanup) = create_in_memory_repository("unknown-headers");
+    let tree = repository.write_tree().expect("Issue when writing tree");
+    let contents = format!("tree {}\nauthor Jane Doe <jane@example.com> 1618565400 +0000\nencoding UTF-8\n\nMessage", tree);
+    let oid = repository.hash_object(contents.as_bytes(), ObjectType::Commit).expect("Issue when hashing commit");
+
+    let commit_object = repository.get_commit(&oid).expect("Issue when reading commit");
+    assert_eq!(commit_object.tree, tree);
+    assert_eq!(commit_object.author, Some(String::from("Jane Doe <jane@example.com>")));
+    assert_eq!(commit_object.message, "Message");
+    cleanup();
+  }
+
+  #[test]
+  fn get_commit_keeps_the_whole_author_name_without_a_timestamp() {
+    let (repository, cleanup) = create_in_memory_repository("short-signature");
+    let tree = repository.write_tree().expect("Issue when writing tree");
+    let contents = format!("tree {}\nauthor Jane Q. Doe <jane@example.com>\n\nMessage", tree);
+    let oid = repository.hash_object(contents.as_bytes(), ObjectType::Commit).expect("Issue when hashing commit");
+
+    assert_eq!(repository.get_commit(&oid).unwrap().author, Some(String::from("Jane Q. Doe <jane@example.com>")));
+    cleanup();
+  }
+
+  #[test]
+  fn get_commit_skips_author_and_committer_lines_without_a_signature() {
+    let (repository, cleanup) = create_in_memory_repository("bare-signatures");
+    let tree = repository.write_tree().expect("Issue when writing tree");
+    let contents = format!("tree {}\nauthor\ncommitter\n\nMessage", tree);
+    let oid = repository.hash_object(contents.as_bytes(), ObjectType::Commit).expect("Issue when hashing commit");
+
+    let commit_object = repository.get_commit(&oid).expect("Issue when reading commit");
+    assert_eq!(commit_object.author, None);
+    assert_eq!(commit_object.timestamp, None);
+    assert_eq!(commit_object.message, "Message");
+    cleanup();
+  }
+
+  #[test]
+  #[serial]
+  fn commit_with_an_empty_message_is_read_back() {
+    let (_, cleanup) = create_test_directory();
+    let root_oid = commit("", false).expect("Issue when committing");
+    let root = get_commit(&root_oid).expect("Issue when reading commit");
+    assert!(root.parents.is_empty());
+    assert_eq!(root.message, "");
+
+    fs::write("index.html", "changed").expect("Issue when writing test file");
+    let child_oid = commit("", false).expect("Issue when committing");
+    assert_eq!(get_commit(&child_oid).unwrap().parents, vec![root_oid.clone()]);
+    let history: Vec<Oid> = iter_commits_and_parents(vec![child_oid.clone()]).collect::<std::io::Result<_>>().unwrap();
+    assert_eq!(history, vec![child_oid, root_oid]);
+    cleanup();
+  }
+
+  #[test]
+  fn get_commit_reads_a_commit_with_nothing_after_its_headers() {
+    let (repository, cleanup) = create_in_memory_repository("no-message");
+    let tree = repository.write_tree().expect("Issue when writing tree");
+    for contents in &[format!("tree {}\n\n", tree), format!("tree {}\n", tree)] {
+      let oid = repository.hash_object(contents.as_bytes(), ObjectType::Commit).expect("Issue when hashing commit");
+      let commit = repository.get_commit(&oid).expect("Issue when reading commit");
+      assert_eq!(commit.tree, tree);
+      assert_eq!(commit.message, "");
+    }
+
+    cleanup();
+  }
+
+  #[test]
+  #[serial]
+  fn get_commit_collects_every_parent_line() {
+    let (_, cleanup) = create_test_directory();
+    let tree = write_tree().expect("Issue when writing tree");
+    let root = format!("tree {}\n\nRoot", tree);
+    let root_oid = data::hash_object(root.as_bytes(), ObjectType::Commit).expect("Issue when hashing commit");
+    let child = format!("tree {}\nparent {}\n\nChild", tree, root_oid);
+    let child_oid = data::hash_object(child.as_bytes(), ObjectType::Commit).expect("Issue when hashing commit");
+    let merge = format!("tree {}\nparent {}\nparent {}\n\nMerge", tree, child_oid, root_oid);
+    let merge_oid = data::hash_object(merge.as_bytes(), ObjectType::Commit).expect("Issue when hashing commit");
+
+    assert!(get_commit(&root_oid).unwrap().parents.is_empty());
+    assert_eq!(get_commit(&child_oid).unwrap().parents, vec![root_oid.clone()]);
+    assert_eq!(get_commit(&merge_oid).unwrap().parents, vec![child_oid.clone(), root_oid.clone()]);
+
+    let history: Vec<Oid> = iter_commits_and_parents(vec![merge_oid.clone()]).collect::<std::io::Result<_>>().unwrap();
+    assert_eq!(history, vec![merge_oid, child_oid, root_oid]);
+    cleanup();
+  }
+
+  #[test]
+  fn get_commit_rejects_a_malformed_tree_or_parent_oid() {
+    let (repository, cleanup) = create_in_memory_repository("malformed-commit");
+    let tree = repository.write_tree().expect("Issue when writing tree");
+    for contents in &[String::from("tree abc\n\nShort"), format!("tree {}\nparent {}\n\nNot hex", tree, "g".repeat(tree.len())), format!("tree {}\nparent\n\nEmpty", tree)] {
+      let oid = repository.hash_object(contents.as_bytes(), ObjectType::Commit).expect("Issue when hashing commit");
+      assert_eq!(repository.get_commit(&oid).err().map(|error| error.kind()), Some(ErrorKind::InvalidData));
+    }
+
+    cleanup();
+  }
+
+  #[test]
+  #[serial]
+  fn iter_commits_and_parents_terminates_on_a_commit_which_is_its_own_parent() {
+    let (_, cleanup) = create_test_directory();
+    let tree = write_tree().expect("Issue when writing tree");
+    // Written by hand, as no real commit can contain its own hash. Such an object can never hash back to its OID either.
+    data::set_config("core.verifyObjects", "false").expect("Issue when setting config");
+    let oid: Oid = "a".repeat(ObjectFormat::Sha256.oid_length()).parse().unwrap();
+    let oid_file = data::generate_path(PathVariant::OID(&oid)).unwrap();
+    fs::create_dir_all(oid_file.parent().unwrap()).expect("Issue when creating object directory");
+    fs::write(&oid_file, format!("commit\0tree {}\nparent {}\n\nLoop", tree, oid)).expect("Issue when writing object");
+
+    let history: Vec<Oid> = iter_commits_and_parents(vec![oid.clone(), oid.clone()]).collect::<std::io::Result<_>>().unwrap();
+    assert_eq!(history, vec![oid]);
+    cleanup();
+  }
+
+  #[test]
+  #[serial]
+  fn iter_commits_and_parents_only_reads_a_commit_once_the_walk_reaches_it() {
+    let (_, cleanup) = create_test_directory();
+    let tree = write_tree().expect("Issue when writing tree");
+    let missing: Oid = "b".repeat(ObjectFormat::Sha256.oid_length()).parse().unwrap();
+    let commit = format!("tree {}\nparent {}\n\nOrphaned", tree, missing);
+    let oid = data::hash_object(commit.as_bytes(), ObjectType::Commit).expect("Issue when hashing commit");
+
+    // The missing parent is not read until the walk moves past the commit
+    let mut walk = iter_commits_and_parents(vec![oid.clone()]);
+    assert_eq!(walk.next().unwrap().unwrap(), oid);
+    assert_eq!(walk.next().unwrap().unwrap_err().kind(), ErrorKind::NotFound);
+    assert!(walk.next().is_none());
+    cleanup();
+  }
+
+  #[test]
+  #[serial]
+  fn iter_commits_topologically_lists_children_before_parents() {
+    let (_, cleanup) = create_test_directory();
+    let tree = write_tree().expect("Issue when writing tree");
+    let hash_commit = |parents: &[&Oid], message: &str| {
+      let parent_lines: String = parents.iter().map(|parent| format!("parent {}\n", parent)).collect();
+      let commit = format!("tree {}\n{}\n{}", tree, parent_lines, message);
+      data::hash_object(commit.as_bytes(), ObjectType::Commit).expect("Issue when hashing commit")
+    };
+    let root_oid = hash_commit(&[], "Root");
+    let short_oid = hash_commit(&[&root_oid], "Short");
+    let long_first_oid = hash_commit(&[&root_oid], "Long 1");
+    let long_second_oid = hash_commit(&[&long_first_oid], "Long 2");
+    let merge_oid = hash_commit(&[&short_oid, &long_second_oid], "Merge");
+
+    // The breadth-first walk reaches the root before the rest of the longer branch
+    let walked: Vec<Oid> = iter_commits_and_parents(vec![merge_oid.clone()]).collect::<std::io::Result<_>>().unwrap();
+    assert_eq!(walked, vec![merge_oid.clone(), short_oid.clone(), long_second_oid.clone(), root_oid.clone(), long_first_oid.clone()]);
+
+    let sorted: Vec<Oid> = iter_commits_topologically(vec![merge_oid.clone()]).unwrap().collect();
+    assert_eq!(sorted, vec![merge_oid, short_oid, long_second_oid, long_first_oid, root_oid]);
+    cleanup();
+  }
+
+  #[test]
+  #[serial]
+  fn write_subtree_matches_the_entry_of_the_whole_tree() {
+    let (_, cleanup) = create_test_directory();
+    let tree = write_tree().expect("Issue when writing tree");
+    let oid = write_subtree(Path::new("One")).expect("Issue when writing subtree");
+    let contents = String::from_utf8(data::get_object(&tree, ObjectType::Tree).unwrap()).unwrap();
+    assert!(contents.split_terminator('\0').any(|entry| entry == format!("{} tree {} One", TREE_MODE, oid)));
+
+    env::set_current_dir("One").unwrap();
+    assert_eq!(write_subtree(Path::new(".")).unwrap(), oid);
+    assert_eq!(write_subtree(Path::new("../..")).unwrap_err().kind(), ErrorKind::InvalidInput);
+    env::set_current_dir("..").unwrap();
+
+    assert_eq!(write_subtree(Path::new(".ugit")).unwrap_err().kind(), ErrorKind::InvalidInput);
+    assert_eq!(write_subtree(Path::new("index.html")).unwrap_err().kind(), ErrorKind::InvalidInput);
+    assert_eq!(write_subtree(Path::new("Missing")).unwrap_err().kind(), ErrorKind::NotFound);
+    cleanup();
+  }
+
+  #[test]
+  #[serial]
+  fn fsck_reports_corrupt_and_missing_objects() {
+    let (_, cleanup) = create_test_directory();
+    let oid = commit("First", false).expect("Issue when committing");
+    let tree = get_commit(&oid).unwrap().tree;
+    assert_eq!(fsck().unwrap(), Vec::new());
+
+    let blob = data::hash_object(b"Excepturi velit rem modi.", ObjectType::Blob).unwrap();
+    let blob_path = data::generate_path(PathVariant::OID(&blob)).unwrap();
+    fs::write(&blob_path, "blob\0Ut non ipsa aut ad dignissimos.").unwrap();
+    let tree_path = data::generate_path(PathVariant::OID(&tree)).unwrap();
+    fs::remove_file(&tree_path).unwrap();
+    let problems = fsck().unwrap();
+    assert_eq!(problems.len(), 2);
+    assert!(problems.contains(&ObjectProblem::Missing(oid, tree)));
+    assert!(problems.iter().any(|problem| matches!(problem, ObjectProblem::Corrupt(corrupt, _) if *corrupt == blob)));
+    cleanup();
+  }
+
+  #[test]
+  #[serial]
+  fn gc_removes_only_unreachable_objects() {
+    let (_, cleanup) = create_test_directory();
+    let first = commit("First", false).expect("Issue when committing");
+    fs::write("index.html", "Second").expect("Issue when writing test file");
+    let second = commit("Second", false).expect("Issue when committing");
+    reset(&first).expect("Issue when resetting");
+    fs::write("index.html", "Throwaway").expect("Issue when writing test file");
+    let throwaway_tree = write_tree().expect("Issue when writing tree");
+    let throwaway_blob = data::hash_object(b"Throwaway", ObjectType::Blob).unwrap();
+    fs::write("staged.txt", "Staged").expect("Issue when writing test file");
+    add(Path::new("staged.txt")).expect("Issue when staging file");
+    let staged_blob = data::get_index().unwrap()["staged.txt"].clone();
+    let object_count = Repository::find().unwrap().list_objects().unwrap().len();
+
+    let mut unreachable = gc(true).expect("Issue when collecting garbage");
+    unreachable.sort();
+    let mut expected = vec![throwaway_tree, throwaway_blob];
+    expected.sort();
+    assert_eq!(unreachable, expected);
+    assert_eq!(Repository::find().unwrap().list_objects().unwrap().len(), object_count);
+
+    assert_eq!(gc(false).unwrap().len(), 2);
+    assert_eq!(Repository::find().unwrap().list_objects().unwrap().len(), object_count - 2);
+    assert!(gc(true).unwrap().is_empty());
+    assert_eq!(fsck().unwrap(), Vec::new());
+    // The commit reset away from is still recorded in the reflog
+    assert_eq!(get_commit(&second).unwrap().message, "Second");
+    assert_eq!(data::get_object(&staged_blob, ObjectType::Blob).unwrap(), b"Staged");
+    cleanup();
+  }
+
+  #[test]
+  #[serial]
+  fn fast_export_writes_each_blob_once_and_commits_after_their_parents() {
+    let (_, cleanup) = create_test_directory();
+    data::set_head(RefTarget::Symbolic(String::from("refs/heads/main")), "checkout: moving to main").expect("Issue when setting HEAD");
+    let first = commit("First", false).expect("Issue when committing");
+    fs::write("index.html", "Second").expect("Issue when writing test file");
+    let second = commit("Second", false).expect("Issue when committing");
+    create_tag("v1", &first, false).expect("Issue when creating tag");
+
+    let mut stream = Vec::new();
+    fast_export(&mut stream).expect("Issue when exporting");
+    let stream = String::from_utf8(stream).unwrap();
+    let mut blobs = HashSet::new();
+    for oid in &[first, second] {
+      blobs.extend(get_tree(&Repository::find().unwrap(), &get_commit(oid).unwrap().tree, &PathBuf::new()).unwrap().into_iter().map(|(_, oid)| oid));
+    }
+
+    assert_eq!(stream.matches("blob\nmark :").count(), blobs.len());
+    assert_eq!(stream.matches("commit refs/heads/main\n").count(), 2);
+    let first_commit = stream.find("data 5\nFirst").unwrap();
+    let second_commit = stream.find("data 6\nSecond").unwrap();
+    assert!(first_commit < second_commit);
+    assert!(stream[second_commit..].contains("from :"));
+    assert!(stream.contains("reset refs/heads/main\nfrom :"));
+    assert!(stream.contains("reset refs/tags/v1\nfrom :"));
+    cleanup();
+  }
+
+  #[test]
+  fn repository_commits_without_changing_the_current_directory() {
+    let (repository, cleanup) = create_in_memory_repository("commits");
+    fs::write(repository.root.join("a.txt"), "a").expect("Issue when writing test file");
+
+    let oid = repository.commit_with_options("First", &test_commit_options()).expect("Issue when committing");
+    assert_eq!(repository.get_head().unwrap().unwrap(), oid);
+    let commit = repository.get_commit(&oid).expect("Issue when getting commit");
+    assert_eq!(commit.message, "First");
+    assert_eq!(commit.tree, repository.write_tree().unwrap());
+    assert_eq!(commit.author.as_deref(), Some("Jane Doe <jane@example.com>"));
+    cleanup();
+  }
+
+  #[test]
+  fn in_memory_repository_writes_trees_without_storing_objects_on_disk() {
+    let (repository, cleanup) = create_in_memory_repository("trees");
+    fs::create_dir(repository.root.join("One")).expect("Issue when creating test directory");
+    fs::write(repository.root.join("One").join("b.txt"), "b").expect("Issue when writing test file");
+    fs::write(repository.root.join("a.txt"), "a").expect("Issue when writing test file");
+
+    let tree = repository.write_tree().expect("Issue when writing tree");
+    assert_eq!(repository.get_object_type(&tree).unwrap(), ObjectType::Tree);
+    assert_eq!(repository.get_working_tree_oid().unwrap().0, tree);
+    assert_eq!(repository.list_objects().unwrap().len(), 4);
+    assert_eq!(fs::read_dir(repository.generate_path(PathVariant::Objects)).unwrap().count(), 0);
+    cleanup();
+  }
+
+  #[test]
+  fn in_memory_repository_advances_and_resolves_refs() {
+    let (repository, cleanup) = create_in_memory_repository("refs");
+    fs::write(repository.root.join("a.txt"), "a").expect("Issue when writing test file");
+    let first = repository.commit_with_options("First", &test_commit_options()).expect("Issue when committing");
+    fs::write(repository.root.join("a.txt"), "b").expect("Issue when writing test file");
+    let second = repository.commit_with_options("Second", &test_commit_options()).expect("Issue when committing");
+
+    let main = repository.generate_path(PathVariant::Ref(RefVariant::Head("main")));
+    assert_eq!(repository.get_ref(&main, false).unwrap().oid(), Some(&second));
+    assert_eq!(repository.get_commit(&second).unwrap().parents, vec![first.clone()]);
+
+    let side = RefValue { value: Some(RefTarget::Oid(first.clone())), path: repository.generate_path(PathVariant::Ref(RefVariant::Head("side"))) };
+    repository.update_ref(&side, true).expect("Issue when creating branch");
+    assert_eq!(repository.locate_ref_or_oid("side", true).unwrap().unwrap(), first);
+    assert_eq!(repository.resolve_oid_prefix(&second[..8]).unwrap(), second);
+    // A ref may only point at a commit the repository holds
+    let missing = RefValue { value: Some(RefTarget::Oid("a".repeat(64).parse().unwrap())), path: side.path.clone() };
+    assert_eq!(repository.update_ref(&missing, true).unwrap_err().kind(), ErrorKind::InvalidInput);
+    cleanup();
+  }
+
+  #[test]
+  fn amended_commit_replaces_HEAD_commit_and_takes_over_its_parents() {
+    let (repository, cleanup) = create_in_memory_repository("amend");
+    let amend = CommitOptions { amend: true, ..test_commit_options() };
+    fs::write(repository.root.join("a.txt"), "a").expect("Issue when writing test file");
+    // The first commit has no parent to take over, and HEAD has no commit before it
+    assert_eq!(repository.commit_with_options("First", &amend).unwrap_err().kind(), ErrorKind::InvalidInput);
+    let first = repository.commit_with_options("First", &test_commit_options()).expect("Issue when committing");
+    fs::write(repository.root.join("a.txt"), "b").expect("Issue when writing test file");
+    let second = repository.commit_with_options("Second", &test_commit_options()).expect("Issue when committing");
+
+    let amended = repository.commit_with_options("Second, amended", &amend).expect("Issue when amending");
+    assert_ne!(amended, second);
+    let commit = repository.get_commit(&amended).unwrap();
+    assert_eq!(commit.parents, vec![first]);
+    assert_eq!(commit.tree, repository.get_commit(&second).unwrap().tree);
+    assert_eq!(commit.message, "Second, amended");
+
+    let main = repository.generate_path(PathVariant::Ref(RefVariant::Head("main")));
+    assert_eq!(repository.get_ref(&main, false).unwrap().oid(), Some(&amended));
+    let reflog = repository.read_reflog(&main).unwrap();
+    let last = reflog.last().unwrap();
+    assert_eq!((&last.old, &last.new), (&second, &amended));
+    assert_eq!(last.message, "commit (amend): Second, amended");
+    cleanup();
+  }
+
+  #[test]
+  fn commit_tree_commits_a_stored_tree_without_moving_HEAD() {
+    let (repository, cleanup) = create_in_memory_repository("commit-tree");
+    fs::write(repository.root.join("a.txt"), "a").expect("Issue when writing test file");
+    let first = repository.commit_with_options("First", &test_commit_options()).expect("Issue when committing");
+    let tree = repository.get_commit(&first).unwrap().tree;
+
+    let parents = vec![first.clone()];
+    let oid = repository.commit_tree(&tree, &parents, "Plumbing", &test_commit_options()).expect("Issue when committing tree");
+    let commit = repository.get_commit(&oid).unwrap();
+    assert_eq!(commit.tree, tree);
+    assert_eq!(commit.parents, parents);
+    assert_eq!(commit.message, "Plumbing");
+    assert_eq!(repository.get_head().unwrap().unwrap(), first);
+
+    // Neither a commit in place of the tree nor a tree in place of a parent is accepted
+    assert_eq!(repository.commit_tree(&first, &[], "Plumbing", &test_commit_options()).unwrap_err().kind(), ErrorKind::InvalidInput);
+    assert_eq!(repository.commit_tree(&tree, std::slice::from_ref(&tree), "Plumbing", &test_commit_options()).unwrap_err().kind(), ErrorKind::InvalidInput);
+    cleanup();
+  }
+
+  #[test]
+  #[serial]
+  fn commit_records_author_and_timestamp() {
+    let (_, cleanup) = create_test_directory();
+    data::set_config("user.name", "Config Name").expect("Issue when setting config");
+    data::set_config("user.email", "config@example.com").expect("Issue when setting config");
+
+    let before = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+    let oid = commit("First", false).expect("Issue when committing");
+    let commit_object = get_commit(&oid).expect("Issue when reading commit");
+    assert_eq!(commit_object.author, Some(String::from("Config Name <config@example.com>")));
+    assert!(commit_object.timestamp.unwrap() >= before);
+    assert_eq!(commit_object.message, "First");
+
+    env::set_var("UGIT_AUTHOR_NAME", "Env Name");
+    env::set_var("UGIT_AUTHOR_EMAIL", "env@example.com");
+    let oid = commit("Second", true);
+    env::remove_var("UGIT_AUTHOR_NAME");
+    env::remove_var("UGIT_AUTHOR_EMAIL");
+    let commit_object = get_commit(&oid.expect("Issue when committing")).expect("Issue when reading commit");
+    assert_eq!(commit_object.author, Some(String::from("Env Name <env@example.com>")));
+    cleanup();
+  }
+
+  #[test]
+  #[serial]
+  fn commit_refuses_a_tree_matching_HEAD_unless_empty_commits_are_allowed() {
+    let (_, cleanup) = create_test_directory();
+    let first = commit("First", false).expect("Issue when committing");
+    assert_eq!(commit("Again", false).unwrap_err().kind(), ErrorKind::InvalidInput);
+    assert_eq!(commit_all("Again", &CommitOptions::default()).unwrap_err().kind(), ErrorKind::InvalidInput);
+    assert_eq!(data::get_head().unwrap().unwrap(), first);
+
+    let empty = commit("Empty", true).expect("Issue when committing");
+    assert_eq!(get_commit(&empty).unwrap().parents, vec![first.clone()]);
+    assert_eq!(get_commit(&empty).unwrap().tree, get_commit(&first).unwrap().tree);
+
+    fs::write("index.html", "changed").expect("Issue when writing test file");
+    commit("Changed", false).expect("Issue when committing");
+    cleanup();
+  }
+
+  #[test]
+  #[serial]
+  fn commit_records_the_given_author_and_timestamp() {
+    let (_, cleanup) = create_test_directory();
+    let options = CommitOptions { author: Some(String::from("Given Name <given@example.com>")), timestamp: Some(1000), ..CommitOptions::default() };
+    let oid = commit_with_options("First", &options).expect("Issue when committing");
+    let commit_object = get_commit(&oid).expect("Issue when reading commit");
+    assert_eq!(commit_object.author, Some(String::from("Given Name <given@example.com>")));
+    assert_eq!(commit_object.timestamp, Some(1000));
+
+    env::set_var("UGIT_COMMIT_DATE", "2000");
+    let oid = commit("Second", true);
+    env::set_var("UGIT_COMMIT_DATE", "yesterday");
+    let invalid_date = commit("Third", true);
+    env::remove_var("UGIT_COMMIT_DATE");
+    assert_eq!(get_commit(&oid.expect("Issue when committing")).unwrap().timestamp, Some(2000));
+    assert_eq!(invalid_date.unwrap_err().kind(), ErrorKind::InvalidInput);
+
+    for author in &["Given Name", "<given@example.com>", "Given Name <given@example.com>\nparent x"] {
+      let options = CommitOptions { author: Some(String::from(*author)), allow_empty: true, ..CommitOptions::default() };
+      assert_eq!(commit_with_options("Fourth", &options).unwrap_err().kind(), ErrorKind::InvalidInput);
+    }
+
+    cleanup();
+  }
+
+  #[test]
+  #[serial]
+  fn reflog_records_each_move_of_head_and_branches() {
+    let (_, cleanup) = create_test_directory();
+    let zero_oid: Oid = "0".repeat(ObjectFormat::Sha256.oid_length()).parse().unwrap();
+    data::set_head(RefTarget::Symbolic(String::from("refs/heads/main")), "checkout: moving to main").expect("Issue when setting HEAD");
+    let first = commit("First\nBody", false).expect("Issue when committing");
+    fs::write("Reflog", "Second").expect("Issue when writing file");
+    let second = commit("Second", false).expect("Issue when committing");
+    create_branch("side", &first).expect("Issue when creating branch");
+    checkout("side").expect("Issue when checking out branch");
+    reset(&second).expect("Issue when resetting");
+
+    let main: Vec<_> = get_reflog("main").unwrap().into_iter().map(|entry| (entry.old, entry.new, entry.message)).collect();
+    assert_eq!(main, vec![
+      (zero_oid.clone(), first.clone(), String::from("commit: First")),
+      (first.clone(), second.clone(), String::from("commit: Second")),
+    ]);
+
+    let side: Vec<_> = get_reflog("refs/heads/side").unwrap().into_iter().map(|entry| (entry.old, entry.new, entry.message)).collect();
+    assert_eq!(side, vec![
+      (zero_oid.clone(), first.clone(), format!("branch: created from {}", first)),
+      (first.clone(), second.clone(), format!("reset: moving to {}", second)),
+    ]);
+
+    // Following HEAD to a branch logs the update for HEAD too
+    let head: Vec<_> = get_reflog("HEAD").unwrap().into_iter().map(|entry| entry.message).collect();
+    assert_eq!(head, vec![
+      String::from("checkout: moving to main"),
+      String::from("commit: First"),
+      String::from("commit: Second"),
+      String::from("checkout: moving to side"),
+      format!("reset: moving to {}", second),
+    ]);
+
+    assert_eq!(get_reflog("missing").unwrap_err().kind(), ErrorKind::NotFound);
+    assert_eq!(get_reflog("../HEAD").unwrap_err().kind(), ErrorKind::InvalidInput);
+    assert_eq!(get_reflog("refs/../HEAD").unwrap_err().kind(), ErrorKind::InvalidInput);
+    assert_eq!(get_reflog("refs/heads/../../HEAD").unwrap_err().kind(), ErrorKind::InvalidInput);
+    delete_branch("main").expect("Issue when deleting branch");
+    assert!(!data::generate_path(PathVariant::Logs).unwrap().join("refs").join("heads").join("main").exists());
+    cleanup();
+  }
+
+  #[test]
+  fn read_tree_rejects_entries_which_would_escape_the_directory() {
+    let (repository, cleanup) = create_in_memory_test_directory("escape");
+    let blob = repository.hash_object(b"Escaped", ObjectType::Blob).expect("Issue when hashing blob");
+    for name in &["../escape", "/escape", "..", ""] {
+      let tree = format!("{} blob {} {}", FILE_MODE, blob, name);
+      let tree_oid = repository.hash_object(tree.as_bytes(), ObjectType::Tree).expect("Issue when hashing tree");
+      let err = repository.read_tree(&tree_oid).expect_err("Tree with an invalid entry name was read");
+      assert_eq!(err.kind(), ErrorKind::InvalidData);
+    }
+
+    assert!(!repository.root.join("../escape").exists());
+    assert!(!Path::new("/escape").exists());
+
+    fs::write(repository.root.join("back\\slash"), "Backslash").expect("Issue when writing test file");
+    let tree_oid = repository.write_tree().expect("Issue when writing tree");
+    fs::remove_file(repository.root.join("back\\slash")).expect("Issue when removing test file");
+    repository.read_tree(&tree_oid).expect("Issue when reading tree");
+    assert_eq!(fs::read_to_string(repository.root.join("back\\slash")).unwrap(), "Backslash");
+    cleanup();
+  }
+
+  #[test]
+  fn get_working_tree_oid_matches_write_tree_without_storing_objects() {
+    let (repository, cleanup) = create_in_memory_repository("working-tree");
+    fs::create_dir(repository.root.join("One")).expect("Issue when creating test directory");
+    fs::write(repository.root.join("One").join("b.txt"), "b").expect("Issue when writing test file");
+    fs::write(repository.root.join("a.txt"), "a").expect("Issue when writing test file");
+
+    let (oid, files) = repository.get_working_tree_oid().expect("Issue when computing working tree");
+    assert!(repository.list_objects().unwrap().is_empty());
+    assert_eq!(files.len(), 2);
+    for (path, file_oid) in &files {
+      assert!(path.is_relative());
+      assert_eq!(file_oid, &repository.get_oid(&fs::read(repository.root.join(path)).unwrap(), ObjectType::Blob));
+    }
+
+    assert_eq!(oid, repository.write_tree().expect("Issue when writing tree"));
+    cleanup();
+  }
+
+  #[test]
+  fn write_tree_skips_paths_matched_by_ugitignore() {
+    let (repository, cleanup) = create_in_memory_test_directory("ignore");
+    fs::write(repository.root.join(".ugitignore"), "# Comments are skipped\nOne/Two/\n*.css\n").expect("Issue when writing ignore file");
+    fs::write(repository.root.join("One/kept.txt"), "").expect("Issue when writing test file");
+
+    let oid = repository.write_tree().expect("Issue when writing tree");
+    let paths: Vec<_> = get_tree(&repository, &oid, &PathBuf::new()).unwrap().into_iter().map(|(path, _)| path).collect();
+    assert!(paths.contains(&PathBuf::from(".ugitignore")));
+    assert!(paths.contains(&PathBuf::from("index.html")));
+    assert!(paths.contains(&PathBuf::from("One/kept.txt")));
+    assert!(!paths.contains(&PathBuf::from("style.css")));
+    assert!(!paths.contains(&PathBuf::from("One/Two/.SuperSecretFile")));
+    cleanup();
+  }
+
+  #[test]
+  fn empty_current_directory_leaves_paths_matched_by_ugitignore() {
+    let (repository, cleanup) = create_in_memory_test_directory("empty-ignore");
+    fs::write(repository.root.join(".ugitignore"), "index.*\n").expect("Issue when writing ignore file");
+
+    empty_current_directory(&repository, &HashSet::new()).expect("Issue when emptying root directory");
+    assert!(repository.root.join("index.html").is_file());
+    assert!(!repository.root.join("style.css").exists());
+    assert!(!repository.root.join("One").exists());
+    cleanup();
+  }
+
+  #[test]
+  fn read_tree_removes_files_missing_from_snapshot_and_overwrites_the_rest() {
+    let (repository, cleanup) = create_in_memory_test_directory("read-tree");
+    let oid = repository.write_tree().expect("Issue when writing tree");
+    fs::write(repository.root.join("index.html"), "<html></html>").expect("Issue when writing test file");
+    fs::write(repository.root.join("One/Two/extra.txt"), "").expect("Issue when writing test file");
+    fs::create_dir(repository.root.join("Three")).expect("Issue when creating test directory");
+    fs::write(repository.root.join("Three/extra.txt"), "").expect("Issue when writing test file");
+
+    repository.read_tree(&oid).expect("Issue when restoring from write_tree snapshot");
+    assert_eq!(fs::read_to_string(repository.root.join("index.html")).unwrap(), "");
+    assert!(repository.root.join("One/Two/.SuperSecretFile").is_file());
+    assert!(!repository.root.join("One/Two/extra.txt").exists());
+    assert!(!repository.root.join("Three").exists());
+    cleanup();
+  }
+
+  #[test]
+  fn read_tree_leaves_working_directory_untouched_if_an_object_is_missing() {
+    let (repository, cleanup) = create_in_memory_test_directory("missing-object");
+    fs::write(repository.root.join("index.html"), "<html></html>").expect("Issue when writing test file");
+    let oid = repository.write_tree().expect("Issue when writing tree");
+    let blob_oid = repository.get_oid(b"<html></html>", ObjectType::Blob);
+    repository.objects().delete_object(&blob_oid).expect("Issue when removing object");
+    fs::write(repository.root.join("extra.txt"), "").expect("Issue when writing test file");
+    // The missing blob must be read, which it need not be while index.html still holds it
+    fs::write(repository.root.join("index.html"), "changed").expect("Issue when writing test file");
+
+    assert!(repository.read_tree(&oid).is_err());
+    assert_eq!(fs::read_to_string(repository.root.join("index.html")).unwrap(), "changed");
+    assert!(repository.root.join("extra.txt").is_file());
+    cleanup();
+  }
+
+  #[test]
+  fn read_tree_leaves_files_which_already_hold_their_blob_alone() {
+    let (repository, cleanup) = create_in_memory_test_directory("unchanged");
+    fs::write(repository.root.join("index.html"), "<html></html>").expect("Issue when writing test file");
+    let oid = repository.write_tree().expect("Issue when writing tree");
+    let modified = fs::metadata(repository.root.join("index.html")).unwrap().modified().unwrap();
+    fs::write(repository.root.join("style.css"), "changed").expect("Issue when writing test file");
+    // Waits out the coarsest timestamp granularity of common file systems, so that rewriting index.html would change its mtime
+    std::thread::sleep(std::time::Duration::from_millis(1100));
+
+    repository.read_tree(&oid).expect("Issue when restoring from write_tree snapshot");
+    assert_eq!(fs::metadata(repository.root.join("index.html")).unwrap().modified().unwrap(), modified);
+    assert_eq!(fs::read_to_string(repository.root.join("style.css")).unwrap(), "");
+    cleanup();
+  }
+
+  #[test]
+  #[cfg(unix)]
+  fn read_tree_restores_the_executable_bit() {
+    use std::os::unix::fs::PermissionsExt;
+    let (repository, cleanup) = create_in_memory_test_directory("executable");
+    fs::write(repository.root.join("run.sh"), "#!/bin/sh\n").expect("Issue when writing test file");
+    fs::set_permissions(repository.root.join("run.sh"), fs::Permissions::from_mode(0o755)).expect("Issue when setting permissions");
+    let oid = repository.write_tree().expect("Issue when writing tree");
+
+    fs::set_permissions(repository.root.join("run.sh"), fs::Permissions::from_mode(0o644)).expect("Issue when setting permissions");
+    repository.read_tree(&oid).expect("Issue when reading tree");
+    assert_eq!(fs::metadata(repository.root.join("run.sh")).unwrap().permissions().mode() & 0o777, 0o755);
+    assert_eq!(fs::metadata(repository.root.join("index.html")).unwrap().permissions().mode() & 0o111, 0);
+    cleanup();
+  }
+
+  #[test]
+  #[cfg(unix)]
+  fn write_tree_and_read_tree_round_trip_symlinks() {
+    let (repository, cleanup) = create_in_memory_test_directory("symlinks");
+    std::os::unix::fs::symlink("index.html", repository.root.join("link.html")).expect("Issue when creating symlink");
+    std::os::unix::fs::symlink("One", repository.root.join("link_dir")).expect("Issue when creating symlink");
+    let oid = repository.write_tree().expect("Issue when writing tree");
+    let head = repository.commit_with_options("Links", &test_commit_options()).expect("Issue when committing");
+    assert!(repository.status().unwrap().changes.is_empty());
+
+    let entries = get_tree_entries(&repository, &oid, &PathBuf::new()).expect("Issue when reading tree");
+    let link_entry = entries.iter().find(|(path, _, _)| path == Path::new("link_dir")).expect("Link missing from tree");
+    assert_eq!(link_entry.2, SYMLINK_MODE);
+    assert_eq!(repository.get_object(&link_entry.1, ObjectType::Blob).unwrap(), b"One");
+
+    fs::remove_file(repository.root.join("link.html")).expect("Issue when removing symlink");
+    fs::write(repository.root.join("link.html"), "not a link").expect("Issue when writing test file");
+    fs::remove_file(repository.root.join("link_dir")).expect("Issue when removing symlink");
+    repository.read_tree(&repository.get_commit(&head).unwrap().tree).expect("Issue when reading tree");
+    assert_eq!(fs::read_link(repository.root.join("link.html")).unwrap(), PathBuf::from("index.html"));
+    assert_eq!(fs::read_link(repository.root.join("link_dir")).unwrap(), PathBuf::from("One"));
+    assert!(repository.root.join("One").is_dir());
+    cleanup();
+  }
+
+  #[test]
+  fn write_tree_and_read_tree_round_trip_names_with_spaces_and_newlines() {
+    let (repository, cleanup) = create_in_memory_test_directory("odd-names");
+    fs::write(repository.root.join("a b\nc.txt"), "Odd name").expect("Issue when writing test file");
+    fs::write(repository.root.join(" leading"), "Leading space").expect("Issue when writing test file");
+    let oid = repository.write_tree().expect("Issue when writing tree");
+    let names: Vec<String> = repository.parse_tree(&oid).unwrap().into_iter().map(|(_, _, _, name)| name).collect();
+    assert!(names.contains(&String::from("a b\nc.txt")));
+    assert!(names.contains(&String::from(" leading")));
+
+    fs::remove_file(repository.root.join("a b\nc.txt")).expect("Issue when removing test file");
+    fs::remove_file(repository.root.join(" leading")).expect("Issue when removing test file");
+    repository.read_tree(&oid).expect("Issue when reading tree");
+    assert_eq!(fs::read_to_string(repository.root.join("a b\nc.txt")).unwrap(), "Odd name");
+    assert_eq!(fs::read_to_string(repository.root.join(" leading")).unwrap(), "Leading space");
+    cleanup();
+  }
+
+  #[test]
+  fn trees_with_one_entry_per_line_are_still_read() {
+    let (repository, cleanup) = create_in_memory_test_directory("old-trees");
+    let blob = repository.hash_object(b"Old", ObjectType::Blob).expect("Issue when hashing blob");
+    let tree = format!("{} blob {} old.txt\nblob {} older.txt", FILE_MODE, blob, blob);
+    let tree_oid = repository.hash_object(tree.as_bytes(), ObjectType::Tree).expect("Issue when hashing tree");
+    assert_eq!(repository.parse_tree(&tree_oid).unwrap(), vec![
+      (String::from(FILE_MODE), String::from("blob"), blob.clone(), String::from("old.txt")),
+      (String::from(FILE_MODE), String::from("blob"), blob.clone(), String::from("older.txt")),
+    ]);
+    cleanup();
+  }
+
+  #[test]
+  fn write_tree_and_read_tree_report_each_file_processed() {
+    let (repository, cleanup) = create_in_memory_test_directory("progress");
+    let mut written = Vec::new();
+    let oid = repository.write_tree_with_progress(&mut |count| written.push(count)).expect("Issue when writing tree");
+    let file_count = get_tree(&repository, &oid, &PathBuf::new()).unwrap().len();
+    assert_eq!(written, (1..=file_count).collect::<Vec<_>>());
+
+    let mut read = Vec::new();
+    repository.read_tree_with_progress(&oid, true, &mut |count| read.push(count)).expect("Issue when reading tree");
+    assert_eq!(read, written);
+    cleanup();
+  }
+
+  #[test]
+  fn write_tree_does_not_depend_on_the_order_files_were_created_in() {
+    let (repository, cleanup) = create_in_memory_repository("creation-order");
+    let first = repository.root.join("First");
+    fs::create_dir(&first).expect("Issue when creating test directory");
+    for name in &["b.txt", "a.txt", "c.txt"] {
+      fs::write(first.join(name), name).expect("Issue when writing test file");
+    }
+
+    let second = repository.root.join("Second");
+    fs::create_dir(&second).expect("Issue when creating test directory");
+    for name in &["c.txt", "a.txt", "b.txt"] {
+      fs::write(second.join(name), name).expect("Issue when writing test file");
+    }
+
+    let ignore_rules = IgnoreRules::load(&repository.root, &repository.generate_path(PathVariant::Ugit)).expect("Issue when loading ignore rules");
+    let first_oid = write_tree_recursive(&repository, &first, &ignore_rules, &mut |_| ()).expect("Issue when writing tree");
+    let second_oid = write_tree_recursive(&repository, &second, &ignore_rules, &mut |_| ()).expect("Issue when writing tree");
+    assert_eq!(first_oid, second_oid);
+    cleanup();
+  }
+
+  #[test]
+  #[serial]
+  fn commit_uses_the_index_once_files_are_staged() {
+    let (_, cleanup) = create_test_directory();
+    fs::write("staged.txt", "staged").expect("Issue when writing test file");
+    fs::write("unstaged.txt", "unstaged").expect("Issue when writing test file");
+    add(Path::new("staged.txt")).expect("Issue when adding file");
+    add(Path::new("One")).expect("Issue when adding directory");
+
+    let oid = commit("Staged", false).expect("Issue when committing");
+    let files: Vec<PathBuf> = get_tree(&Repository::find().unwrap(), &get_commit(&oid).unwrap().tree, &PathBuf::new()).unwrap().into_iter().map(|(path, _)| path).collect();
+    assert!(files.contains(&PathBuf::from("staged.txt")));
+    assert!(files.iter().any(|path| path.starts_with("One")));
+    assert!(!files.contains(&PathBuf::from("unstaged.txt")));
+    assert!(!files.contains(&PathBuf::from("index.html")));
+
+    remove(Path::new("staged.txt"), true).expect("Issue when removing file");
+    assert!(Path::new("staged.txt").is_file());
+    remove(Path::new("One"), false).expect("Issue when removing directory");
+    assert!(fs::read_dir("One").map_or(true, |mut entries| entries.all(|entry| entry.unwrap().path().is_dir())));
+    assert!(data::get_index().unwrap().is_empty());
+    cleanup();
+  }
+
+  #[test]
+  fn add_after_a_commit_keeps_the_other_tracked_files() {
+    let (repository, cleanup) = create_in_memory_test_directory("add");
+    let first = repository.commit_with_options("First", &test_commit_options()).expect("Issue when committing");
+    let tracked = get_tree(&repository, &repository.get_commit(&first).unwrap().tree, &PathBuf::new()).unwrap();
+    fs::write(repository.root.join("new.txt"), "new").expect("Issue when writing test file");
+    fs::write(repository.root.join("untracked.txt"), "untracked").expect("Issue when writing test file");
+    repository.add(&repository.root.join("new.txt")).expect("Issue when adding file");
+
+    let oid = repository.commit_with_options("Second", &test_commit_options()).expect("Issue when committing");
+    let files: HashMap<PathBuf, Oid> = get_tree(&repository, &repository.get_commit(&oid).unwrap().tree, &PathBuf::new()).unwrap().into_iter().collect();
+    for (path, oid) in &tracked {
+      assert_eq!(files.get(path), Some(oid));
+    }
+    assert!(files.contains_key(Path::new("new.txt")));
+    assert!(!files.contains_key(Path::new("untracked.txt")));
+    cleanup();
+  }
+
+  #[test]
+  #[serial]
+  fn move_path_moves_files_along_with_their_index_entries() {
+    let (_, cleanup) = create_test_directory();
+    // Without an index, only the working directory changes
+    move_path(Path::new("style.css"), Path::new("main.css"), false).expect("Issue when moving file");
+    assert!(Path::new("main.css").is_file() && !Path::new("style.css").exists());
+    assert!(data::get_index().unwrap().is_empty());
+
+    add(Path::new(".")).expect("Issue when adding everything");
+    let staged = data::get_index().unwrap();
+    move_path(Path::new("One"), Path::new("Three"), false).expect("Issue when moving directory");
+    move_path(Path::new("main.css"), Path::new("Three"), false).expect("Issue when moving file into directory");
+    let index = data::get_index().unwrap();
+    assert_eq!(index["Three/Two/.SuperSecretFile"], staged["One/Two/.SuperSecretFile"]);
+    assert_eq!(index["Three/main.css"], staged["main.css"]);
+    assert!(!index.keys().any(|key| key.starts_with("One/") || key == "main.css"));
+    assert!(Path::new("Three/main.css").is_file());
+
+    assert_eq!(move_path(Path::new("index.html"), Path::new("Three/main.css"), false).unwrap_err().kind(), ErrorKind::AlreadyExists);
+    move_path(Path::new("index.html"), Path::new("Three/main.css"), true).expect("Issue when replacing file");
+    assert_eq!(data::get_index().unwrap()["Three/main.css"], staged["index.html"]);
+    assert_eq!(move_path(Path::new("Three"), Path::new("Three/Four"), true).unwrap_err().kind(), ErrorKind::InvalidInput);
+    assert_eq!(move_path(Path::new("missing"), Path::new("other"), false).unwrap_err().kind(), ErrorKind::NotFound);
+    assert_eq!(move_path(Path::new("Three/main.css"), Path::new("Four/main.css"), false).unwrap_err().kind(), ErrorKind::NotFound);
+    cleanup();
+  }
+
+  #[test]
+  fn write_tree_from_index_matches_write_tree_when_everything_is_staged() {
+    let (repository, cleanup) = create_in_memory_test_directory("index-tree");
+    repository.add(&repository.root.join(".")).expect("Issue when adding everything");
+    assert_eq!(repository.write_tree_from_index().unwrap(), repository.write_tree().unwrap());
+    cleanup();
+  }
+
+  #[test]
+  #[serial]
+  fn commit_all_snapshots_the_working_directory_and_updates_the_index() {
+    let (_, cleanup) = create_test_directory();
+    fs::write("staged.txt", "staged").expect("Issue when writing test file");
+    add(Path::new("staged.txt")).expect("Issue when adding file");
+    fs::write("unstaged.txt", "unstaged").expect("Issue when writing test file");
+
+    let oid = commit_all("Everything", &CommitOptions::default()).expect("Issue when committing");
+    let tree = get_commit(&oid).unwrap().tree;
+    assert_eq!(tree, write_tree().unwrap());
+    assert!(data::get_index().unwrap().contains_key("unstaged.txt"));
+    assert_eq!(Repository::find().unwrap().write_tree_from_index().unwrap(), tree);
+    cleanup();
+  }
+
+  #[test]
+  #[serial]
+  fn ls_tree_lists_a_tree_level_or_every_file_beneath_it() {
+    let (_, cleanup) = create_test_directory();
+    let head = commit("First", false).expect("Issue when committing");
+    let tree = get_commit(&head).unwrap().tree;
+
+    let entries = ls_tree(&head, false).unwrap();
+    assert_eq!(entries, ls_tree(&tree, false).unwrap());
+    assert!(entries.iter().all(|(_, _, _, path)| path.components().count() == 1));
+    let subtree = entries.iter().find(|(_, object_type, _, _)| object_type == "tree").expect("Tree has no subtrees");
+    assert_eq!(subtree.0, TREE_MODE);
+
+    let files = ls_tree(&head, true).unwrap();
+    assert!(files.iter().all(|(mode, object_type, _, _)| mode == FILE_MODE && object_type == "blob"));
+    assert!(files.iter().any(|(_, _, _, path)| path.starts_with(&subtree.3) && path != &subtree.3));
+    let mut paths: Vec<_> = files.into_iter().map(|(_, _, _, path)| path).collect();
+    paths.sort();
+    let mut expected: Vec<_> = get_tree(&Repository::find().unwrap(), &tree, &PathBuf::new()).unwrap().into_iter().map(|(path, _)| path).collect();
+    expected.sort();
+    assert_eq!(paths, expected);
+    cleanup();
+  }
+
+  #[test]
+  fn get_tree_reads_entries_written_without_a_mode() {
+    let (repository, cleanup) = create_in_memory_test_directory("modeless");
+    let blob_oid = repository.hash_object(b"old", ObjectType::Blob).expect("Issue when hashing blob");
+    let tree = format!("blob {} old.txt", blob_oid);
+    let oid = repository.hash_object(tree.as_bytes(), ObjectType::Tree).expect("Issue when hashing tree");
+
+    let entries = get_tree_entries(&repository, &oid, &PathBuf::new()).expect("Issue when reading tree");
+    assert_eq!(entries, vec![(PathBuf::from("old.txt"), blob_oid, String::from(FILE_MODE))]);
+    cleanup();
+  }
+
+  #[test]
+  #[serial]
+  fn read_tree_restores_the_whole_repository_from_a_subdirectory() {
+    let (_, cleanup) = create_test_directory();
+    let oid = write_tree().expect("Issue when writing tree");
+    fs::write("index.html", "changed").expect("Issue when writing test file");
+    fs::write("added.txt", "added").expect("Issue when writing test file");
+
+    env::set_current_dir("One").expect("Issue when cding into subdirectory");
+    let result = read_tree_with_progress(&oid, true, &mut |_| ());
+    env::set_current_dir("..").expect("Issue when cding out of subdirectory");
+    result.expect("Issue when reading tree");
+
+    assert_eq!(fs::read_to_string("index.html").unwrap(), "");
+    assert!(!Path::new("added.txt").exists());
+    assert!(!Path::new("One").join("index.html").exists());
+    assert_eq!(Repository::find().unwrap().get_working_tree_oid().unwrap().0, oid);
+    cleanup();
+  }
+
+  #[test]
+  #[serial]
+  fn checkout_path_restores_only_the_given_file_or_directory_from_HEAD() {
+    let (_, cleanup) = create_test_directory();
+    fs::write("index.html", "committed").expect("Issue when writing test file");
+    fs::write(Path::new("One").join("Two").join("nested.txt"), "nested").expect("Issue when writing test file");
+    let head = commit("First", false).expect("Issue when committing");
+    fs::write("index.html", "changed").expect("Issue when writing test file");
+    fs::write("style.css", "changed").expect("Issue when writing test file");
+    fs::remove_dir_all("One").expect("Issue when removing directory");
+
+    checkout_path(Path::new("index.html")).expect("Issue when checking out path");
+    assert_eq!(fs::read_to_string("index.html").unwrap(), "committed");
+    assert_eq!(fs::read_to_string("style.css").unwrap(), "changed");
+
+    checkout_path(Path::new("One")).expect("Issue when checking out directory");
+    assert_eq!(fs::read_to_string(Path::new("One").join("Two").join("nested.txt")).unwrap(), "nested");
+    assert!(Path::new("One").join("Two").join(".SuperSecretFile").is_file());
+
+    assert_eq!(checkout_path(Path::new("missing.txt")).unwrap_err().kind(), ErrorKind::NotFound);
+    assert_eq!(data::get_head().unwrap().unwrap(), head);
+    assert_eq!(fs::read_to_string("style.css").unwrap(), "changed");
+    cleanup();
+  }
+
+  #[test]
+  fn read_tree_without_cleaning_leaves_files_the_tree_does_not_hold() {
+    let (repository, cleanup) = create_in_memory_test_directory("no-clean");
+    fs::write(repository.root.join("index.html"), "committed").expect("Issue when writing test file");
+    let oid = repository.write_tree().expect("Issue when writing tree");
+    fs::write(repository.root.join("index.html"), "changed").expect("Issue when writing test file");
+    fs::write(repository.root.join("untracked.txt"), "untracked").expect("Issue when writing test file");
+    fs::remove_dir_all(repository.root.join("One")).expect("Issue when removing directory");
+
+    repository.read_tree_with_progress(&oid, false, &mut |_| ()).expect("Issue when reading tree");
+    assert_eq!(fs::read_to_string(repository.root.join("index.html")).unwrap(), "committed");
+    assert_eq!(fs::read_to_string(repository.root.join("untracked.txt")).unwrap(), "untracked");
+    assert!(repository.root.join("One").join("Two").join(".SuperSecretFile").is_file());
+
+    repository.read_tree(&oid).expect("Issue when reading tree");
+    assert!(!repository.root.join("untracked.txt").exists());
+    cleanup();
+  }
+
+  #[test]
+  #[serial]
+  fn read_tree_returns_an_error_outside_of_a_repository() {
+    let (_, cleanup) = create_test_directory();
+    let oid = write_tree().expect("Issue when writing tree");
+    fs::remove_dir_all(".ugit").expect("Issue when removing repository");
+
+    let err = read_tree_with_progress(&oid, true, &mut |_| ()).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::NotFound);
+    assert!(Path::new("index.html").is_file());
+    cleanup();
+  }
+
+  #[test]
+  fn checkout_of_a_branch_makes_HEAD_symbolic_and_anything_else_detaches_it() {
+    let (repository, cleanup) = create_in_memory_test_directory("checkout");
+    let head_path = repository.generate_path(PathVariant::Head);
+    let first_oid = repository.commit_with_options("First", &test_commit_options()).expect("Issue when committing");
+    repository.create_branch("main", &first_oid).expect("Issue when creating branch");
+    let tag = RefValue { value: Some(RefTarget::Oid(first_oid.clone())), path: repository.generate_path(PathVariant::Ref(RefVariant::Tag("v1.0"))) };
+    repository.update_ref(&tag, true).expect("Issue when creating tag");
+
+    repository.checkout("main").expect("Issue when checking out branch");
+    assert_eq!(fs::read_to_string(&head_path).unwrap(), "ref:refs/heads/main");
+    let second_oid = repository.commit_with_options("Second", &CommitOptions { allow_empty: true, ..test_commit_options() }).expect("Issue when committing");
+    let branch_path = repository.generate_path(PathVariant::Ref(RefVariant::Head("main")));
+    assert_eq!(fs::read_to_string(&branch_path).unwrap(), second_oid);
+
+    repository.checkout(&first_oid).expect("Issue when checking out OID");
+    assert_eq!(fs::read_to_string(&head_path).unwrap(), first_oid);
+    repository.checkout("main").expect("Issue when checking out branch");
+    repository.checkout("v1.0").expect("Issue when checking out tag");
+    assert_eq!(fs::read_to_string(&head_path).unwrap(), first_oid);
+    assert_eq!(fs::read_to_string(&branch_path).unwrap(), second_oid);
+    cleanup();
+  }
+
+  #[test]
+  #[serial]
+  fn reset_moves_the_branch_without_touching_the_working_directory() {
+    let (_, cleanup) = create_test_directory();
+    let first_oid = commit("First", false).expect("Issue when committing");
+    create_branch("main", &first_oid).expect("Issue when creating branch");
+    checkout("main").expect("Issue when checking out");
+    fs::write("index.html", "second").expect("Issue when writing test file");
+    commit("Second", false).expect("Issue when committing");
+
+    reset(&first_oid).expect("Issue when resetting");
+    let branch_path = data::generate_path(PathVariant::Ref(RefVariant::Head("main"))).unwrap();
+    assert_eq!(fs::read_to_string(branch_path).unwrap(), first_oid);
+    assert_eq!(data::get_head().unwrap().unwrap(), first_oid);
+    assert_eq!(fs::read_to_string("index.html").unwrap(), "second");
+
+    let tree = get_commit(&first_oid).unwrap().tree;
+    let err = reset(&tree).expect_err("A tree should not be accepted");
+    assert_eq!(err.kind(), ErrorKind::InvalidInput);
+    cleanup();
+  }
+
+  #[test]
+  #[serial]
+  fn annotated_tag_stores_its_message_and_resolves_to_the_tagged_commit() {
+    let (_, cleanup) = create_test_directory();
+    let oid = commit("First", false).expect("Issue when committing");
+    let tag_oid = create_annotated_tag("v1.0", &oid, "First release\nWith notes", false).expect("Issue when creating tag");
+
+    let tag_path = data::generate_path(PathVariant::Ref(RefVariant::Tag("v1.0"))).unwrap();
+    assert_eq!(fs::read_to_string(tag_path).unwrap(), tag_oid);
+    let tag = get_tag(&tag_oid).expect("Issue when reading tag");
+    assert_eq!(tag.object, oid);
+    assert_eq!(tag.name, "v1.0");
+    assert_eq!(tag.message, "First release\nWith notes");
+    assert!(tag.tagger.is_some());
+
+    assert_eq!(try_resolve_as_ref("v1.0").unwrap(), oid);
+    assert_eq!(try_resolve_as_ref(&tag_oid).unwrap(), oid);
+    // As cat-file resolves its argument, so that the tag object itself can be printed
+    assert_eq!(resolve_object("v1.0").unwrap(), tag_oid);
+    assert_eq!(resolve_object(&tag_oid[..8]).unwrap(), tag_oid);
+    let contents = String::from_utf8(data::get_object(&tag_oid, ObjectType::Tag).unwrap()).unwrap();
+    assert!(contents.starts_with(&format!("object {}\n", oid)));
+    fs::write("index.html", "changed").expect("Issue when writing test file");
+    commit("Second", false).expect("Issue when committing");
+    checkout("v1.0").expect("Issue when checking out tag");
+    assert_eq!(data::get_head().unwrap().unwrap(), oid);
+    cleanup();
+  }
+
+  #[test]
+  #[serial]
+  fn tags_are_listed_sorted_and_only_overwritten_when_forced() {
+    let (_, cleanup) = create_test_directory();
+    let first = commit("First", false).expect("Issue when committing");
+    fs::write("index.html", "changed").expect("Issue when writing test file");
+    let second = commit("Second", false).expect("Issue when committing");
+    assert!(list_tags(None).unwrap().is_empty());
+
+    create_tag("v2", &second, false).expect("Issue when creating tag");
+    create_tag("v1", &first, false).expect("Issue when creating tag");
+    create_branch("v3", &first).expect("Issue when creating branch");
+    assert_eq!(list_tags(None).unwrap(), vec!["v1", "v2"]);
+
+    assert_eq!(create_tag("v1", &second, false).unwrap_err().kind(), ErrorKind::AlreadyExists);
+    assert_eq!(create_annotated_tag("v1", &second, "Again", false).unwrap_err().kind(), ErrorKind::AlreadyExists);
+    assert_eq!(try_resolve_as_ref("v1").unwrap(), first);
+
+    create_tag("v1", &second, true).expect("Issue when replacing tag");
+    assert_eq!(try_resolve_as_ref("v1").unwrap(), second);
+    let tag_oid = create_annotated_tag("v2", &first, "Replaced", true).expect("Issue when replacing tag");
+    assert_eq!(data::locate_ref_or_oid("v2", false).unwrap().unwrap(), tag_oid);
+    assert_eq!(try_resolve_as_ref("v2").unwrap(), first);
+    cleanup();
+  }
+
+  #[test]
+  #[serial]
+  fn rev_parse_resolves_refs_and_oids_and_rejects_unknown_names() {
+    let (_, cleanup) = create_test_directory();
+    let first = commit("First", false).expect("Issue when committing");
+    fs::write("index.html", "changed").expect("Issue when writing test file");
+    let second = commit("Second", false).expect("Issue when committing");
+    create_annotated_tag("v1", &first, "First release", false).expect("Issue when creating tag");
+    create_branch("main", &second).expect("Issue when creating branch");
+    checkout("main").expect("Issue when checking out");
+
+    assert_eq!(rev_parse("HEAD").unwrap(), second);
+    assert_eq!(rev_parse("@").unwrap(), second);
+    assert_eq!(rev_parse("main").unwrap(), second);
+    assert_eq!(rev_parse("v1").unwrap(), first);
+    assert_eq!(rev_parse(&first).unwrap(), first);
+    assert_eq!(rev_parse(&first[..8]).unwrap(), first);
+    assert_eq!(rev_parse("missing").unwrap_err().kind(), ErrorKind::NotFound);
+
+    create_branch("v1", &second).expect("Issue when creating branch");
+    assert_eq!(rev_parse("v1").unwrap_err().kind(), ErrorKind::InvalidInput);
+    cleanup();
+  }
+
+  #[test]
+  #[serial]
+  fn verify_revision_rejects_names_which_do_not_resolve_to_a_stored_object() {
+    let (_, cleanup) = create_test_directory();
+    let first = commit("First", false).expect("Issue when committing");
+    create_branch("main", &first).expect("Issue when creating branch");
+    assert_eq!(verify_revision("main").unwrap(), first);
+    assert_eq!(verify_revision("missing").unwrap_err().kind(), ErrorKind::NotFound);
+    assert_eq!(verify_revision(&"a".repeat(first.len())).unwrap_err().kind(), ErrorKind::NotFound);
+
+    Repository::find().unwrap().objects().delete_object(&first).expect("Issue when deleting commit");
+    assert_eq!(rev_parse("main").unwrap(), first);
+    assert_eq!(verify_revision("main").unwrap_err().kind(), ErrorKind::NotFound);
+    cleanup();
+  }
+
+  #[test]
+  #[serial]
+  fn blame_finds_the_last_commit_to_change_a_file() {
+    let (_, cleanup) = create_test_directory();
+    let first = commit("First", false).expect("Issue when committing");
+    fs::write("style.css", "body {}").expect("Issue when writing test file");
+    let second = commit("Second", false).expect("Issue when committing");
+    fs::write("index.html", "<html></html>").expect("Issue when writing test file");
+    let third = commit("Third", false).expect("Issue when committing");
+
+    assert_eq!(blame(Path::new("style.css")).unwrap(), second);
+    assert_eq!(blame(Path::new("index.html")).unwrap(), third);
+    assert_eq!(blame(Path::new("One/Two/.SuperSecretFile")).unwrap(), first);
+    // Changing a file back still changes it
+    fs::write("style.css", "").expect("Issue when writing test file");
+    let fourth = commit("Fourth", false).expect("Issue when committing");
+    assert_eq!(blame(Path::new("style.css")).unwrap(), fourth);
+
+    assert_eq!(blame(Path::new("missing.txt")).unwrap_err().kind(), ErrorKind::NotFound);
+    assert_eq!(blame(Path::new("One")).unwrap_err().kind(), ErrorKind::NotFound);
+    cleanup();
+  }
+
+  #[test]
+  #[serial]
+  fn count_objects_counts_objects_by_type() {
+    let (_, cleanup) = create_test_directory();
+    assert_eq!(count_objects().unwrap().count, 0);
+    let oid = commit("First", false).expect("Issue when committing");
+    create_annotated_tag("v1", &oid, "First release", false).expect("Issue when creating tag");
+
+    let counts = count_objects().expect("Issue when counting objects");
+    let repository = Repository::find().unwrap();
+    let size: u64 = repository.list_objects().unwrap().iter().map(|oid| fs::metadata(data::generate_path(PathVariant::OID(oid)).unwrap()).unwrap().len()).sum();
+    // index.html, style.css and .SuperSecretFile are all empty, so they share one blob
+    assert_eq!(counts.types, vec![("blob", 1), ("commit", 1), ("tag", 1), ("tree", 3)].into_iter().collect());
+    assert_eq!(counts.count, 6);
+    assert_eq!(counts.size, size);
+    cleanup();
+  }
+
+  #[test]
+  #[serial]
+  fn list_tags_filters_by_pattern() {
+    let (_, cleanup) = create_test_directory();
+    let oid = commit("First", false).expect("Issue when committing");
+    for name in &["v1.0", "v1.1", "v2.0", "release"] {
+      create_tag(name, &oid, false).expect("Issue when creating tag");
+    }
+
+    assert_eq!(list_tags(Some("v1.*")).unwrap(), vec!["v1.0", "v1.1"]);
+    assert_eq!(list_tags(Some("v?.0")).unwrap(), vec!["v1.0", "v2.0"]);
+    assert!(list_tags(Some("v3*")).unwrap().is_empty());
+    assert_eq!(list_tags(None).unwrap().len(), 4);
+    cleanup();
+  }
+
+  #[test]
+  #[serial]
+  fn describe_names_a_commit_after_the_nearest_tag() {
+    let (_, cleanup) = create_test_directory();
+    let first = commit("First", false).expect("Issue when committing");
+    assert_eq!(describe(&first).unwrap_err().kind(), ErrorKind::NotFound);
+
+    create_annotated_tag("v1", &first, "First release", false).expect("Issue when creating tag");
+    create_tag("a-v1", &first, false).expect("Issue when creating tag");
+    fs::write("index.html", "changed").expect("Issue when writing test file");
+    let second = commit("Second", false).expect("Issue when committing");
+    fs::write("style.css", "changed").expect("Issue when writing test file");
+    let third = commit("Third", false).expect("Issue when committing");
+
+    assert_eq!(describe(&first).unwrap(), "a-v1");
+    assert_eq!(describe(&third).unwrap(), format!("a-v1-2-g{}", &third[..10]));
+    create_tag("v2", &second, false).expect("Issue when creating tag");
+    assert_eq!(describe(&third).unwrap(), format!("v2-1-g{}", &third[..10]));
+    cleanup();
+  }
+
+  #[test]
+  #[serial]
+  fn describe_counts_every_commit_since_the_tag_across_a_merge() {
+    let (_, cleanup) = create_test_directory();
+    let tree = write_tree().expect("Issue when writing tree");
+    let options = CommitOptions::default();
+    let tagged = commit_tree(&tree, &[], "Tagged", &options).expect("Issue when committing");
+    let side_first = commit_tree(&tree, std::slice::from_ref(&tagged), "Side 1", &options).expect("Issue when committing");
+    let side_second = commit_tree(&tree, &[side_first], "Side 2", &options).expect("Issue when committing");
+    let main = commit_tree(&tree, std::slice::from_ref(&tagged), "Main", &options).expect("Issue when committing");
+    let merge = commit_tree(&tree, &[main, side_second], "Merge", &options).expect("Issue when committing");
+    create_tag("v1", &tagged, false).expect("Issue when creating tag");
+
+    // The tagged commit is the fourth one walked, but four commits are not reachable from it
+    assert_eq!(describe(&merge).unwrap(), format!("v1-4-g{}", &merge[..10]));
+    cleanup();
+  }
+
+  #[test]
+  #[serial]
+  fn get_ref_decorations_names_the_refs_pointing_to_each_commit() {
+    let (_, cleanup) = create_test_directory();
+    let first = commit("First", false).expect("Issue when committing");
+    fs::write("index.html", "changed").expect("Issue when writing test file");
+    let second = commit("Second", false).expect("Issue when committing");
+    create_tag("v1", &first, false).expect("Issue when creating tag");
+    create_annotated_tag("v2", &second, "Second release", false).expect("Issue when creating tag");
+    create_branch("side", &first).expect("Issue when creating branch");
+    create_branch("main", &second).expect("Issue when creating branch");
+    checkout("main").expect("Issue when checking out");
+
+    let decorations = get_ref_decorations().unwrap();
+    assert_eq!(decorations[&second], vec!["HEAD -> main", "tag: v2"]);
+    assert_eq!(decorations[&first], vec!["side", "tag: v1"]);
+
+    checkout(&first).expect("Issue when checking out");
+    let decorations = get_ref_decorations().unwrap();
+    assert_eq!(decorations[&second], vec!["main", "tag: v2"]);
+    assert_eq!(decorations[&first], vec!["HEAD", "side", "tag: v1"]);
+    cleanup();
+  }
+
+  #[test]
+  #[serial]
+  fn delete_branch_refuses_the_branch_HEAD_points_to() {
+    let (_, cleanup) = create_test_directory();
+    let oid = commit("First", false).expect("Issue when committing");
+    create_branch("main", &oid).expect("Issue when creating branch");
+    create_branch("feature", &oid).expect("Issue when creating branch");
+    checkout("main").expect("Issue when checking out");
+
+    assert_eq!(delete_branch("main").unwrap_err().kind(), ErrorKind::InvalidInput);
+    delete_branch("feature").expect("Issue when deleting branch");
+    assert!(!data::generate_path(PathVariant::Ref(RefVariant::Head("feature"))).unwrap().exists());
+    assert!(data::generate_path(PathVariant::Ref(RefVariant::Head("main"))).unwrap().is_file());
+    assert_eq!(delete_tag("missing").unwrap_err().kind(), ErrorKind::NotFound);
+    cleanup();
+  }
+
+  #[test]
+  #[serial]
+  fn rename_branch_moves_the_branch_and_HEAD_along_with_it() {
+    let (_, cleanup) = create_test_directory();
+    let oid = commit("First", false).expect("Issue when committing");
+    create_branch("feature", &oid).expect("Issue when creating branch");
+
+    rename_branch("main", "trunk").expect("Issue when renaming branch");
+    let head_path = data::generate_path(PathVariant::Head).unwrap();
+    assert_eq!(fs::read_to_string(&head_path).unwrap(), "ref:refs/heads/trunk");
+    assert!(!data::generate_path(PathVariant::Ref(RefVariant::Head("main"))).unwrap().exists());
+    assert_eq!(fs::read_to_string(data::generate_path(PathVariant::Ref(RefVariant::Head("trunk"))).unwrap()).unwrap(), oid);
+
+    // HEAD is left alone when another branch is renamed
+    rename_branch("feature", "topic").expect("Issue when renaming branch");
+    assert_eq!(fs::read_to_string(&head_path).unwrap(), "ref:refs/heads/trunk");
+    assert_eq!(try_resolve_as_ref("topic").unwrap(), oid);
+
+    assert_eq!(rename_branch("topic", "trunk").unwrap_err().kind(), ErrorKind::AlreadyExists);
+    assert_eq!(rename_branch("missing", "other").unwrap_err().kind(), ErrorKind::NotFound);
+    assert_eq!(rename_branch("topic", "../evil").unwrap_err().kind(), ErrorKind::InvalidInput);
+    assert!(data::generate_path(PathVariant::Ref(RefVariant::Head("topic"))).unwrap().is_file());
+    cleanup();
+  }
+
+  #[test]
+  #[serial]
+  fn creating_a_ref_with_a_malicious_name_writes_nothing() {
+    let (_, cleanup) = create_test_directory();
+    let oid = commit("First", false).expect("Issue when committing");
+    let objects_before = fs::read_dir(data::generate_path(PathVariant::Objects).unwrap()).unwrap().count();
+    for name in &["../../evil", "nested/tag", "..", ".hidden"] {
+      assert_eq!(create_tag(name, &oid, false).unwrap_err().kind(), ErrorKind::InvalidInput);
+      assert_eq!(create_branch(name, &oid).unwrap_err().kind(), ErrorKind::InvalidInput);
+      assert_eq!(create_annotated_tag(name, &oid, "Message", false).unwrap_err().kind(), ErrorKind::InvalidInput);
+    }
+
+    assert!(!Path::new("evil").exists());
+    assert!(!data::generate_path(PathVariant::Tags).unwrap().join("nested").exists());
+    assert_eq!(fs::read_dir(data::generate_path(PathVariant::Objects).unwrap()).unwrap().count(), objects_before);
+    cleanup();
+  }
+
+  #[test]
+  fn merge_base_returns_the_first_common_ancestor() {
+    let (repository, cleanup) = create_in_memory_test_directory("merge-base");
+    let root_oid = repository.commit_with_options("Root", &test_commit_options()).expect("Issue when committing");
+    fs::write(repository.root.join("index.html"), "a").expect("Issue when writing test file");
+    let base_oid = repository.commit_with_options("Base", &test_commit_options()).expect("Issue when committing");
+    fs::write(repository.root.join("index.html"), "b").expect("Issue when writing test file");
+    let first_oid = repository.commit_with_options("First", &test_commit_options()).expect("Issue when committing");
+    repository.set_head(RefTarget::Oid(base_oid.clone()), "checkout").expect("Issue when setting HEAD");
+    fs::write(repository.root.join("index.html"), "c").expect("Issue when writing test file");
+    let second_oid = repository.commit_with_options("Second", &test_commit_options()).expect("Issue when committing");
+
+    assert_eq!(repository.merge_base(&first_oid, &second_oid).unwrap(), base_oid);
+    assert_eq!(repository.merge_base(&second_oid, &first_oid).unwrap(), base_oid);
+    assert_eq!(repository.merge_base(&first_oid, &base_oid).unwrap(), base_oid);
+    assert_eq!(repository.merge_base(&root_oid, &second_oid).unwrap(), root_oid);
+
+    // A second root commit shares no history with the first
+    let tree = repository.get_commit(&root_oid).unwrap().tree;
+    let orphan = format!("tree {}\n\nOrphan", tree);
+    let orphan_oid = repository.hash_object(orphan.as_bytes(), ObjectType::Commit).expect("Issue when hashing commit");
+    assert_eq!(repository.merge_base(&orphan_oid, &second_oid).unwrap_err().kind(), ErrorKind::NotFound);
+    cleanup();
+  }
+
+  #[test]
+  fn merge_combines_changes_from_both_sides_into_a_commit_with_two_parents() {
+    let (repository, cleanup) = create_in_memory_test_directory("merge");
+    let base_oid = repository.commit_with_options("Base", &test_commit_options()).expect("Issue when committing");
+    repository.create_branch("main", &base_oid).expect("Issue when creating branch");
+    repository.create_branch("feature", &base_oid).expect("Issue when creating branch");
+    repository.checkout("main").expect("Issue when checking out");
+    fs::write(repository.root.join("index.html"), "main").expect("Issue when writing test file");
+    let main_oid = repository.commit_with_options("Main", &test_commit_options()).expect("Issue when committing");
+    repository.checkout("feature").expect("Issue when checking out");
+    fs::write(repository.root.join("style.css"), "feature").expect("Issue when writing test file");
+    fs::remove_dir_all(repository.root.join("One")).expect("Issue when removing test directory");
+    let feature_oid = repository.commit_with_options("Feature", &test_commit_options()).expect("Issue when committing");
+    repository.checkout("main").expect("Issue when checking out");
+
+    let oid = match repository.merge(&feature_oid, "feature", &test_commit_options()).expect("Issue when merging") {
+      MergeResult::Merged(oid) => oid,
+      _ => panic!("Expected a merge commit")
     };
 
-    let file_func = |node: &DirNode| {
-      let path = Path::new(&node.name);
-      assert!(path.is_file());
-      true
+    let merge_commit = repository.get_commit(&oid).unwrap();
+    assert_eq!(merge_commit.parents, vec![main_oid, feature_oid]);
+    assert_eq!(merge_commit.message, "Merge feature");
+    assert_eq!(fs::read_to_string(repository.root.join("index.html")).unwrap(), "main");
+    assert_eq!(fs::read_to_string(repository.root.join("style.css")).unwrap(), "feature");
+    assert!(!repository.root.join("One").exists());
+    assert!(!repository.generate_path(PathVariant::MergeHead).exists());
+    let branch_path = repository.generate_path(PathVariant::Ref(RefVariant::Head("main")));
+    assert_eq!(fs::read_to_string(branch_path).unwrap(), oid);
+    cleanup();
+  }
+
+  #[test]
+  fn merge_writes_conflict_markers_and_commits_after_resolution() {
+    let (repository, cleanup) = create_in_memory_test_directory("conflicts");
+    let base_oid = repository.commit_with_options("Base", &test_commit_options()).expect("Issue when committing");
+    repository.create_branch("main", &base_oid).expect("Issue when creating branch");
+    repository.create_branch("feature", &base_oid).expect("Issue when creating branch");
+    repository.checkout("main").expect("Issue when checking out");
+    fs::write(repository.root.join("index.html"), "main").expect("Issue when writing test file");
+    let main_oid = repository.commit_with_options("Main", &test_commit_options()).expect("Issue when committing");
+    repository.checkout("feature").expect("Issue when checking out");
+    fs::write(repository.root.join("index.html"), "feature\n").expect("Issue when writing test file");
+    let feature_oid = repository.commit_with_options("Feature", &test_commit_options()).expect("Issue when committing");
+    repository.checkout("main").expect("Issue when checking out");
+
+    match repository.merge(&feature_oid, "feature", &test_commit_options()).expect("Issue when merging") {
+      MergeResult::Conflicts(conflicts) => assert_eq!(conflicts, vec![PathBuf::from("index.html")]),
+      _ => panic!("Expected a conflict")
     };
 
-    let next_tree = DirNode {
-      name: String::from("."),
-      children: dir_tree.children.clone(),
+    let contents = fs::read_to_string(repository.root.join("index.html")).unwrap();
+    assert_eq!(contents, "<<<<<<< HEAD\nmain\n=======\nfeature\n>>>>>>> feature\n");
+
+    fs::write(repository.root.join("index.html"), "resolved").expect("Issue when writing test file");
+    let oid = repository.commit_with_options("Resolved", &test_commit_options()).expect("Issue when committing");
+    assert_eq!(repository.get_commit(&oid).unwrap().parents, vec![main_oid, feature_oid]);
+    cleanup();
+  }
+
+  #[test]
+  #[serial]
+  fn cherry_pick_applies_only_the_changes_of_the_picked_commit() {
+    let (_, cleanup) = create_test_directory();
+    let base_oid = commit("Base", false).expect("Issue when committing");
+    create_branch("main", &base_oid).expect("Issue when creating branch");
+    create_branch("feature", &base_oid).expect("Issue when creating branch");
+    checkout("feature").expect("Issue when checking out");
+    fs::write("style.css", "feature").expect("Issue when writing test file");
+    commit("Style", false).expect("Issue when committing");
+    fs::write("index.html", "feature\n").expect("Issue when writing test file");
+    let picked_oid = commit("Index\n\nBody", false).expect("Issue when committing");
+    checkout("main").expect("Issue when checking out");
+
+    let oid = match cherry_pick(&picked_oid, "feature").expect("Issue when cherry-picking") {
+      CherryPickResult::Picked(oid) => oid,
+      _ => panic!("Expected a commit")
     };
 
-    next_tree.foreach(dir_func, file_func);
-    env::set_current_dir(&dir_tree.name).expect("Issue when cding to test directory");
+    let picked = get_commit(&oid).unwrap();
+    assert_eq!(picked.parents, vec![base_oid]);
+    assert_eq!(picked.message, "Index\n\nBody");
+    assert_eq!(fs::read_to_string("index.html").unwrap(), "feature\n");
+    assert_eq!(fs::read_to_string("style.css").unwrap(), "");
+    assert_eq!(data::get_head().unwrap().unwrap(), oid);
+
+    // Changing the file since the picked commit's parent conflicts with the picked change
+    fs::write("index.html", "main\n").expect("Issue when writing test file");
+    let main_oid = commit("Main", false).expect("Issue when committing");
+    match cherry_pick(&picked_oid, "feature").expect("Issue when cherry-picking") {
+      CherryPickResult::Conflicts(conflicts) => assert_eq!(conflicts, vec![PathBuf::from("index.html")]),
+      _ => panic!("Expected a conflict")
+    };
+
+    assert!(fs::read_to_string("index.html").unwrap().starts_with("<<<<<<< HEAD\n"));
+    assert!(!data::generate_path(PathVariant::MergeHead).unwrap().exists());
+    fs::write("index.html", "resolved").expect("Issue when writing test file");
+    let oid = commit("Resolved", false).expect("Issue when committing");
+    assert_eq!(get_commit(&oid).unwrap().parents, vec![main_oid]);
+    cleanup();
+  }
+
+  #[test]
+  #[serial]
+  fn cherry_pick_refuses_to_overwrite_local_changes_and_keeps_the_picked_author_through_a_conflict() {
+    let (_, cleanup) = create_test_directory();
+    let base_oid = commit("Base", false).expect("Issue when committing");
+    fs::write("index.html", "picked\n").expect("Issue when writing test file");
+    fs::write("new.txt", "picked").expect("Issue when writing test file");
+    let options = CommitOptions { author: Some(String::from("Picked Author <picked@example.com>")), ..CommitOptions::default() };
+    let picked_oid = commit_with_options("Picked\n\nBody", &options).expect("Issue when committing");
+    checkout(&base_oid).expect("Issue when checking out");
+
+    // Neither a local edit nor an untracked file at a path the picked commit touches is overwritten
+    fs::write("index.html", "local\n").expect("Issue when writing test file");
+    assert_eq!(cherry_pick(&picked_oid, "picked").err().map(|err| err.kind()), Some(ErrorKind::InvalidInput));
+    assert_eq!(fs::read_to_string("index.html").unwrap(), "local\n");
+    let main_oid = commit("Main", false).expect("Issue when committing");
+    fs::write("new.txt", "untracked").expect("Issue when writing test file");
+    assert_eq!(cherry_pick(&picked_oid, "picked").err().map(|err| err.kind()), Some(ErrorKind::InvalidInput));
+    assert_eq!(fs::read_to_string("new.txt").unwrap(), "untracked");
+    assert_eq!(data::get_head().unwrap().unwrap(), main_oid);
+    fs::remove_file("new.txt").expect("Issue when removing test file");
+
+    match cherry_pick(&picked_oid, "picked").expect("Issue when cherry-picking") {
+      CherryPickResult::Conflicts(conflicts) => assert_eq!(conflicts, vec![PathBuf::from("index.html")]),
+      _ => panic!("Expected a conflict")
+    };
+
+    // The picked commit is kept until the commit concluding the cherry-pick takes its author
+    assert_eq!(get_picked_commit().unwrap().unwrap().message, "Picked\n\nBody");
+    assert_eq!(cherry_pick(&picked_oid, "picked").err().map(|err| err.kind()), Some(ErrorKind::InvalidInput));
+    fs::write("index.html", "resolved\n").expect("Issue when writing test file");
+    let oid = commit("Picked\n\nBody", false).expect("Issue when committing");
+    let concluded = get_commit(&oid).unwrap();
+    assert_eq!(concluded.parents, vec![main_oid]);
+    assert_eq!(concluded.author.as_deref(), Some("Picked Author <picked@example.com>"));
+    assert!(get_picked_commit().unwrap().is_none());
+    cleanup();
+  }
+
+  #[test]
+  fn stash_sets_changes_aside_until_popped() {
+    let (repository, cleanup) = create_in_memory_test_directory("stash");
+    assert_eq!(repository.stash(&test_commit_options()).unwrap_err().kind(), ErrorKind::NotFound);
+    let base_oid = repository.commit_with_options("Base", &test_commit_options()).expect("Issue when committing");
+    assert_eq!(repository.stash(&test_commit_options()).unwrap_err().kind(), ErrorKind::InvalidInput);
+    assert_eq!(repository.stash_pop().unwrap_err().kind(), ErrorKind::NotFound);
+
+    fs::write(repository.root.join("index.html"), "stashed").expect("Issue when writing test file");
+    fs::write(repository.root.join("new.txt"), "new").expect("Issue when writing test file");
+    fs::remove_file(repository.root.join("style.css")).expect("Issue when removing test file");
+    let stash_oid = repository.stash(&test_commit_options()).expect("Issue when stashing");
+    assert_eq!(repository.get_commit(&stash_oid).unwrap().parents, vec![base_oid.clone()]);
+    assert_eq!(fs::read_to_string(repository.root.join("index.html")).unwrap(), "");
+    assert!(!repository.root.join("new.txt").exists());
+    assert!(repository.root.join("style.css").is_file());
+    assert_eq!(repository.get_head().unwrap().unwrap(), base_oid);
+
+    fs::write(repository.root.join("index.html"), "again").expect("Issue when writing test file");
+    assert_eq!(repository.stash(&test_commit_options()).unwrap_err().kind(), ErrorKind::AlreadyExists);
+    fs::write(repository.root.join("index.html"), "").expect("Issue when writing test file");
+
+    // Commits made since stashing are kept
+    fs::write(repository.root.join("One/Two/.SuperSecretFile"), "committed").expect("Issue when writing test file");
+    repository.commit_with_options("Since", &test_commit_options()).expect("Issue when committing");
+    assert!(repository.stash_pop().expect("Issue when popping stash").is_empty());
+    assert_eq!(fs::read_to_string(repository.root.join("index.html")).unwrap(), "stashed");
+    assert_eq!(fs::read_to_string(repository.root.join("new.txt")).unwrap(), "new");
+    assert!(!repository.root.join("style.css").exists());
+    assert_eq!(fs::read_to_string(repository.root.join("One/Two/.SuperSecretFile")).unwrap(), "committed");
+    assert!(!repository.generate_path(PathVariant::Stash).exists());
+    assert_eq!(repository.stash_pop().unwrap_err().kind(), ErrorKind::NotFound);
+    cleanup();
+  }
+
+  #[test]
+  fn stash_pop_refuses_to_overwrite_local_changes() {
+    let (repository, cleanup) = create_in_memory_test_directory("stash-pop");
+    repository.commit_with_options("Base", &test_commit_options()).expect("Issue when committing");
+    fs::write(repository.root.join("index.html"), "stashed").expect("Issue when writing test file");
+    fs::remove_file(repository.root.join("style.css")).expect("Issue when removing test file");
+    repository.stash(&test_commit_options()).expect("Issue when stashing");
+
+    // Both a file the stash changes and one it removes count, while other local changes do not stop the pop
+    fs::write(repository.root.join("index.html"), "local").expect("Issue when writing test file");
+    assert_eq!(repository.stash_pop().unwrap_err().kind(), ErrorKind::InvalidInput);
+    assert_eq!(fs::read_to_string(repository.root.join("index.html")).unwrap(), "local");
+    fs::write(repository.root.join("index.html"), "").expect("Issue when writing test file");
+    fs::write(repository.root.join("style.css"), "local").expect("Issue when writing test file");
+    assert_eq!(repository.stash_pop().unwrap_err().kind(), ErrorKind::InvalidInput);
+    assert_eq!(fs::read_to_string(repository.root.join("style.css")).unwrap(), "local");
+    assert!(repository.generate_path(PathVariant::Stash).is_file());
+
+    fs::write(repository.root.join("style.css"), "").expect("Issue when writing test file");
+    fs::write(repository.root.join("One/Two/.SuperSecretFile"), "local").expect("Issue when writing test file");
+    assert!(repository.stash_pop().expect("Issue when popping stash").is_empty());
+    assert_eq!(fs::read_to_string(repository.root.join("index.html")).unwrap(), "stashed");
+    assert!(!repository.root.join("style.css").exists());
+    assert_eq!(fs::read_to_string(repository.root.join("One/Two/.SuperSecretFile")).unwrap(), "local");
+    cleanup();
+  }
+
+  #[test]
+  #[cfg(unix)]
+  fn stash_pop_restores_modes_and_symlinks() {
+    use std::os::unix::fs::PermissionsExt;
+    let (repository, cleanup) = create_in_memory_test_directory("stash-modes");
+    fs::write(repository.root.join("a.txt"), "a").expect("Issue when writing test file");
+    std::os::unix::fs::symlink("a.txt", repository.root.join("link")).expect("Issue when creating symlink");
+    repository.commit_with_options("Base", &test_commit_options()).expect("Issue when committing");
+
+    fs::write(repository.root.join("run.sh"), "#!/bin/sh\n").expect("Issue when writing test file");
+    fs::set_permissions(repository.root.join("run.sh"), fs::Permissions::from_mode(0o755)).expect("Issue when setting permissions");
+    fs::set_permissions(repository.root.join("index.html"), fs::Permissions::from_mode(0o755)).expect("Issue when setting permissions");
+    fs::remove_file(repository.root.join("link")).expect("Issue when removing symlink");
+    std::os::unix::fs::symlink("index.html", repository.root.join("link")).expect("Issue when creating symlink");
+    std::os::unix::fs::symlink("a.txt", repository.root.join("new_link")).expect("Issue when creating symlink");
+    repository.stash(&test_commit_options()).expect("Issue when stashing");
+    assert_eq!(fs::read_link(repository.root.join("link")).unwrap(), PathBuf::from("a.txt"));
+    assert_eq!(fs::metadata(repository.root.join("index.html")).unwrap().permissions().mode() & 0o777, 0o644);
+
+    assert!(repository.stash_pop().expect("Issue when popping stash").is_empty());
+    assert_eq!(fs::metadata(repository.root.join("run.sh")).unwrap().permissions().mode() & 0o777, 0o755);
+    assert_eq!(fs::metadata(repository.root.join("index.html")).unwrap().permissions().mode() & 0o777, 0o755);
+    assert_eq!(fs::read_link(repository.root.join("link")).unwrap(), PathBuf::from("index.html"));
+    assert_eq!(fs::read_link(repository.root.join("new_link")).unwrap(), PathBuf::from("a.txt"));
+    assert_eq!(fs::read_to_string(repository.root.join("a.txt")).unwrap(), "a");
+    assert_eq!(fs::read_to_string(repository.root.join("index.html")).unwrap(), "");
+    cleanup();
+  }
+
+  #[test]
+  #[cfg(unix)]
+  fn merge_keeps_symlinks_and_modes_of_merged_files() {
+    use std::os::unix::fs::PermissionsExt;
+    let (repository, cleanup) = create_in_memory_test_directory("merge-modes");
+    fs::write(repository.root.join("a.txt"), "a").expect("Issue when writing test file");
+    std::os::unix::fs::symlink("a.txt", repository.root.join("link")).expect("Issue when creating symlink");
+    let base_oid = repository.commit_with_options("Base", &test_commit_options()).expect("Issue when committing");
+    repository.create_branch("main", &base_oid).expect("Issue when creating branch");
+    repository.create_branch("side", &base_oid).expect("Issue when creating branch");
+    repository.checkout("side").expect("Issue when checking out");
+    fs::write(repository.root.join("run.sh"), "#!/bin/sh\n").expect("Issue when writing test file");
+    fs::set_permissions(repository.root.join("run.sh"), fs::Permissions::from_mode(0o755)).expect("Issue when setting permissions");
+    fs::write(repository.root.join("style.css"), "side").expect("Issue when writing test file");
+    let side_oid = repository.commit_with_options("Side", &test_commit_options()).expect("Issue when committing");
+    repository.checkout("main").expect("Issue when checking out");
+    fs::write(repository.root.join("index.html"), "main").expect("Issue when writing test file");
+    repository.commit_with_options("Main", &test_commit_options()).expect("Issue when committing");
+
+    let oid = match repository.merge(&side_oid, "side", &test_commit_options()).expect("Issue when merging") {
+      MergeResult::Merged(oid) => oid,
+      _ => panic!("Expected a merge commit")
+    };
+
+    // The symlink is recreated rather than written through
+    assert_eq!(fs::read_to_string(repository.root.join("a.txt")).unwrap(), "a");
+    assert_eq!(fs::read_link(repository.root.join("link")).unwrap(), PathBuf::from("a.txt"));
+    assert_eq!(fs::metadata(repository.root.join("run.sh")).unwrap().permissions().mode() & 0o777, 0o755);
+    let entries: HashMap<PathBuf, (Oid, String)> = get_tree_entries(&repository, &repository.get_commit(&oid).unwrap().tree, Path::new(""))
+      .unwrap()
+      .into_iter()
+      .map(|(path, oid, mode)| (path, (oid, mode)))
+      .collect();
+        assert_eq!(entries[Path::new("a.txt")], (repository.get_oid(b"a", ObjectType::Blob), String::from(FILE_MODE)));
+    assert_eq!(entries[Path::new("link")], (repository.get_oid(b"a.txt", ObjectType::Blob), String::from(SYMLINK_MODE)));
+    assert_eq!(entries[Path::new("run.sh")].1, EXECUTABLE_MODE);
+    cleanup();
+  }
+
+  #[test]
+  #[serial]
+  #[cfg(unix)]
+  fn cherry_pick_keeps_and_creates_symlinks() {
+    let (_, cleanup) = create_test_directory();
+    fs::write("a.txt", "a").expect("Issue when writing test file");
+    std::os::unix::fs::symlink("a.txt", "link").expect("Issue when creating symlink");
+    let base_oid = commit("Base", false).expect("Issue when committing");
+    create_branch("main", &base_oid).expect("Issue when creating branch");
+    create_branch("feature", &base_oid).expect("Issue when creating branch");
+    checkout("feature").expect("Issue when checking out");
+    fs::write("style.css", "feature").expect("Issue when writing test file");
+    std::os::unix::fs::symlink("index.html", "new_link").expect("Issue when creating symlink");
+    let picked_oid = commit("Links", false).expect("Issue when committing");
+    checkout("main").expect("Issue when checking out");
+
+    match cherry_pick(&picked_oid, "feature").expect("Issue when cherry-picking") {
+      CherryPickResult::Picked(_) => (),
+      _ => panic!("Expected a commit")
+    };
+
+    // The kept symlink is not written through, and the picked one is created as a symlink
+    assert_eq!(fs::read_to_string("a.txt").unwrap(), "a");
+    assert_eq!(fs::read_link("link").unwrap(), PathBuf::from("a.txt"));
+    assert_eq!(fs::read_link("new_link").unwrap(), PathBuf::from("index.html"));
+    assert_eq!(fs::read_to_string("style.css").unwrap(), "feature");
+    assert!(status().unwrap().changes.is_empty());
+    cleanup();
+  }
+
+  #[test]
+  fn merge_fast_forwards_when_HEAD_is_an_ancestor() {
+    let (repository, cleanup) = create_in_memory_test_directory("fast-forward");
+    let base_oid = repository.commit_with_options("Base", &test_commit_options()).expect("Issue when committing");
+    fs::write(repository.root.join("index.html"), "ahead").expect("Issue when writing test file");
+    let ahead_oid = repository.commit_with_options("Ahead", &test_commit_options()).expect("Issue when committing");
+    repository.checkout(&base_oid).expect("Issue when checking out");
+
+    assert!(matches!(repository.merge(&ahead_oid, "ahead", &test_commit_options()).unwrap(), MergeResult::FastForward));
+    assert_eq!(repository.get_head().unwrap().unwrap(), ahead_oid);
+    assert_eq!(fs::read_to_string(repository.root.join("index.html")).unwrap(), "ahead");
+    assert!(matches!(repository.merge(&base_oid, "base", &test_commit_options()).unwrap(), MergeResult::UpToDate));
+    cleanup();
+  }
+
+  #[test]
+  fn merge_refuses_to_overwrite_local_changes_and_leaves_untracked_files_alone() {
+    let (repository, cleanup) = create_in_memory_test_directory("merge-local");
+    let base_oid = repository.commit_with_options("Base", &test_commit_options()).expect("Issue when committing");
+    fs::write(repository.root.join("index.html"), "ahead").expect("Issue when writing test file");
+    fs::write(repository.root.join("new.txt"), "ahead").expect("Issue when writing test file");
+    let ahead_oid = repository.commit_with_options("Ahead", &test_commit_options()).expect("Issue when committing");
+    repository.checkout(&base_oid).expect("Issue when checking out");
+    fs::write(repository.root.join("style.css"), "side").expect("Issue when writing test file");
+    let side_oid = repository.commit_with_options("Side", &test_commit_options()).expect("Issue when committing");
+
+    // Neither a local edit nor an untracked file at a path the merge writes is overwritten
+    fs::write(repository.root.join("index.html"), "local").expect("Issue when writing test file");
+    let err = repository.merge(&ahead_oid, "ahead", &test_commit_options()).err().expect("Merge overwrote local changes");
+    assert_eq!(err.kind(), ErrorKind::InvalidInput);
+    assert!(err.to_string().contains("index.html"));
+    assert_eq!(fs::read_to_string(repository.root.join("index.html")).unwrap(), "local");
+    assert!(!repository.root.join("new.txt").exists());
+    assert!(!repository.generate_path(PathVariant::MergeHead).exists());
+    repository.checkout(&side_oid).expect("Issue when checking out");
+    fs::write(repository.root.join("new.txt"), "untracked").expect("Issue when writing test file");
+    assert_eq!(repository.merge(&ahead_oid, "ahead", &test_commit_options()).err().map(|err| err.kind()), Some(ErrorKind::InvalidInput));
+    assert_eq!(fs::read_to_string(repository.root.join("new.txt")).unwrap(), "untracked");
+    assert_eq!(repository.get_head().unwrap().unwrap(), side_oid);
+
+    // The same goes for a fast-forward, while untracked files elsewhere are kept
+    repository.checkout(&base_oid).expect("Issue when checking out");
+    fs::write(repository.root.join("new.txt"), "untracked").expect("Issue when writing test file");
+    assert_eq!(repository.merge(&ahead_oid, "ahead", &test_commit_options()).err().map(|err| err.kind()), Some(ErrorKind::InvalidInput));
+    assert_eq!(fs::read_to_string(repository.root.join("new.txt")).unwrap(), "untracked");
+    fs::remove_file(repository.root.join("new.txt")).expect("Issue when removing test file");
+    fs::write(repository.root.join("untracked.txt"), "untracked").expect("Issue when writing test file");
+    assert!(matches!(repository.merge(&ahead_oid, "ahead", &test_commit_options()).unwrap(), MergeResult::FastForward));
+    assert_eq!(fs::read_to_string(repository.root.join("new.txt")).unwrap(), "ahead");
+    assert_eq!(fs::read_to_string(repository.root.join("untracked.txt")).unwrap(), "untracked");
     cleanup();
   }
 
+  // A repository of its own under the temporary directory, keeping its objects in memory, for tests which can run alongside others
+  // as they leave the current directory alone
+  fn create_in_memory_repository(name: &str) -> (Repository, impl Fn()) {
+    let root = env::temp_dir().join(format!("ugit-{}-{}", name, std::process::id()));
+    if root.exists() {
+      fs::remove_dir_all(&root).expect("Issue when cleaning up possible leftovers");
+    }
+
+    let repository = Repository::init_in_memory(&root, ".ugit", "main", ObjectFormat::Sha256).expect("Issue when initializing repository");
+    (repository, move || fs::remove_dir_all(&root).expect("Issue when deleting test repository"))
+  }
+
+  // Like create_in_memory_repository, but fills the root with the same files create_test_directory does
+  fn create_in_memory_test_directory(name: &str) -> (Repository, impl Fn()) {
+    let (repository, cleanup) = create_in_memory_repository(name);
+    fs::create_dir_all(repository.root.join("One").join("Two")).expect("Issue when creating test directory");
+    for path in &["index.html", "style.css", "One/Two/.SuperSecretFile"] {
+      fs::write(repository.root.join(path), "").expect("Issue when writing test file");
+    }
+
+    (repository, cleanup)
+  }
+
+  // Commit options which read nothing from the environment, which tests running alongside these may be changing
+  fn test_commit_options() -> CommitOptions {
+    CommitOptions { author: Some(String::from("Jane Doe <jane@example.com>")), timestamp: Some(5), ..CommitOptions::default() }
+  }
+
+  // Commits to the repository in the current directory, as the command line does without any options
+  fn commit(message: &str, allow_empty: bool) -> std::io::Result<Oid> {
+    commit_with_options(message, &CommitOptions { allow_empty, ..CommitOptions::default() })
+  }
+
   fn create_test_directory() -> (DirNode, impl Fn()) {
     let dir_tree = DirNode::default();
     let root = PathBuf::from(&dir_tree.name);
@@ -429,7 +4161,7 @@ mod tests {
 
     create_test_directory_recur(&dir_tree, PathBuf::new());
     env::set_current_dir(&root).expect("Issue when cding one up from test directory");
-    data::init().expect("Issue when initing test repository");
+    data::init(ObjectFormat::Sha256).expect("Issue when initing test repository");
     (
       dir_tree, move || {
         env::set_current_dir("..").expect("Issue when cding one up from test directory");
@@ -1,39 +1,99 @@
+use std::collections::HashMap;
+use std::env;
 use std::fs;
-use std::path::Path;
+use std::io::{self, IsTerminal, Read};
+use std::path::{Path, PathBuf};
+use std::process::Command;
 
 use clap::{App, Arg, SubCommand};
 
 use crate::base;
+use base::{ChangeKind, CherryPickResult, CommitOptions, FileStatus, MergeResult, ObjectProblem};
 use crate::data;
-use data::ObjectType;
+use crate::diff;
+use crate::utils;
+use data::{Commit, ObjectFormat, ObjectType, Oid, PathVariant, RefTarget, Tag};
+
+// How many files write-tree and read-tree process between reports of their progress
+static PROGRESS_INTERVAL: usize = 100;
 
 pub fn cli() -> std::io::Result<()> {
   let matches = App::new(env!("CARGO_PKG_NAME"))
     .version(env!("CARGO_PKG_VERSION"))
     .author(env!("CARGO_PKG_AUTHORS"))
     .about(env!("CARGO_PKG_DESCRIPTION"))
+    .arg(Arg::with_name("dir")
+      .long("dir")
+      .short("C")
+      .takes_value(true)
+      .value_name("PATH")
+      .help("Runs as if started in PATH, which paths given to the subcommand are then relative to"))
+    .arg(Arg::with_name("no-ancestor-search")
+      .long("no-ancestor-search")
+      .global(true)
+      .help("Only uses a repository in the current directory, rather than one in any directory above it"))
     .subcommand(SubCommand::with_name("init")
-      .about("Creates a new ugit repository"))
+      .about("Creates a new ugit repository")
+      .arg(Arg::with_name("object-format")
+        .long("object-format")
+        .takes_value(true)
+        .value_name("FORMAT")
+        .possible_values(&["sha1", "sha256"])
+        .default_value("sha256")
+        .help("The hash function objects are named by"))
+      .arg(Arg::with_name("force")
+        .long("force")
+        .help("When a repository already exists, recreates any of its directories which are missing instead of failing. Existing \
+          files, including the object format, are left as they are")))
     .subcommand(SubCommand::with_name("hash-object")
-      .about("Returns the SHA2 hash of a file")
+      .about("Returns the hash of a file")
       .arg(Arg::with_name("FILE")
-        .help("The path to a file to be hashed")
-        .required(true)
-        .index(1)))
+        .help("The path to a file to be hashed. Without FILE, or given -, standard input is hashed instead")
+        .index(1))
+      .arg(object_type_arg()
+        .short("t")
+        .help("The type of object to be stored")))
     .subcommand(SubCommand::with_name("cat-file")
       .about("Writes contents of file with given OID to stdout")
       .arg(Arg::with_name("OID")
         .help("The resulting hash of a file that has previously been hashed by the hash-object command")
         .required(true)
-        .index(1)))
+        .index(1))
+      .arg(object_type_arg()
+        .help("The type the object is expected to be"))
+      .arg(Arg::with_name("pretty")
+        .long("pretty")
+        .short("p")
+        .help("Prints the object whatever its type, as read from the object itself. A tree is listed one entry per line, by mode, type, OID and name"))
+      .arg(Arg::with_name("show-type")
+        .long("show-type")
+        .short("t")
+        .conflicts_with_all(&["pretty", "size"])
+        .help("Prints the object's type instead of its contents"))
+      .arg(Arg::with_name("size")
+        .long("size")
+        .short("s")
+        .conflicts_with("pretty")
+        .help("Prints the size of the object's contents in bytes instead of the contents")))
     .subcommand(SubCommand::with_name("write-tree")
-      .about("Stores current working directory to the object database"))
+      .about("Stores current working directory to the object database")
+      .arg(Arg::with_name("PATH")
+        .help("A directory within the repository to store instead of the whole working directory")
+        .index(1))
+      .arg(Arg::with_name("print-tree")
+        .long("print-tree")
+        .help("Also prints every entry of the stored tree by name and OID, indented by depth"))
+      .arg(quiet_arg()))
     .subcommand(SubCommand::with_name("read-tree")
       .about("Replaces current working directory with the one stored under provided OID")
       .arg(Arg::with_name("OID")
         .help("The resulting hash of the current working directory that has previously been hashed by the write-tree command")
         .required(true)
-        .index(1)))
+        .index(1))
+      .arg(Arg::with_name("no-clean")
+        .long("no-clean")
+        .help("Writes the tree's files over the working directory, leaving files the tree does not hold in place"))
+      .arg(quiet_arg()))
     .subcommand(SubCommand::with_name("commit")
       .about("Creates a new snapshot of the observed directory with a description")
       .arg(Arg::with_name("message")
@@ -41,151 +101,1402 @@ pub fn cli() -> std::io::Result<()> {
         .short("m")
         .takes_value(true)
         .value_name("TEXT")
+        .help("Description of the new commit. Without TEXT, the description is read from standard input when it is not a terminal, \
+          and otherwise written in $VISUAL or $EDITOR"))
+      .arg(Arg::with_name("allow-empty-message")
+        .long("allow-empty-message")
+        .help("Commits even when the description is empty"))
+      .arg(Arg::with_name("all")
+        .long("all")
+        .short("a")
+        .help("Commits the whole observed directory, even when files have been staged with add"))
+      .arg(Arg::with_name("allow-empty")
+        .long("allow-empty")
+        .help("Commits even when nothing changed since HEAD"))
+      .arg(Arg::with_name("amend")
+        .long("amend")
+        .help("Replaces HEAD's commit instead of adding to it, keeping its description unless given another"))
+      .arg(Arg::with_name("author")
+        .long("author")
+        .takes_value(true)
+        .value_name("AUTHOR")
+        .help("Records AUTHOR, given as \"Name <email>\", instead of the configured author"))
+      .arg(Arg::with_name("date")
+        .long("date")
+        .takes_value(true)
+        .value_name("SECONDS")
+        .validator(validate_timestamp)
+        .help("Records the commit as made SECONDS after the Unix epoch, instead of now or UGIT_COMMIT_DATE")))
+    .subcommand(SubCommand::with_name("commit-tree")
+      .about("Stores a commit of an already stored tree and prints its OID, without moving HEAD")
+      .arg(Arg::with_name("TREE")
+        .help("The tree to commit, such as one printed by the write-tree command")
+        .required(true)
+        .index(1))
+      .arg(Arg::with_name("parent")
+        .short("p")
+        .takes_value(true)
+        .value_name("PARENT")
+        .multiple(true)
+        .number_of_values(1)
+        .help("A parent of the new commit, given once per parent"))
+      .arg(Arg::with_name("message")
+        .long("message")
+        .short("m")
+        .takes_value(true)
+        .value_name("TEXT")
+        .help("Description of the new commit. Without TEXT, the description is read as it is for the commit command")))
+    .subcommand(SubCommand::with_name("add")
+      .about("Stages files to be committed. Once anything is staged, commits hold only staged files")
+      .arg(Arg::with_name("PATH")
+        .help("The files or directories to be staged")
+        .required(true)
+        .multiple(true)
+        .index(1)))
+    .subcommand(SubCommand::with_name("rm")
+      .about("Unstages files, and removes them from the observed directory")
+      .arg(Arg::with_name("PATH")
+        .help("The files or directories to be unstaged")
+        .required(true)
+        .multiple(true)
+        .index(1))
+      .arg(Arg::with_name("cached")
+        .long("cached")
+        .help("Only unstages the files, leaving them in the observed directory")))
+    .subcommand(SubCommand::with_name("mv")
+      .about("Moves or renames a file or directory, along with whatever of it is staged")
+      .arg(Arg::with_name("SOURCE")
+        .help("The file or directory to be moved")
         .required(true)
-        .help("Description of the new commit")))
+        .index(1))
+      .arg(Arg::with_name("DESTINATION")
+        .help("Where SOURCE is moved to. Given an existing directory, SOURCE is moved into it")
+        .required(true)
+        .index(2))
+      .arg(Arg::with_name("force")
+        .long("force")
+        .short("f")
+        .help("Replaces DESTINATION if it already exists")))
     .subcommand(SubCommand::with_name("log")
       .about("Prints descending list of commits")
       .arg(Arg::with_name("OID")
         .help("An optional starting point. By default, it will start from HEAD")
+        .index(1))
+      .arg(Arg::with_name("oneline")
+        .long("oneline")
+        .help("Prints each commit on a single line, as its abbreviated OID and the first line of its message"))
+      .arg(Arg::with_name("depth")
+        .long("depth")
+        .takes_value(true)
+        .value_name("N")
+        .validator(validate_positive_integer)
+        .help("Stops after printing N commits"))
+      .arg(Arg::with_name("graph")
+        .long("graph")
+        .help("Draws lines to the left of the commits, connecting each to its parents"))
+      .arg(Arg::with_name("date-order")
+        .long("date-order")
+        .conflicts_with("graph")
+        .help("Prints commits newest first by the time they were made, rather than in the order their parents are walked"))
+      .arg(Arg::with_name("format")
+        .long("format")
+        .takes_value(true)
+        .value_name("FORMAT")
+        .conflicts_with("oneline")
+        .help("Prints each commit as FORMAT, in which %H is replaced by its OID, %h its abbreviated OID, %s the first line of its \
+          message, %P and %p its parents' full and abbreviated OIDs, %an, %ae and %ad its author's name, email and date, %d the \
+          refs pointing to it, %n a newline and %% a %. Anything else is printed as it is")))
+    .subcommand(SubCommand::with_name("show")
+      .about("Prints a commit along with the changes it introduced")
+      .arg(Arg::with_name("OID")
+        .help("The commit to be shown. By default, HEAD is shown")
+        .index(1)))
+    .subcommand(SubCommand::with_name("reflog")
+      .about("Prints where a ref has pointed, most recent first")
+      .arg(Arg::with_name("REF")
+        .help("HEAD, a branch, a tag, or a full ref name such as refs/heads/main. By default, HEAD's updates are printed")
         .index(1)))
     .subcommand(SubCommand::with_name("checkout")
-      .about("Sets HEAD to given commit OID, and updates observed directory with the contents of that commit")
+      .about("Sets HEAD to given branch or commit OID, and updates observed directory with the contents of that commit")
+      .arg(Arg::with_name("OID")
+        .help("The branch or commit identifier to set HEAD to. Checking out anything other than a branch detaches HEAD")
+        .required_unless("PATH")
+        .conflicts_with("PATH")
+        .index(1))
+      .arg(Arg::with_name("PATH")
+        .help("Files or directories to restore from HEAD, given after --, leaving HEAD and every other file as they are")
+        .multiple(true)
+        .last(true)))
+    .subcommand(SubCommand::with_name("reset")
+      .about("Moves HEAD, or the branch it points to, to the given commit without updating the observed directory")
       .arg(Arg::with_name("OID")
-        .help("The commit identifier to set HEAD to")
+        .help("The commit to move HEAD to")
         .required(true)
         .index(1)))
     .subcommand(SubCommand::with_name("tag")
-      .about("Creates an alias NAME for either the given OID or HEAD")
+      .about("Creates an alias NAME for either the given OID or HEAD, or lists every tag when given no NAME")
       .arg(Arg::with_name("NAME")
         .help("The name of the tag to be created")
-        .required(true)
         .index(1))
       .arg(Arg::with_name("OID")
         .help("An optional commit OID to be aliased")
         .required(false)
-        .index(2)))
+        .conflicts_with("delete")
+        .index(2))
+      .arg(Arg::with_name("delete")
+        .long("delete")
+        .short("d")
+        .conflicts_with("message")
+        .requires("NAME")
+        .help("Deletes the tag NAME instead of creating it"))
+      .arg(Arg::with_name("message")
+        .long("message")
+        .short("m")
+        .takes_value(true)
+        .value_name("TEXT")
+        .requires("NAME")
+        .help("Creates an annotated tag, which stores TEXT along with who created the tag"))
+      .arg(Arg::with_name("force")
+        .long("force")
+        .short("f")
+        .conflicts_with("delete")
+        .requires("NAME")
+        .help("Replaces the tag NAME if it already exists"))
+      .arg(Arg::with_name("list")
+        .long("list")
+        .short("l")
+        .takes_value(true)
+        .min_values(0)
+        .max_values(1)
+        .value_name("PATTERN")
+        .conflicts_with("NAME")
+        .help("Lists every tag, or only those whose names match PATTERN, in which '*' matches any run of characters and '?' any one")))
     .subcommand(SubCommand::with_name("branch")
       .about("Creates a new branch")
       .arg(Arg::with_name("NAME")
         .help("The name of the branch to be created")
-        .required(true)
+        .required_unless("move")
         .index(1))
       .arg(Arg::with_name("OID")
         .help("An optional commit OID for the branch to be started from")
         .required(false)
+        .conflicts_with("delete")
+        .index(2))
+      .arg(Arg::with_name("delete")
+        .long("delete")
+        .short("d")
+        .help("Deletes the branch NAME instead of creating it. The branch HEAD points to cannot be deleted"))
+      .arg(Arg::with_name("move")
+        .long("move")
+        .short("m")
+        .number_of_values(2)
+        .value_names(&["OLD", "NEW"])
+        .conflicts_with_all(&["NAME", "delete"])
+        .help("Renames the branch OLD to NEW, which must not already exist. HEAD is kept on the branch if it pointed to OLD")))
+    .subcommand(SubCommand::with_name("config")
+      .about("Reads or sets a value in the repository's config")
+      .arg(Arg::with_name("KEY")
+        .help("The key to be read or set, such as user.name")
+        .required(true)
+        .index(1))
+      .arg(Arg::with_name("VALUE")
+        .help("The value KEY is to be set to. Without VALUE, the current value of KEY is printed")
+        .index(2)))
+    .subcommand(SubCommand::with_name("fsck")
+      .about("Checks every stored object for corruption and for references to missing objects"))
+    .subcommand(SubCommand::with_name("status")
+      .about("Shows the current branch, and files which differ from the commit HEAD points to"))
+    .subcommand(SubCommand::with_name("merge")
+      .about("Merges the history of a branch or commit into HEAD")
+      .arg(Arg::with_name("OID")
+        .help("The branch or commit to be merged")
+        .required(true)
+        .index(1)))
+    .subcommand(SubCommand::with_name("cherry-pick")
+      .about("Applies the changes a commit made onto HEAD as a new commit")
+      .arg(Arg::with_name("OID")
+        .help("The branch or commit whose changes are applied")
+        .required(true)
+        .index(1)))
+    .subcommand(SubCommand::with_name("stash")
+      .about("Sets the changes in the observed directory aside, restoring HEAD's snapshot")
+      .subcommand(SubCommand::with_name("pop")
+        .about("Applies the changes set aside by stash, and drops them")))
+    .subcommand(SubCommand::with_name("merge-base")
+      .about("Prints the best common ancestor of two commits")
+      .arg(Arg::with_name("FIRST")
+        .help("A branch or commit")
+        .required(true)
+        .index(1))
+      .arg(Arg::with_name("SECOND")
+        .help("Another branch or commit")
+        .required(true)
+        .index(2)))
+    .subcommand(SubCommand::with_name("rev-parse")
+      .about("Prints the full OID a ref, HEAD, or abbreviated OID resolves to")
+      .arg(Arg::with_name("REF")
+        .help("A branch, tag, HEAD, or full or abbreviated OID")
+        .required(true)
+        .index(1))
+      .arg(Arg::with_name("verify")
+        .long("verify")
+        .help("Exits with 1 unless REF resolves to an object which exists, for use as a check in scripts")))
+    .subcommand(SubCommand::with_name("describe")
+      .about("Names a commit after the nearest tag reachable from it")
+      .arg(Arg::with_name("REF")
+        .help("The commit to be described. Without REF, HEAD is described")
+        .index(1)))
+    .subcommand(SubCommand::with_name("gc")
+      .about("Deletes every object which cannot be reached from a ref, the reflog, or the index")
+      .arg(Arg::with_name("dry-run")
+        .long("dry-run")
+        .short("n")
+        .help("Only lists the objects which would be deleted")))
+    .subcommand(SubCommand::with_name("count-objects")
+      .about("Counts the objects in the repository and how much space they take up")
+      .arg(Arg::with_name("verbose")
+        .short("v")
+        .long("verbose")
+        .help("Also breaks the count down by object type, and by loose and packed objects")))
+    .subcommand(SubCommand::with_name("export")
+      .about("Prints the history of every branch and tag as a stream for git fast-import"))
+    .subcommand(SubCommand::with_name("ls-tree")
+      .about("Lists the entries of a tree, or of a commit's tree")
+      .arg(Arg::with_name("OID")
+        .help("A tree, or a branch, tag or commit whose tree is listed")
+        .required(true)
+        .index(1))
+      .arg(Arg::with_name("recursive")
+        .short("r")
+        .help("Lists every file beneath the tree by its path, instead of only the tree's own entries")))
+    .subcommand(SubCommand::with_name("blame")
+      .about("Prints the most recent commit to change a file, walking back from HEAD")
+      .arg(Arg::with_name("PATH")
+        .help("A file in HEAD's tree")
+        .required(true)
+        .index(1)))
+    .subcommand(SubCommand::with_name("show-ref")
+      .about("Lists every ref along with the OID it resolves to, and the ref a symbolic ref points at"))
+    .subcommand(SubCommand::with_name("k")
+      .about("Draws every commit reachable from a ref, along with the refs pointing to each")
+      .arg(Arg::with_name("dot")
+        .long("dot")
+        .help("Prints the graph in Graphviz's dot format instead")))
+    .subcommand(SubCommand::with_name("diff")
+      .about("Shows changes between two commits or trees")
+      .arg(Arg::with_name("FROM")
+        .help("The commit or tree OID to compare from")
+        .required(true)
+        .index(1))
+      .arg(Arg::with_name("TO")
+        .help("The commit or tree OID to compare to")
+        .required(true)
         .index(2)))
     .get_matches();
 
-  if let Some(_) = matches.subcommand_matches("init") {
-    init()?;
+  // Changed before anything else, as the repository is looked for from the current directory
+  if let Some(dir) = matches.value_of("dir") {
+    if !Path::new(dir).is_dir() {
+      return Err(io::Error::new(io::ErrorKind::NotFound, format!("Cannot run in a path which is not a directory [{}]", dir)));
+    }
+
+    env::set_current_dir(dir)?;
+  }
+
+  // Without the ancestor search, the repository has to be directly within the current directory. Opening it there up front is
+  // enough, as data reuses the repository last opened and any search for one starts from the current directory anyway.
+  let search_ancestors = !matches.is_present("no-ancestor-search");
+  if !search_ancestors && matches.subcommand_name() != Some("init") {
+    data::Repository::open(&env::current_dir()?)?;
+  }
+
+  if let Some(matches) = matches.subcommand_matches("init") {
+    // Can simply unwrap, as FORMAT has a default value and is limited to valid formats by clap
+    let object_format = data::parse_object_format(matches.value_of("object-format").unwrap())?;
+    init(object_format, matches.is_present("force"), search_ancestors)?;
   }
   else if let Some(matches) = matches.subcommand_matches("hash-object") {
-    // Can simply unwrap, as FILE arg's presence is required by clap
-    let file = Path::new(matches.value_of("FILE").unwrap());
-    hash_object(&file)?;
+    let file = match matches.value_of("FILE") {
+      Some("-") | None => None,
+      Some(file) => Some(Path::new(file))
+    };
+
+    // Can simply unwrap, as TYPE has a default value and is limited to valid types by clap
+    let object_type = data::parse_object_type(matches.value_of("type").unwrap())?;
+    hash_object(file, object_type)?;
   }
   else if let Some(matches) = matches.subcommand_matches("cat-file") {
-    // Can simply unwrap, as OID arg's presence is required by clap
-    let oid = base::try_resolve_as_ref(matches.value_of("OID").unwrap())?;
-    cat_file(&oid)?;
+    // Can simply unwrap, as OID arg's presence is required by clap. Tags are not peeled, so that a tag object can be printed.
+    let oid = base::resolve_object(matches.value_of("OID").unwrap())?;
+    if matches.is_present("show-type") || matches.is_present("size") {
+      let (object_type, size) = data::object_info(&oid)?;
+      if matches.is_present("show-type") {
+        println!("{}", data::type_header(object_type));
+      }
+      else {
+        println!("{}", size);
+      }
+
+      return Ok(());
+    }
+
+    let object_type = if matches.is_present("pretty") {
+      data::get_object_type(&oid)?
+    }
+    else {
+      // Can simply unwrap, as TYPE has a default value and is limited to valid types by clap
+      data::parse_object_type(matches.value_of("type").unwrap())?
+    };
+
+    cat_file(&oid, object_type, matches.is_present("pretty"))?;
   }
-  else if let Some(_) = matches.subcommand_matches("write-tree") {
-    write_tree()?;
+  else if let Some(matches) = matches.subcommand_matches("write-tree") {
+    write_tree(matches.value_of("PATH"), matches.is_present("quiet"), matches.is_present("print-tree"))?;
   }
   else if let Some(matches) = matches.subcommand_matches("read-tree") {
     // Can simply unwrap, as OID arg's presence is required by clap
     let oid = base::try_resolve_as_ref(matches.value_of("OID").unwrap())?;
-    read_tree(&oid)?;
+    read_tree(&oid, matches.is_present("quiet"), !matches.is_present("no-clean"))?;
   }
   else if let Some(matches) = matches.subcommand_matches("commit") {
-    // Can simply unwrap, as TEXT arg's presence is required by clap
-    let message = matches.value_of("message").unwrap();
-    commit(&message)?;
+    let amend = matches.is_present("amend");
+    let message = match (matches.value_of("message"), data::get_head()) {
+      (Some(message), _) => String::from(message),
+      (None, Some(head)) if amend => base::get_commit(&head?)?.message,
+      // Rather than having the description written only for the commit to be refused
+      (None, None) if amend => return Err(io::Error::new(io::ErrorKind::InvalidInput, "There is no commit to amend, HEAD does not point to one")),
+      // Concluding a cherry-pick which stopped on conflicts keeps the picked commit's message
+      (None, _) => match base::get_picked_commit()? {
+        Some(picked) => picked.message,
+        None => read_commit_message()?
+      }
+    };
+
+    if message.trim().is_empty() && !matches.is_present("allow-empty-message") {
+      return Err(io::Error::new(io::ErrorKind::InvalidInput, "Aborting commit due to empty commit message, use --allow-empty-message to commit anyway"));
+    }
+
+    let options = CommitOptions {
+      allow_empty: matches.is_present("allow-empty"),
+      author: matches.value_of("author").map(String::from),
+      // Already validated by clap
+      timestamp: matches.value_of("date").map(|date| date.parse().unwrap()),
+      amend,
+    };
+
+    commit(&message, matches.is_present("all"), &options)?;
+  }
+  else if let Some(matches) = matches.subcommand_matches("commit-tree") {
+    // Can simply unwrap, as TREE arg's presence is required by clap
+    let tree = base::try_resolve_as_ref(matches.value_of("TREE").unwrap())?;
+    let parents = match matches.values_of("parent") {
+      Some(parents) => parents.map(base::try_resolve_as_ref).collect::<std::io::Result<Vec<_>>>()?,
+      None => Vec::new()
+    };
+
+    let message = match matches.value_of("message") {
+      Some(message) => String::from(message),
+      None => read_commit_message()?
+    };
+
+    println!("{}", base::commit_tree(&tree, &parents, &message, &CommitOptions::default())?);
+  }
+  else if let Some(matches) = matches.subcommand_matches("add") {
+    // Can simply unwrap, as PATH arg's presence is required by clap
+    for path in matches.values_of("PATH").unwrap() {
+      base::add(Path::new(path))?;
+    }
+  }
+  else if let Some(matches) = matches.subcommand_matches("rm") {
+    // Can simply unwrap, as PATH arg's presence is required by clap
+    for path in matches.values_of("PATH").unwrap() {
+      base::remove(Path::new(path), matches.is_present("cached"))?;
+    }
+  }
+  else if let Some(matches) = matches.subcommand_matches("mv") {
+    // Can simply unwrap, as SOURCE and DESTINATION args' presence is required by clap
+    let source = Path::new(matches.value_of("SOURCE").unwrap());
+    let destination = Path::new(matches.value_of("DESTINATION").unwrap());
+    base::move_path(source, destination, matches.is_present("force"))?;
   }
   else if let Some(matches) = matches.subcommand_matches("log") {
+    // Before the first commit, HEAD has no history to print
+    if matches.value_of("OID").is_none() && data::get_head().is_none() {
+      return Ok(());
+    }
+
     let oid = base::try_resolve_as_ref(matches.value_of("OID").unwrap_or("@"))?;
-    log(&oid)?;
+    // Can simply unwrap, as N has already been validated by clap
+    let depth = matches.value_of("depth").map(|depth| depth.parse().unwrap());
+    let (graph, date_order) = (matches.is_present("graph"), matches.is_present("date-order"));
+    log(&oid, matches.is_present("oneline"), depth, graph, date_order, matches.value_of("format"))?;
+  }
+  else if let Some(matches) = matches.subcommand_matches("show") {
+    let name = matches.value_of("OID").unwrap_or("@");
+    let oid = base::try_resolve_as_ref(name)?;
+    show(name, &oid)?;
+  }
+  else if let Some(matches) = matches.subcommand_matches("reflog") {
+    reflog(matches.value_of("REF").unwrap_or("HEAD"))?;
   }
   else if let Some(matches) = matches.subcommand_matches("checkout") {
+    // clap requires either OID or PATH
+    match matches.values_of("PATH") {
+      Some(paths) => for path in paths {
+        base::checkout_path(Path::new(path))?;
+      },
+      None => checkout(matches.value_of("OID").unwrap())?
+    };
+  }
+  else if let Some(matches) = matches.subcommand_matches("reset") {
     // Can simply unwrap, as OID arg's presence is required by clap
     let oid = base::try_resolve_as_ref(matches.value_of("OID").unwrap())?;
-    checkout(&oid)?;
+    reset(&oid)?;
   }
   else if let Some(matches) = matches.subcommand_matches("tag") {
-    // Can simply unwrap, as NAME arg's presence is required by clap
-    let name = matches.value_of("NAME").unwrap();
-    let oid = base::try_resolve_as_ref(matches.value_of("OID").unwrap_or("@"))?;
-    tag(&name, &oid)?;
+    // -d and -m require NAME, so without it tags are only being listed
+    match matches.value_of("NAME") {
+      None => list_tags(matches.value_of("list"))?,
+      Some(name) if matches.is_present("delete") => base::delete_tag(name)?,
+      Some(name) => {
+        let oid = base::try_resolve_as_ref(matches.value_of("OID").unwrap_or("@"))?;
+        tag(name, &oid, matches.value_of("message"), matches.is_present("force"))?;
+      }
+    };
   }
   else if let Some(matches) = matches.subcommand_matches("branch") {
-    // Can simply unwrap, as NAME arg's presence is required by clap
-    let name = matches.value_of("NAME").unwrap();
-    let oid = base::try_resolve_as_ref(matches.value_of("OID").unwrap_or("@"))?;
-    branch(&name, &oid)?;
+    if let Some(names) = matches.values_of("move") {
+      // Can simply index, as clap requires exactly two values
+      let names: Vec<_> = names.collect();
+      base::rename_branch(names[0], names[1])?;
+    }
+    // Can simply unwrap NAME, as its presence is required by clap unless renaming
+    else if matches.is_present("delete") {
+      base::delete_branch(matches.value_of("NAME").unwrap())?;
+    }
+    else {
+      let name = matches.value_of("NAME").unwrap();
+      let oid = base::try_resolve_as_ref(matches.value_of("OID").unwrap_or("@"))?;
+      branch(name, &oid)?;
+    }
+  }
+  else if let Some(matches) = matches.subcommand_matches("config") {
+    // Can simply unwrap, as KEY arg's presence is required by clap
+    config(matches.value_of("KEY").unwrap(), matches.value_of("VALUE"))?;
+  }
+  else if matches.subcommand_matches("fsck").is_some() {
+    fsck()?;
+  }
+  else if matches.subcommand_matches("status").is_some() {
+    status()?;
+  }
+  else if let Some(matches) = matches.subcommand_matches("merge") {
+    // Can simply unwrap, as OID arg's presence is required by clap
+    let name = matches.value_of("OID").unwrap();
+    let oid = base::try_resolve_as_ref(name)?;
+    merge(&oid, name)?;
+  }
+  else if let Some(matches) = matches.subcommand_matches("cherry-pick") {
+    // Can simply unwrap, as OID arg's presence is required by clap
+    let name = matches.value_of("OID").unwrap();
+    let oid = base::try_resolve_as_ref(name)?;
+    cherry_pick(&oid, name)?;
+  }
+  else if let Some(matches) = matches.subcommand_matches("stash") {
+    stash(matches.subcommand_matches("pop").is_some())?;
+  }
+  else if let Some(matches) = matches.subcommand_matches("merge-base") {
+    // Can simply unwrap, as FIRST and SECOND args' presence is required by clap
+    let first = base::try_resolve_as_ref(matches.value_of("FIRST").unwrap())?;
+    let second = base::try_resolve_as_ref(matches.value_of("SECOND").unwrap())?;
+    merge_base(&first, &second)?;
+  }
+  else if let Some(matches) = matches.subcommand_matches("rev-parse") {
+    // Can simply unwrap, as REF arg's presence is required by clap
+    rev_parse(matches.value_of("REF").unwrap(), matches.is_present("verify"))?;
+  }
+  else if let Some(matches) = matches.subcommand_matches("describe") {
+    let oid = base::try_resolve_as_ref(matches.value_of("REF").unwrap_or("@"))?;
+    describe(&oid)?;
+  }
+  else if let Some(matches) = matches.subcommand_matches("gc") {
+    gc(matches.is_present("dry-run"))?;
+  }
+  else if let Some(matches) = matches.subcommand_matches("count-objects") {
+    count_objects(matches.is_present("verbose"))?;
+  }
+  else if matches.subcommand_matches("export").is_some() {
+    base::fast_export(&mut io::stdout().lock())?;
+  }
+  else if let Some(matches) = matches.subcommand_matches("ls-tree") {
+    // Can simply unwrap, as OID arg's presence is required by clap
+    let oid = base::try_resolve_as_ref(matches.value_of("OID").unwrap())?;
+    ls_tree(&oid, matches.is_present("recursive"))?;
+  }
+  else if let Some(matches) = matches.subcommand_matches("blame") {
+    // Can simply unwrap, as PATH arg's presence is required by clap
+    blame(Path::new(matches.value_of("PATH").unwrap()))?;
+  }
+  else if matches.subcommand_matches("show-ref").is_some() {
+    show_ref()?;
+  }
+  else if let Some(matches) = matches.subcommand_matches("k") {
+    k(matches.is_present("dot"))?;
+  }
+  else if let Some(matches) = matches.subcommand_matches("diff") {
+    // Can simply unwrap, as FROM and TO args' presence is required by clap
+    let from = base::try_resolve_as_ref(matches.value_of("FROM").unwrap())?;
+    let to = base::try_resolve_as_ref(matches.value_of("TO").unwrap())?;
+    diff(&from, &to)?;
   }
 
   Ok(())
 }
 
-fn init() -> std::io::Result<()> {
-  data::init()?;
-  println!("Creating new ugit repository...");
+// Shared by the subcommands dealing with raw objects. Objects are blobs unless stated otherwise.
+fn object_type_arg<'a, 'b>() -> Arg<'a, 'b> {
+  Arg::with_name("type")
+    .long("type")
+    .takes_value(true)
+    .value_name("TYPE")
+    .possible_values(&["blob", "commit", "tag", "tree"])
+    .default_value("blob")
+}
+
+// Shared by the subcommands reporting their progress on stderr
+fn quiet_arg<'a, 'b>() -> Arg<'a, 'b> {
+  Arg::with_name("quiet")
+    .long("quiet")
+    .short("q")
+    .help("Doesn't report progress")
+}
+
+fn validate_positive_integer(value: String) -> Result<(), String> {
+  match value.parse::<usize>() {
+    Ok(n) if n > 0 => Ok(()),
+    _ => Err(format!("'{}' is not a positive integer", value))
+  }
+}
+
+fn validate_timestamp(value: String) -> Result<(), String> {
+  match value.parse::<u64>() {
+    Ok(_) => Ok(()),
+    Err(_) => Err(format!("'{}' is not a number of seconds since the Unix epoch", value))
+  }
+}
+
+// Without the ancestor search, a repository in a directory above does not stop one being created in the current directory.
+fn init(object_format: ObjectFormat, force: bool, search_ancestors: bool) -> std::io::Result<()> {
+  let result = if search_ancestors {
+    data::init(object_format)
+  }
+  else {
+    data::Repository::init(&env::current_dir()?, object_format).map(drop)
+  };
+
+  match result {
+    Err(err) if force && err.kind() == io::ErrorKind::AlreadyExists => {
+      data::reinit()?;
+      eprintln!("Reinitializing existing ugit repository...");
+    },
+    result => {
+      result?;
+      eprintln!("Creating new ugit repository...");
+    }
+  }
+
   Ok(())
 }
 
-fn hash_object(filename: &Path) -> std::io::Result<()> {
-  let contents = fs::read(filename)?;
-  let hash = data::hash_object(&contents, ObjectType::Blob)?;
+fn hash_object(filename: Option<&Path>, object_type: ObjectType) -> std::io::Result<()> {
+  let contents = match filename {
+    Some(filename) => fs::read(filename)?,
+    None => {
+      let mut contents = Vec::new();
+      io::stdin().read_to_end(&mut contents)?;
+      contents
+    }
+  };
+
+  let hash = data::hash_object(&contents, object_type)?;
   println!("{}", hash);
   Ok(())
 }
 
-fn cat_file(oid: &str) -> std::io::Result<()> {
-  let contents = data::get_object(oid, ObjectType::Blob)?;
-  print!("{}", contents);
+// Pretty printing lists a tree's entries one per line, rather than as the null-terminated entries it is stored as.
+fn cat_file(oid: &Oid, object_type: ObjectType, pretty: bool) -> std::io::Result<()> {
+  if pretty && object_type == ObjectType::Tree {
+    for (mode, object_type, oid, name) in base::parse_tree(oid)? {
+      println!("{} {} {}\t{}", mode, object_type, oid, name);
+    }
+
+    return Ok(());
+  }
+
+  // Streamed rather than read whole, so that a large blob is never held in memory
+  io::copy(&mut data::open_object(oid, object_type)?, &mut io::stdout().lock())?;
   Ok(())
 }
 
-fn write_tree() -> std::io::Result<()> {
-  let hash = base::write_tree()?;
+fn write_tree(path: Option<&str>, quiet: bool, print_tree: bool) -> std::io::Result<()> {
+  let hash = match path {
+    Some(path) if quiet => base::write_subtree(Path::new(path))?,
+    Some(path) => with_progress("Writing tree", |progress| base::write_subtree_with_progress(Path::new(path), progress))?,
+    None if quiet => base::write_tree()?,
+    None => with_progress("Writing tree", base::write_tree_with_progress)?
+  };
+
   println!("{}", hash);
+  if print_tree {
+    print_tree_entries(&hash, 1)?;
+  }
+
+  Ok(())
+}
+
+// Prints each entry of the tree as its name and OID, indented by depth, with a subtree's name ending in '/' and followed by its own
+// entries.
+fn print_tree_entries(oid: &Oid, depth: usize) -> std::io::Result<()> {
+  for (_, object_type, oid, name) in base::parse_tree(oid)? {
+    let is_tree = object_type == "tree";
+    println!("{fill}{}{} {}", name, if is_tree { "/" } else { "" }, oid, fill="  ".repeat(depth));
+    if is_tree {
+      print_tree_entries(&oid, depth + 1)?;
+    }
+  }
+
   Ok(())
 }
 
-fn read_tree(oid: &str) -> std::io::Result<()> {
-  base::read_tree(oid)?;
-  println!("Restored current working directory [{}]", oid);
+// Reports the number of files operation has processed every PROGRESS_INTERVAL files, and once it is done. Progress goes to stderr,
+// leaving stdout for the result.
+fn with_progress<T>(action: &str, operation: impl FnOnce(&mut dyn FnMut(usize)) -> std::io::Result<T>) -> std::io::Result<T> {
+  let mut processed = 0;
+  let result = operation(&mut |count| {
+    processed = count;
+    if count % PROGRESS_INTERVAL == 0 {
+      eprint!("\r{}: {} files", action, count);
+    }
+  })?;
+
+  eprintln!("\r{}: {} files, done", action, processed);
+  Ok(result)
+}
+
+fn read_tree(oid: &Oid, quiet: bool, clean: bool) -> std::io::Result<()> {
+  if quiet {
+    base::read_tree_with_progress(oid, clean, &mut |_| ())?;
+  }
+  else {
+    with_progress("Reading tree", |progress| base::read_tree_with_progress(oid, clean, progress))?;
+  }
+
+  eprintln!("Restored current working directory [{}]", oid);
   Ok(())
 }
 
-fn commit(message: &str) -> std::io::Result<()> {
-  let hash = base::commit(message)?;
+// Lines starting with '#' are dropped from the message written in an editor
+static COMMIT_MESSAGE_TEMPLATE: &str = "\n# Please enter the commit message for your changes. Lines starting\n\
+  # with '#' will be ignored, and an empty message aborts the commit.\n";
+
+// Reads a commit message from standard input when it is not a terminal, as when one is piped in. Otherwise, the message is written in
+// $VISUAL or $EDITOR, falling back to vi, opened on .ugit/COMMIT_EDITMSG.
+fn read_commit_message() -> std::io::Result<String> {
+  if !io::stdin().is_terminal() {
+    let mut message = String::new();
+    io::stdin().read_to_string(&mut message)?;
+    return Ok(utils::clean_message(&message, false));
+  }
+
+  let path = data::generate_path(PathVariant::CommitMessage)?;
+  fs::write(&path, COMMIT_MESSAGE_TEMPLATE)?;
+  let editor = match (env::var("VISUAL"), env::var("EDITOR")) {
+    (Ok(editor), _) if !editor.is_empty() => editor,
+    (_, Ok(editor)) if !editor.is_empty() => editor,
+    _ => String::from("vi")
+  };
+
+  // Run through the shell, so that an editor given along with its arguments, such as "code --wait", works too
+  let status = Command::new("sh")
+    .arg("-c")
+    .arg(format!("{} \"$@\"", editor))
+    .arg(&editor)
+    .arg(&path)
+    .status();
+
+  match status {
+    Ok(status) if status.success() => Ok(utils::clean_message(&fs::read_to_string(&path)?, true)),
+    Ok(_) => Err(io::Error::new(io::ErrorKind::Interrupted, format!("Editor [{}] exited unsuccessfully, aborting commit", editor))),
+    Err(err) => Err(io::Error::new(err.kind(), format!("Could not start editor [{}] -- {}", editor, err)))
+  }
+}
+
+fn commit(message: &str, all: bool, options: &CommitOptions) -> std::io::Result<()> {
+  let hash = if all {
+    base::commit_all(message, options)?
+  }
+  else {
+    base::commit_with_options(message, options)?
+  };
+
   println!("Successfully created commit: [{}]", hash);
   Ok(())
 }
 
-fn log(oid: &str) -> std::io::Result<()> {
-  for (oid, commit) in base::get_commits_to_root(oid)? {
-    println!("commit {}", &oid);
-    
-    for line in commit.message.lines() {
-      print!("\n{fill}{}", line, fill=" ".repeat(10));
+fn log(oid: &Oid, oneline: bool, depth: Option<usize>, graph: bool, date_order: bool, format: Option<&str>) -> std::io::Result<()> {
+  // A commit must come after all of its children for its lanes to be drawn
+  let commits: Box<dyn Iterator<Item=std::io::Result<Oid>>> = if graph {
+    Box::new(base::iter_commits_topologically(vec![oid.clone()])?.map(Ok))
+  }
+  else if date_order {
+    Box::new(base::iter_commits_by_date(vec![oid.clone()])?.map(Ok))
+  }
+  else {
+    // Walked lazily, so that no more history is read than --depth shows
+    Box::new(base::iter_commits_and_parents(vec![oid.clone()]))
+  };
+
+  let decorations = base::get_ref_decorations()?;
+  let mut lanes = Vec::new();
+  for oid in commits.take(depth.unwrap_or(usize::MAX)) {
+    let oid = oid?;
+    let commit = base::get_commit(&oid)?;
+    // The refs pointing to the commit follow its OID, e.g. "(HEAD -> main, tag: v1)"
+    let decoration = match decorations.get(&oid) {
+      Some(names) => format!(" ({})", names.join(", ")),
+      None => String::new()
+    };
+
+    let lines = if let Some(format) = format {
+      expand_log_format(format, &oid, &commit, &decoration).split('\n').map(String::from).collect()
+    }
+    else if oneline {
+      let abbreviated_oid = &oid[..10.min(oid.len())];
+      match commit.message.lines().next() {
+        Some(subject) if !subject.is_empty() => vec![format!("{}{} {}", abbreviated_oid, decoration, subject)],
+        _ => vec![format!("{}{}", abbreviated_oid, decoration)]
+      }
     }
+    else {
+      let mut lines = format_commit(&oid, &commit, false);
+      lines[0].push_str(&decoration);
+      lines
+    };
 
-    println!("\n");
+    let lines = if graph { format_graph_commit(&mut lanes, &oid, &commit.parents, &lines) } else { lines };
+    for line in lines {
+      println!("{}", line);
+    }
   }
 
   Ok(())
 }
 
-fn checkout(oid: &str) -> std::io::Result<()> {
-  base::checkout(oid)
+// Replaces the placeholders of a log --format template with the commit's details. An unknown placeholder is left as it is.
+fn expand_log_format(format: &str, oid: &str, commit: &Commit, decoration: &str) -> String {
+  // The author is recorded as "Name <email>"
+  let (name, email) = match commit.author {
+    Some(ref author) => match author.find(" <") {
+      Some(i) => (&author[..i], author[i + 2..].trim_end_matches('>')),
+      None => (author.as_str(), "")
+    },
+    None => ("", "")
+  };
+
+  let abbreviate = |oid: &str| String::from(&oid[..10.min(oid.len())]);
+  let mut expanded = String::new();
+  let mut chars = format.chars().peekable();
+  while let Some(c) = chars.next() {
+    if c != '%' {
+      expanded.push(c);
+      continue;
+    }
+
+    // Author placeholders are two characters long
+    let placeholder: String = match chars.peek() {
+      Some('a') => chars.by_ref().take(2).collect(),
+      _ => chars.next().into_iter().collect()
+    };
+
+    match placeholder.as_str() {
+      "H" => expanded.push_str(oid),
+      "h" => expanded.push_str(&abbreviate(oid)),
+      "s" => expanded.push_str(commit.message.lines().next().unwrap_or("")),
+      "P" => expanded.push_str(&commit.parents.iter().map(|parent| parent.to_string()).collect::<Vec<_>>().join(" ")),
+      "p" => expanded.push_str(&commit.parents.iter().map(|parent| abbreviate(parent)).collect::<Vec<_>>().join(" ")),
+      "an" => expanded.push_str(name),
+      "ae" => expanded.push_str(email),
+      "ad" => expanded.push_str(&commit.timestamp.map(utils::format_timestamp).unwrap_or_default()),
+      "d" => expanded.push_str(decoration),
+      "n" => expanded.push('\n'),
+      "%" => expanded.push('%'),
+      _ => {
+        expanded.push('%');
+        expanded.push_str(&placeholder);
+      }
+    };
+  }
+
+  expanded
 }
 
-fn tag(name: &str, oid: &str) -> std::io::Result<()> {
-  base::create_tag(name, &oid)
+// Each lane holds the OID of the commit expected next in that column. The commit's row marks its lane with '*', and is preceded by
+// rows drawing the lanes which join it and followed by those branching off of it, alongside the commit's lines.
+fn format_graph_commit(lanes: &mut Vec<Oid>, oid: &Oid, parents: &[Oid], lines: &[String]) -> Vec<String> {
+  let mut output = Vec::new();
+  let mut rows = Vec::new();
+  let column = match lanes.iter().position(|lane| lane == oid) {
+    Some(column) => column,
+    None => {
+      lanes.push(oid.clone());
+      lanes.len() - 1
+    }
+  };
+
+  // Other lanes leading to this commit join the one to their left, shifting the lanes after them along
+  while let Some(joining) = lanes.iter().rposition(|lane| lane == oid).filter(|&joining| joining != column) {
+    output.push(graph_row((0..lanes.len()).map(|i| if i < joining { (2 * i, '|') } else { (2 * i - 1, '/') })));
+    lanes.remove(joining);
+  }
+
+  rows.push(graph_row((0..lanes.len()).map(|i| (2 * i, if i == column { '*' } else { '|' }))));
+  let width = lanes.len();
+  match parents.first() {
+    Some(parent) => lanes[column] = parent.clone(),
+    None => {
+      lanes.remove(column);
+      if column < lanes.len() {
+        rows.push(graph_row((0..width).filter(|&i| i != column).map(|i| if i < column { (2 * i, '|') } else { (2 * i - 1, '/') })));
+      }
+    }
+  }
+
+  let mut branching = 0;
+  for parent in parents.iter().skip(1) {
+    if !lanes.contains(parent) {
+      branching += 1;
+      lanes.insert(column + branching, parent.clone());
+    }
+  }
+
+  if branching > 0 {
+    rows.push(graph_row((0..width).map(|i| if i <= column { (2 * i, '|') } else { (2 * i + 1, '\\') })
+      .chain(std::iter::once((2 * column + 1, '\\')))));
+  }
+
+  let continuation = graph_row((0..lanes.len()).map(|i| (2 * i, '|')));
+  let width = rows.iter().chain(std::iter::once(&continuation)).map(|row| row.len()).max().unwrap_or(0);
+  for i in 0..rows.len().max(lines.len()) {
+    let row = rows.get(i).unwrap_or(&continuation);
+    match lines.get(i) {
+      Some(line) => output.push(format!("{:width$} {}", row, line, width=width)),
+      None => output.push(row.clone())
+    };
+  }
+
+  output
+}
+
+// Places each character at its position, leaving the gaps between them blank
+fn graph_row(marks: impl Iterator<Item=(usize, char)>) -> String {
+  let mut row = Vec::new();
+  for (position, mark) in marks {
+    if row.len() <= position {
+      row.resize(position + 1, ' ');
+    }
+
+    row[position] = mark;
+  }
+
+  row.into_iter().collect()
+}
+
+fn show(name: &str, oid: &Oid) -> std::io::Result<()> {
+  // An annotated tag is printed before the commit it was resolved to
+  if let Some(Ok(tag_oid)) = data::locate_ref_or_oid(name, false) {
+    if let Ok(tag) = base::get_tag(&tag_oid) {
+      print_tag(&tag);
+    }
+  }
+
+  let commit = base::get_commit(oid)?;
+  print_commit(oid, &commit, true);
+  print_changes(base::diff_commit(&commit)?)
+}
+
+// Entries are numbered from the most recent, as in "HEAD@{0}"
+fn reflog(name: &str) -> std::io::Result<()> {
+  for (i, entry) in base::get_reflog(name)?.iter().rev().enumerate() {
+    println!("{} {}@{{{}}}: {}", &entry.new[..10.min(entry.new.len())], name, i, entry.message);
+  }
+
+  Ok(())
+}
+
+fn checkout(name: &str) -> std::io::Result<()> {
+  base::checkout(name)
+}
+
+fn reset(oid: &Oid) -> std::io::Result<()> {
+  base::reset(oid)?;
+  eprintln!("HEAD is now at [{}]", oid);
+  Ok(())
+}
+
+fn tag(name: &str, oid: &Oid, message: Option<&str>, force: bool) -> std::io::Result<()> {
+  match message {
+    Some(message) => base::create_annotated_tag(name, oid, message, force).map(|_| ()),
+    None => base::create_tag(name, oid, force)
+  }
+}
+
+fn list_tags(pattern: Option<&str>) -> std::io::Result<()> {
+  for name in base::list_tags(pattern)? {
+    println!("{}", name);
+  }
+
+  Ok(())
+}
+
+fn branch(name: &str, oid: &Oid) -> std::io::Result<()> {
+  base::create_branch(name, oid)
+}
+
+fn config(key: &str, value: Option<&str>) -> std::io::Result<()> {
+  if let Some(value) = value {
+    return data::set_config(key, value);
+  }
+
+  match data::get_config(key)? {
+    Some(value) => println!("{}", value),
+    None => return Err(io::Error::new(io::ErrorKind::NotFound, format!("Config has no value for [{}]", key)))
+  };
+
+  Ok(())
+}
+
+fn fsck() -> std::io::Result<()> {
+  let problems = base::fsck()?;
+  for problem in &problems {
+    match problem {
+      ObjectProblem::Corrupt(oid, reason) => println!("corrupt object {}: {}", oid, reason),
+      ObjectProblem::Missing(oid, missing) => println!("missing object {}, referenced by {}", missing, oid),
+    };
+  }
+
+  // Reported with a kind main maps to exit status 1, as for any other failure
+  match problems.len() {
+    0 => Ok(()),
+    1 => Err(io::Error::new(io::ErrorKind::Other, "Found 1 problem with stored objects")),
+    count => Err(io::Error::new(io::ErrorKind::Other, format!("Found {} problems with stored objects", count)))
+  }
+}
+
+fn status() -> std::io::Result<()> {
+  let status = base::status()?;
+  match (&status.branch, &status.head) {
+    (Some(branch), _) => println!("On branch {}", branch),
+    (None, Some(head)) => println!("detached HEAD at {}", head),
+    (None, None) => println!("No commits yet")
+  };
+
+  let categories = [
+    (FileStatus::New, "New files:"),
+    (FileStatus::Modified, "Modified files:"),
+    (FileStatus::Deleted, "Deleted files:"),
+  ];
+
+  for (file_status, heading) in categories.iter() {
+    let paths: Vec<_> = status.changes
+      .iter()
+      .filter(|(_, change)| change == file_status)
+      .collect();
+
+    if paths.is_empty() {
+      continue;
+    }
+
+    println!("\n{}", heading);
+    for (path, _) in paths {
+      println!("{fill}{}", path.display(), fill=" ".repeat(4));
+    }
+  }
+
+  Ok(())
+}
+
+fn merge(oid: &Oid, name: &str) -> std::io::Result<()> {
+  match base::merge(oid, name)? {
+    MergeResult::UpToDate => println!("Already up to date"),
+    MergeResult::FastForward => println!("Fast-forwarded to [{}]", oid),
+    MergeResult::Merged(hash) => println!("Successfully created merge commit: [{}]", hash),
+    MergeResult::Conflicts(paths) => {
+      println!("Merge conflicts in the following files. Resolve them, then commit the result:");
+      for path in paths {
+        println!("{fill}{}", path.display(), fill=" ".repeat(4));
+      }
+    }
+  };
+
+  Ok(())
+}
+
+fn cherry_pick(oid: &Oid, name: &str) -> std::io::Result<()> {
+  match base::cherry_pick(oid, name)? {
+    CherryPickResult::Picked(hash) => println!("Successfully created commit: [{}]", hash),
+    CherryPickResult::Conflicts(paths) => {
+      println!("Conflicts in the following files. Resolve them, then commit the result, which keeps the picked commit's message and author:");
+      for path in paths {
+        println!("{fill}{}", path.display(), fill=" ".repeat(4));
+      }
+    }
+  };
+
+  Ok(())
+}
+
+fn stash(pop: bool) -> std::io::Result<()> {
+  if !pop {
+    println!("Saved working directory changes as stash [{}]", base::stash()?);
+    return Ok(());
+  }
+
+  let conflicts = base::stash_pop()?;
+  if conflicts.is_empty() {
+    println!("Applied and dropped the stash");
+  }
+  else {
+    println!("Conflicts in the following files, so the stash has been kept. Resolve them:");
+    for path in conflicts {
+      println!("{fill}{}", path.display(), fill=" ".repeat(4));
+    }
+  }
+
+  Ok(())
+}
+
+fn merge_base(first: &Oid, second: &Oid) -> std::io::Result<()> {
+  println!("{}", base::merge_base(first, second)?);
+  Ok(())
+}
+
+fn rev_parse(name: &str, verify: bool) -> std::io::Result<()> {
+  if !verify {
+    println!("{}", base::rev_parse(name)?);
+    return Ok(());
+  }
+
+  // Any failure is reported with the same kind, so that it always exits with 1 whatever went wrong
+  match base::verify_revision(name) {
+    Ok(oid) => println!("{}", oid),
+    Err(err) => return Err(io::Error::new(io::ErrorKind::Other, format!("Needed a single revision -- {}", err)))
+  };
+
+  Ok(())
+}
+
+fn describe(oid: &Oid) -> std::io::Result<()> {
+  println!("{}", base::describe(oid)?);
+  Ok(())
+}
+
+fn blame(path: &Path) -> std::io::Result<()> {
+  let oid = base::blame(path)?;
+  let commit = base::get_commit(&oid)?;
+  println!("{} {}", &oid[..10.min(oid.len())], commit.message.lines().next().unwrap_or(""));
+  Ok(())
+}
+
+fn ls_tree(oid: &Oid, recursive: bool) -> std::io::Result<()> {
+  for (mode, object_type, oid, path) in base::ls_tree(oid, recursive)? {
+    println!("{} {} {}\t{}", mode, object_type, oid, path.display());
+  }
+
+  Ok(())
+}
+
+fn count_objects(verbose: bool) -> std::io::Result<()> {
+  let counts = base::count_objects()?;
+  if !verbose {
+    println!("{} objects, {} bytes", counts.count, counts.size);
+    return Ok(());
+  }
+
+  println!("count: {}", counts.count);
+  println!("size: {}", counts.size);
+  for (object_type, count) in &counts.types {
+    println!("{}: {}", object_type, count);
+  }
+
+  // Every object is stored loose, in a file of its own, as ugit does not pack objects
+  println!("in-pack: 0");
+  println!("packs: 0");
+  Ok(())
+}
+
+fn gc(dry_run: bool) -> std::io::Result<()> {
+  for oid in base::gc(dry_run)? {
+    if dry_run {
+      println!("Would remove {}", oid);
+    }
+    else {
+      println!("Removed {}", oid);
+    }
+  }
+
+  Ok(())
+}
+
+// A ref which doesn't resolve to a commit yet, such as HEAD pointing to a branch without commits, is listed with an OID of zeros.
+fn show_ref() -> std::io::Result<()> {
+  let zero_oid = "0".repeat(data::Repository::find()?.object_format.oid_length());
+  let mut refs: Vec<_> = data::iter_refs(false)?.into_iter().zip(data::iter_refs(true)?).collect();
+  refs.sort_by(|a, b| (a.0).0.cmp(&(b.0).0));
+  for ((name, raw), (_, resolved)) in refs {
+    let oid = resolved.oid().map_or(zero_oid.as_str(), |oid| oid);
+    match raw.symbolic_target() {
+      Some(target) => println!("{} {} -> {}", oid, name, target),
+      None => println!("{} {}", oid, name)
+    };
+  }
+
+  Ok(())
+}
+
+fn k(dot: bool) -> std::io::Result<()> {
+  let mut refs_by_oid: HashMap<Oid, Vec<String>> = HashMap::new();
+  let mut starting_oids = Vec::new();
+  for (name, ref_value) in data::iter_refs(true)? {
+    // A branch without commits yet has nothing to draw
+    let oid = match ref_value.value {
+      Some(RefTarget::Oid(oid)) => oid,
+      _ => continue
+    };
+
+    // Refs pointing at missing objects are reported rather than aborting the whole graph
+    let oid = match data::peel_tag(oid).and_then(|oid| base::get_commit(&oid).map(|_| oid)) {
+      Ok(oid) => oid,
+      Err(err) => {
+        eprintln!("Skipping ref {} -- {}", name, err);
+        continue;
+      }
+    };
+
+    refs_by_oid.entry(oid.clone()).or_default().push(name);
+    starting_oids.push(oid);
+  }
+
+  if dot {
+    println!("digraph commits {{");
+  }
+
+  // A commit must come after all of its children for its lanes to be drawn
+  let mut lanes = Vec::new();
+  for oid in base::iter_commits_topologically(starting_oids)? {
+    let commit = base::get_commit(&oid)?;
+    let abbreviated_oid = &oid[..10.min(oid.len())];
+    let refs = refs_by_oid.get(&oid).cloned().unwrap_or_default();
+    if dot {
+      println!("  \"{}\" [shape=box style=filled label=\"{}\"];", oid, abbreviated_oid);
+      for parent in &commit.parents {
+        println!("  \"{}\" -> \"{}\";", oid, parent);
+      }
+
+      for name in refs {
+        println!("  \"{}\" [shape=note];", name);
+        println!("  \"{}\" -> \"{}\";", name, oid);
+      }
+
+      continue;
+    }
+
+    let refs = if refs.is_empty() {
+      String::new()
+    }
+    else {
+      format!(" ({})", refs.join(", "))
+    };
+
+    let line = format!("{}{} {}", abbreviated_oid, refs, commit.message.lines().next().unwrap_or(""));
+    for row in format_graph_commit(&mut lanes, &oid, &commit.parents, &[line]) {
+      println!("{}", row);
+    }
+  }
+
+  if dot {
+    println!("}}");
+  }
+
+  Ok(())
 }
 
-fn branch(name: &str, oid: &str) -> std::io::Result<()> {
-  base::create_branch(name, &oid)
+fn diff(from: &Oid, to: &Oid) -> std::io::Result<()> {
+  let from = base::resolve_tree_oid(from)?;
+  let to = base::resolve_tree_oid(to)?;
+  print_changes(base::diff_trees(&from, &to)?)
+}
+
+fn print_commit(oid: &str, commit: &Commit, show_parents: bool) {
+  for line in format_commit(oid, commit, show_parents) {
+    println!("{}", line);
+  }
+}
+
+// Every parent is listed when show_parents is set. Otherwise, only merge commits list theirs.
+fn format_commit(oid: &str, commit: &Commit, show_parents: bool) -> Vec<String> {
+  let mut lines = vec![format!("commit {}", oid)];
+  if show_parents {
+    for parent in &commit.parents {
+      lines.push(format!("Parent: {}", parent));
+    }
+  }
+  else if commit.parents.len() > 1 {
+    lines.push(format!("Merge:  {}", commit.parents.iter().map(|parent| parent.to_string()).collect::<Vec<_>>().join(" ")));
+  }
+
+  if let Some(ref author) = commit.author {
+    lines.push(format!("Author: {}", author));
+  }
+
+  if let Some(timestamp) = commit.timestamp {
+    lines.push(format!("Date:   {}", utils::format_timestamp(timestamp)));
+  }
+
+  lines.push(String::new());
+  for line in commit.message.lines() {
+    lines.push(format!("{fill}{}", line, fill=" ".repeat(10)));
+  }
+
+  lines.push(String::new());
+  lines
+}
+
+fn print_tag(tag: &Tag) {
+  println!("tag {}", tag.name);
+  if let Some(ref tagger) = tag.tagger {
+    println!("Tagger: {}", tagger);
+  }
+
+  if let Some(timestamp) = tag.timestamp {
+    println!("Date:   {}", utils::format_timestamp(timestamp));
+  }
+
+  for line in tag.message.lines() {
+    print!("\n{fill}{}", line, fill=" ".repeat(10));
+  }
+
+  println!("\n");
+}
+
+fn print_changes(changes: Vec<(PathBuf, ChangeKind)>) -> std::io::Result<()> {
+  for (path, change) in changes {
+    let (from_contents, to_contents) = match change {
+      ChangeKind::Added(to) => (None, Some(data::get_object(&to, ObjectType::Blob)?)),
+      ChangeKind::Removed(from) => (Some(data::get_object(&from, ObjectType::Blob)?), None),
+      ChangeKind::Modified(from, to) => (Some(data::get_object(&from, ObjectType::Blob)?), Some(data::get_object(&to, ObjectType::Blob)?)),
+    };
+
+    print!("{}", diff::diff_blobs(&path, from_contents.as_deref(), to_contents.as_deref()));
+  }
+
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn oid(digit: char) -> Oid {
+    digit.to_string().repeat(ObjectFormat::Sha256.oid_length()).parse().unwrap()
+  }
+
+  // Draws the commits in the order given, each with its parents and a one-line description
+  fn draw_graph(commits: &[(Oid, Vec<Oid>, &str)]) -> Vec<String> {
+    let mut lanes = Vec::new();
+    commits
+      .iter()
+      .flat_map(|(oid, parents, line)| format_graph_commit(&mut lanes, oid, parents, &[String::from(*line)]))
+      .collect()
+  }
+
+  fn test_commit() -> Commit {
+    Commit {
+      author: Some(String::from("Jane Doe <jane@example.com>")),
+      message: String::from("Subject\n\nBody"),
+      parents: vec![oid('1'), oid('2')],
+      timestamp: Some(1618565400),
+      tree: oid('3'),
+    }
+  }
+
+  #[test]
+  fn expand_log_format_expands_every_placeholder() {
+    let commit = test_commit();
+    let head = oid('a');
+    let expand = |format| expand_log_format(format, &head, &commit, " (HEAD -> main)");
+    assert_eq!(expand("%H"), head.to_string());
+    assert_eq!(expand("%h"), "aaaaaaaaaa");
+    assert_eq!(expand("%s"), "Subject");
+    assert_eq!(expand("%P"), format!("{} {}", oid('1'), oid('2')));
+    assert_eq!(expand("%p"), "1111111111 2222222222");
+    assert_eq!(expand("%an"), "Jane Doe");
+    assert_eq!(expand("%ae"), "jane@example.com");
+    assert_eq!(expand("%ad"), "2021-04-16 09:30:00 +0000");
+    assert_eq!(expand("%d"), " (HEAD -> main)");
+    assert_eq!(expand("%n"), "\n");
+    assert_eq!(expand("%%"), "%");
+    assert_eq!(expand("%h%d: %s by %an%n%%H"), "aaaaaaaaaa (HEAD -> main): Subject by Jane Doe\n%H");
+  }
+
+  #[test]
+  fn expand_log_format_leaves_unknown_and_incomplete_placeholders_as_they_are() {
+    let commit = test_commit();
+    let head = oid('a');
+    let expand = |format| expand_log_format(format, &head, &commit, "");
+    assert_eq!(expand("%x %ax"), "%x %ax");
+    assert_eq!(expand("100%"), "100%");
+    assert_eq!(expand("%h %a"), "aaaaaaaaaa %a");
+  }
+
+  #[test]
+  fn expand_log_format_leaves_details_a_commit_does_not_record_empty() {
+    let commit = Commit { author: None, message: String::new(), parents: Vec::new(), timestamp: None, tree: oid('3') };
+    assert_eq!(expand_log_format("[%s|%P|%an|%ae|%ad]", &oid('a'), &commit, ""), "[||||]");
+    let commit = Commit { author: Some(String::from("Jane Doe")), ..test_commit() };
+    assert_eq!(expand_log_format("%an|%ae", &oid('a'), &commit, ""), "Jane Doe|");
+  }
+
+  #[test]
+  fn format_graph_commit_keeps_a_linear_history_in_one_lane() {
+    let mut lanes = Vec::new();
+    let lines = vec![String::from("Third"), String::from("Body")];
+    assert_eq!(format_graph_commit(&mut lanes, &oid('3'), &[oid('2')], &lines), vec!["* Third", "| Body"]);
+    assert_eq!(lanes, vec![oid('2')]);
+
+    let rows = draw_graph(&[(oid('2'), vec![oid('1')], "Second"), (oid('1'), vec![], "First")]);
+    assert_eq!(rows, vec!["* Second", "* First"]);
+  }
+
+  #[test]
+  fn format_graph_commit_joins_the_lanes_of_a_fork() {
+    let rows = draw_graph(&[
+      (oid('a'), vec![oid('1')], "Left"),
+      (oid('b'), vec![oid('1')], "Right"),
+      (oid('1'), vec![], "Base"),
+    ]);
+
+    assert_eq!(rows, vec!["* Left", "| * Right", "|/", "* Base"]);
+  }
+
+  #[test]
+  fn format_graph_commit_branches_off_a_lane_for_each_parent_of_a_merge() {
+    let rows = draw_graph(&[
+      (oid('f'), vec![oid('a'), oid('b')], "Merge"),
+      (oid('a'), vec![oid('1')], "Left"),
+      (oid('b'), vec![oid('1')], "Right"),
+      (oid('1'), vec![], "Base"),
+    ]);
+
+    assert_eq!(rows, vec!["*   Merge", "|\\", "* | Left", "| * Right", "|/", "* Base"]);
+  }
 }
@@ -1,304 +1,1329 @@
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
 use std::env;
+use std::fmt;
 use std::fs;
-use std::io::{Error, ErrorKind};
+use std::io::{BufRead, BufReader, Error, ErrorKind, Read, Write};
+use std::ops::Deref;
 use std::path::{Path, PathBuf};
-
+use std::rc::Rc;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use flate2::Compression;
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use sha1::Sha1;
 use sha2::{Digest, Sha256};
 
+use crate::store::{FsObjectStore, ObjectStore};
+#[cfg(test)]
+use crate::store::MemoryObjectStore;
 use crate::utils;
 
-static GIT_DIR: &str = ".ugit";
+// Name of the repository directory unless UGIT_DIR gives another
+static DEFAULT_GIT_DIR: &str = ".ugit";
+// Every zlib stream written by hash_object begins with this CMF byte (deflate, 32K window). Objects stored before compression
+// was introduced begin with their plain-text type header instead, so the first byte is enough to tell the two formats apart.
+static ZLIB_MAGIC: u8 = 0x78;
+// Branch a new repository's HEAD names, unless UGIT_DEFAULT_BRANCH or init.defaultBranch gives another
+static DEFAULT_BRANCH: &str = "main";
+// Longest type header open_object reads before deciding an object has none
+static MAX_TYPE_HEADER_LENGTH: usize = 16;
+// Shorter prefixes are too likely to match an unintended object
+static MIN_OID_PREFIX_LENGTH: usize = 4;
+// Longest chain of symbolic refs followed before giving up on it as a cycle
+static MAX_SYMBOLIC_REF_DEPTH: usize = 16;
+
+thread_local! {
+  // The current directory get_repository last searched from, along with the .ugit directory it found
+  static REPOSITORY: RefCell<Option<(PathBuf, PathBuf)>> = const { RefCell::new(None) };
+  // The repository last opened or created, which find hands out again while it is the one found, so that its config is read once
+  // rather than on every call
+  static OPENED: RefCell<Option<Repository>> = const { RefCell::new(None) };
+}
+
+// The hash function objects are named by, chosen when a repository is created. SHA-1 is offered for experiments alongside git.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum ObjectFormat {
+  Sha1,
+  Sha256,
+}
+
+impl ObjectFormat {
+  // Length of a full OID: the hex-encoded hash
+  pub fn oid_length(self) -> usize {
+    match self {
+      ObjectFormat::Sha1 => 40,
+      ObjectFormat::Sha256 => 64,
+    }
+  }
+
+  fn hash(self, contents: &[u8]) -> String {
+    match self {
+      ObjectFormat::Sha1 => format!("{:x}", Sha1::digest(contents)),
+      ObjectFormat::Sha256 => format!("{:x}", Sha256::digest(contents)),
+    }
+  }
+
+  fn hasher(self) -> ObjectHasher {
+    match self {
+      ObjectFormat::Sha1 => ObjectHasher::Sha1(Sha1::new()),
+      ObjectFormat::Sha256 => ObjectHasher::Sha256(Sha256::new()),
+    }
+  }
+}
+
+// Hashes an object a piece at a time, for contents which are streamed rather than read whole
+enum ObjectHasher {
+  Sha1(Sha1),
+  Sha256(Sha256),
+}
+
+impl ObjectHasher {
+  fn update(&mut self, contents: &[u8]) {
+    match self {
+      ObjectHasher::Sha1(hasher) => hasher.update(contents),
+      ObjectHasher::Sha256(hasher) => hasher.update(contents),
+    }
+  }
+
+  fn finish(self) -> String {
+    match self {
+      ObjectHasher::Sha1(hasher) => format!("{:x}", hasher.finalize()),
+      ObjectHasher::Sha256(hasher) => format!("{:x}", hasher.finalize()),
+    }
+  }
+}
+
+// Passes an object's contents through while hashing them, failing the read which reaches the end if they do not hash back to oid
+struct VerifyingReader<R> {
+  inner: R,
+  hasher: Option<ObjectHasher>,
+  oid: String,
+}
+
+impl<R: Read> Read for VerifyingReader<R> {
+  fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+    let count = self.inner.read(buf)?;
+    if count > 0 {
+      if let Some(hasher) = self.hasher.as_mut() {
+        hasher.update(&buf[..count]);
+      }
+    }
+    else if let Some(hasher) = self.hasher.take() {
+      if hasher.finish() != self.oid {
+        return Err(Error::new(ErrorKind::InvalidData, format!("Object contents do not match its OID [{}]", self.oid)));
+      }
+    }
+
+    Ok(count)
+  }
+}
+
+// The OID of an object: its hash in lowercase hex, of either object format's length. Parsing one checks both, so that a malformed
+// OID read from an object is reported where it is found, rather than once a path is built from it, and lowercases any uppercase
+// hex. It dereferences to the hex.
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Oid(String);
+
+impl FromStr for Oid {
+  type Err = Error;
+
+  fn from_str(s: &str) -> std::io::Result<Self> {
+    let valid_length = s.len() == ObjectFormat::Sha1.oid_length() || s.len() == ObjectFormat::Sha256.oid_length();
+    if !valid_length || !utils::is_hex(s) {
+      return Err(Error::new(ErrorKind::InvalidData, format!("Not a valid OID [{}]", s)));
+    }
+
+    Ok(Oid(s.to_ascii_lowercase()))
+  }
+}
+
+impl fmt::Display for Oid {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{}", self.0)
+  }
+}
+
+impl Deref for Oid {
+  type Target = str;
+
+  fn deref(&self) -> &str {
+    &self.0
+  }
+}
+
+impl From<Oid> for String {
+  fn from(oid: Oid) -> Self {
+    oid.0
+  }
+}
+
+impl PartialEq<String> for Oid {
+  fn eq(&self, other: &String) -> bool {
+    &self.0 == other
+  }
+}
+
+impl PartialEq<Oid> for String {
+  fn eq(&self, other: &Oid) -> bool {
+    self == &other.0
+  }
+}
+
+impl PartialEq<str> for Oid {
+  fn eq(&self, other: &str) -> bool {
+    self.0 == other
+  }
+}
+
+impl PartialEq<&str> for Oid {
+  fn eq(&self, other: &&str) -> bool {
+    self.0 == *other
+  }
+}
+
+// What hash_object_with_status did with an object
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum ObjectWrite {
+  Written,
+  AlreadyStored,
+}
 
-#[derive(Eq, PartialEq, Hash, Copy, Clone)]
+#[derive(Debug, Eq, PartialEq, Hash, Copy, Clone)]
 pub enum ObjectType {
   Blob,
   Commit,
+  Tag,
   Tree,
 }
 
 pub struct Commit {
+  // "Name <email>" of whoever made the commit, absent from commits made before this was recorded
+  pub author: Option<String>,
   pub message: String,
-  pub parent: Option<String>,
-  pub tree: String,
+  // Empty for a root commit, and holding more than one OID for a merge commit
+  pub parents: Vec<Oid>,
+  // Seconds since the Unix epoch at which the commit was made, absent from commits made before this was recorded
+  pub timestamp: Option<u64>,
+  pub tree: Oid,
 }
 
-pub fn init() -> std::io::Result<()> {
-  if repository_initialized() {
-    return Err(Error::new(ErrorKind::AlreadyExists, "A ugit repository already exists"));
+// An annotated tag, which unlike a lightweight tag is an object of its own that the tag's ref points to
+pub struct Tag {
+  pub message: String,
+  pub name: String,
+  // OID of the tagged object
+  pub object: Oid,
+  // "Name <email>" of whoever created the tag
+  pub tagger: Option<String>,
+  // Seconds since the Unix epoch at which the tag was created
+  pub timestamp: Option<u64>,
+}
+
+// The objects of a repository: framed with their type header, named by their hash, and compressed on their way into store, then
+// checked on their way back out. Repository keeps its objects in .ugit/objects, while one kept in any other store can be used
+// on its own.
+pub struct ObjectDatabase<S: ObjectStore> {
+  store: S,
+  object_format: ObjectFormat,
+}
+
+impl<S: ObjectStore> ObjectDatabase<S> {
+  pub fn new(store: S, object_format: ObjectFormat) -> Self {
+    Self { store, object_format }
+  }
+
+  // Computes the OID hash_object would give the contents, without storing anything.
+  pub fn get_oid(&self, file_contents: &[u8], object_type: ObjectType) -> Oid {
+    Oid(self.object_format.hash(&frame_object(file_contents, object_type)))
+  }
+
+  // Also tells whether the object was written, or was already stored. As an object is named by the hash of its contents, one
+  // already stored under the same OID holds the same contents, and is left as it is.
+  pub fn hash_object_with_status(&self, file_contents: &[u8], object_type: ObjectType) -> std::io::Result<(Oid, ObjectWrite)> {
+    let contents = frame_object(file_contents, object_type);
+    let oid = Oid(self.object_format.hash(&contents));
+    if self.store.exists(&oid) {
+      return Ok((oid, ObjectWrite::AlreadyStored));
+    }
+
+    self.store.write(&oid, &compress_object(&contents)?)?;
+    Ok((oid, ObjectWrite::Written))
+  }
+
+  // With verify set, the contents read are checked to hash back to oid, so that a corrupted object is caught rather than used.
+  pub fn get_object(&self, oid: &Oid, expected_type: ObjectType, verify: bool) -> std::io::Result<Vec<u8>> {
+    let contents = self.read_object(oid)?;
+    if verify {
+      self.check_object_hash(oid, &contents)?;
+    }
+
+    split_object(oid, &contents, expected_type)
+  }
+
+  // Like get_object, but returns a reader over the contents rather than the contents themselves, so that a large blob can be
+  // copied elsewhere without holding all of it in memory. The object is decompressed as it is read. Since the contents cannot
+  // be hashed until all of them have been read, a mismatch with oid is reported by the read which reaches the end, after the rest
+  // have already been handed out.
+  pub fn open_object(&self, oid: &Oid, expected_type: ObjectType, verify: bool) -> std::io::Result<impl Read> {
+    let mut stored = BufReader::new(self.store.open(oid)?);
+    let mut reader: Box<dyn Read> = if stored.fill_buf()?.first() == Some(&ZLIB_MAGIC) {
+      Box::new(ZlibDecoder::new(stored))
+    }
+    else {
+      Box::new(stored)
+    };
+
+    let header = read_type_header(&mut reader, oid)?;
+    if parse_type_header(&header)? != expected_type {
+      let message = format!("Object was expected to be a {}, but was a [{}]", type_header(expected_type), String::from_utf8_lossy(&header));
+      return Err(Error::new(ErrorKind::InvalidData, message));
+    }
+
+    let hasher = if verify {
+      let mut hasher = self.object_format.hasher();
+      hasher.update(&header);
+      hasher.update(b"\0");
+      Some(hasher)
+    }
+    else {
+      None
+    };
+
+    Ok(VerifyingReader { inner: reader, hasher, oid: oid.to_string() })
+  }
+
+  // Returns an object's type along with the length of its contents, not counting the type header.
+  pub fn object_info(&self, oid: &Oid) -> std::io::Result<(ObjectType, usize)> {
+    let contents = self.read_object(oid)?;
+    let header_end = match contents.iter().position(|b| *b == b'\0') {
+      Some(end) => end,
+      None => return Err(Error::new(ErrorKind::InvalidData, format!("Object is missing its type header [{}]", oid)))
+    };
+
+    let object_type = parse_type_header(&contents[..header_end])?;
+    Ok((object_type, contents.len() - header_end - 1))
+  }
+
+  // Checks that the object stored under oid can be read, has a known type, and hashes back to oid, returning its type.
+  pub fn verify_object(&self, oid: &Oid) -> std::io::Result<ObjectType> {
+    let contents = self.read_object(oid)?;
+    self.check_object_hash(oid, &contents)?;
+    match contents.iter().position(|b| *b == b'\0') {
+      Some(end) => parse_type_header(&contents[..end]),
+      None => Err(Error::new(ErrorKind::InvalidData, format!("Object is missing its type header [{}]", oid)))
+    }
+  }
+
+  pub fn contains(&self, oid: &Oid) -> bool {
+    self.store.exists(oid)
+  }
+
+  pub fn delete_object(&self, oid: &Oid) -> std::io::Result<()> {
+    self.store.delete(oid)
+  }
+
+  // How many bytes the object takes up in the store, compressed, as opposed to the length of its contents.
+  pub fn stored_size(&self, oid: &Oid) -> std::io::Result<u64> {
+    self.store.size(oid)
+  }
+
+  // Lists the OID of every stored object, sorted. Anything stored under a name which is not an OID is an InvalidData error.
+  pub fn list_objects(&self) -> std::io::Result<Vec<Oid>> {
+    let mut oids = self.store.iter_oids()?.iter().map(|oid| oid.parse()).collect::<std::io::Result<Vec<Oid>>>()?;
+    oids.sort();
+    Ok(oids)
+  }
+
+  // Finds the one object whose OID begins with the given prefix.
+  pub fn resolve_oid_prefix(&self, prefix: &str) -> std::io::Result<Oid> {
+    if prefix.len() < MIN_OID_PREFIX_LENGTH || !utils::is_hex(prefix) {
+      return Err(Error::new(ErrorKind::InvalidInput, format!("An OID prefix must be at least {} hex characters long [{}]", MIN_OID_PREFIX_LENGTH, prefix)));
+    }
+
+    let prefix = prefix.to_ascii_lowercase();
+    let mut matches = self.store.oids_with_prefix(&prefix)?;
+    match matches.len() {
+      0 => Err(Error::new(ErrorKind::NotFound, format!("No object matches the OID prefix [{}]", prefix))),
+      1 => matches.remove(0).parse(),
+      _ => Err(Error::new(ErrorKind::InvalidInput, format!("OID prefix [{}] is ambiguous: it matches {} objects", prefix, matches.len())))
+    }
+  }
+
+  // Returns the uncompressed contents of the object with the given OID, including its type header.
+  pub fn read_object(&self, oid: &Oid) -> std::io::Result<Vec<u8>> {
+    decompress_object(oid, self.store.read(oid)?)
+  }
+
+  fn check_object_hash(&self, oid: &str, contents: &[u8]) -> std::io::Result<()> {
+    if self.object_format.hash(contents) != oid {
+      return Err(Error::new(ErrorKind::InvalidData, format!("Object contents do not match its OID [{}]", oid)));
+    }
+
+    Ok(())
+  }
+}
+
+// A ugit repository at an explicit location. Its methods work relative to root rather than to the current directory, so that ugit
+// can be used as a library, or against several repositories at once. The free functions of this module act on the repository
+// found from the current directory.
+#[derive(Clone, Debug)]
+pub struct Repository {
+  // The root of the ugit project, which holds the repository directory
+  pub root: PathBuf,
+  // Read from the core.objectFormat config value once, when the repository is opened
+  pub object_format: ObjectFormat,
+  // The repository directory within root, named by UGIT_DIR as it was when the repository was found
+  ugit: PathBuf,
+  // .ugit/objects, unless the repository was wired to another store
+  object_store: Rc<dyn ObjectStore>,
+  // The values of .ugit/config, read once when the repository is opened and kept in step by set_config
+  config: Rc<RefCell<HashMap<String, String>>>,
+}
+
+impl Repository {
+  fn new(root: &Path, object_format: ObjectFormat) -> Self {
+    Self::with_git_dir(root, &git_dir(), object_format)
+  }
+
+  fn with_git_dir(root: &Path, git_dir: &str, object_format: ObjectFormat) -> Self {
+    let ugit = root.join(git_dir);
+    let object_store = Rc::new(FsObjectStore::new(ugit.join("objects")));
+    Self { root: root.to_path_buf(), object_format, ugit, object_store, config: Rc::default() }
+  }
+
+  // Creates an empty repository directory within root, creating root as well if need be.
+  pub fn init(root: &Path, object_format: ObjectFormat) -> std::io::Result<Self> {
+    let repository = Self::new(root, object_format);
+    let branch = repository.default_branch()?;
+    repository.create(&branch)?;
+    // Replaces any repository remembered from a directory which has since been deleted and created again
+    OPENED.with(|opened| *opened.borrow_mut() = Some(repository.clone()));
+    Ok(repository)
+  }
+
+  // Like init, but keeps the repository's objects in memory rather than in .ugit/objects, so that a test can work on a repository
+  // of its own without the current directory being changed. Its refs, config and index are still files under root. The repository
+  // directory and the branch HEAD starts out on are given rather than read from UGIT_DIR and UGIT_DEFAULT_BRANCH, which tests
+  // running alongside may be changing, and the repository is not remembered for find.
+  #[cfg(test)]
+  pub fn init_in_memory(root: &Path, git_dir: &str, branch: &str, object_format: ObjectFormat) -> std::io::Result<Self> {
+    let mut repository = Self::with_git_dir(root, git_dir, object_format);
+    repository.object_store = Rc::new(MemoryObjectStore::default());
+    repository.create(branch)?;
+    Ok(repository)
+  }
+
+  fn create(&self, branch: &str) -> std::io::Result<()> {
+    if self.generate_path(PathVariant::Ugit).exists() {
+      return Err(Error::new(ErrorKind::AlreadyExists, "A ugit repository already exists"));
+    }
+
+    // Checked before anything is created, so that an invalid name leaves no repository behind
+    validate_ref_name(branch)?;
+    self.create_missing_directories()?;
+    self.set_config("core.objectFormat", object_format_name(self.object_format))?;
+    self.create_head(branch)
+  }
+
+  // Creates whichever of .ugit, .ugit/objects, .ugit/refs and the directories within .ugit/refs are missing. Those which already
+  // exist, along with everything in them, are left as they are.
+  pub fn create_missing_directories(&self) -> std::io::Result<()> {
+    let paths = [
+      self.generate_path(PathVariant::Ugit),
+      self.generate_path(PathVariant::Objects),
+      self.generate_path(PathVariant::Refs),
+      self.generate_path(PathVariant::Heads),
+      self.generate_path(PathVariant::Tags),
+    ];
+
+    for path in paths.iter() {
+      fs::create_dir_all(path)?;
+    }
+
+    Ok(())
+  }
+
+  // Recreates whichever of the repository's directories are missing, along with HEAD if it is missing too.
+  pub fn reinit(&self) -> std::io::Result<()> {
+    self.create_missing_directories()?;
+    if !self.generate_path(PathVariant::Head).exists() {
+      let branch = self.default_branch()?;
+      validate_ref_name(&branch)?;
+      self.create_head(&branch)?;
+    }
+
+    Ok(())
+  }
+
+  // The branch HEAD starts out on, named by the UGIT_DEFAULT_BRANCH environment variable, which unlike the repository's config
+  // exists before the repository does. When HEAD is recreated within an existing repository, the init.defaultBranch config value
+  // is used otherwise.
+  fn default_branch(&self) -> std::io::Result<String> {
+    let branch = match env::var("UGIT_DEFAULT_BRANCH") {
+      Ok(branch) if !branch.is_empty() => branch,
+      _ => self.get_config("init.defaultBranch")?.unwrap_or_else(|| String::from(DEFAULT_BRANCH))
+    };
+
+    Ok(branch)
+  }
+
+  // Points HEAD at branch, which is left to be created by the first commit.
+  fn create_head(&self, branch: &str) -> std::io::Result<()> {
+    self.update_ref_file(&self.generate_path(PathVariant::Head), &format!("ref:refs/heads/{}", branch))
+  }
+
+  // Finds the repository containing the current directory. Its ancestors are searched too, unless UGIT_NO_ANCESTOR_SEARCH is set.
+  // The repository last opened is reused when it is the one found, config and all.
+  pub fn find() -> std::io::Result<Self> {
+    let path = match get_repository() {
+      Some(path) => path,
+      None => return Err(Error::new(ErrorKind::NotFound, "A ugit repository does not exist"))
+    };
+
+    let opened = OPENED.with(|opened| opened.borrow().clone());
+    match opened {
+      Some(repository) if repository.ugit == path => Ok(repository),
+      _ => Self::open(path.parent().unwrap())
+    }
+  }
+
+  // Opens the repository directory directly within root, without looking for one in root's ancestors. Its config is read afresh,
+  // and the repository is remembered for find.
+  pub fn open(root: &Path) -> std::io::Result<Self> {
+    if !root.join(git_dir()).is_dir() {
+      return Err(Error::new(ErrorKind::NotFound, format!("A ugit repository does not exist in [{}]", root.display())));
+    }
+
+    // Repositories created before the object format was recorded all use SHA-256
+    let mut repository = Self::new(root, ObjectFormat::Sha256);
+    repository.config = Rc::new(RefCell::new(repository.read_config()?));
+    if let Some(name) = repository.get_config("core.objectFormat")? {
+      repository.object_format = parse_object_format(&name)?;
+    }
+
+    OPENED.with(|opened| *opened.borrow_mut() = Some(repository.clone()));
+    Ok(repository)
+  }
+
+  // The repository's objects, kept in .ugit/objects unless the repository was wired to another store.
+  pub fn objects(&self) -> ObjectDatabase<Rc<dyn ObjectStore>> {
+    ObjectDatabase::new(Rc::clone(&self.object_store), self.object_format)
+  }
+
+  // Computes the OID hash_object would give the contents, without storing anything.
+  pub fn get_oid(&self, file_contents: &[u8], object_type: ObjectType) -> Oid {
+    self.objects().get_oid(file_contents, object_type)
+  }
+
+  pub fn hash_object(&self, file_contents: &[u8], object_type: ObjectType) -> std::io::Result<Oid> {
+    Ok(self.hash_object_with_status(file_contents, object_type)?.0)
+  }
+
+  pub fn hash_object_with_status(&self, file_contents: &[u8], object_type: ObjectType) -> std::io::Result<(Oid, ObjectWrite)> {
+    self.objects().hash_object_with_status(file_contents, object_type)
+  }
+
+  // Unless the core.verifyObjects config value is "false", the contents read are checked to hash back to oid, so that an object
+  // corrupted on disk is caught rather than used.
+  pub fn get_object(&self, oid: &Oid, expected_type: ObjectType) -> std::io::Result<Vec<u8>> {
+    if !self.verify_objects()? {
+      return self.get_object_unchecked(oid, expected_type);
+    }
+
+    self.objects().get_object(oid, expected_type, true)
+  }
+
+  // Like get_object, but streams the contents as ObjectDatabase::open_object does.
+  pub fn open_object(&self, oid: &Oid, expected_type: ObjectType) -> std::io::Result<impl Read> {
+    self.objects().open_object(oid, expected_type, self.verify_objects()?)
+  }
+
+  // Skips hashing the contents, for reads of objects which are already trusted.
+  pub fn get_object_unchecked(&self, oid: &Oid, expected_type: ObjectType) -> std::io::Result<Vec<u8>> {
+    self.objects().get_object(oid, expected_type, false)
+  }
+
+  // Reads only as far as the type header, so that an object can be inspected without knowing its type beforehand.
+  pub fn get_object_type(&self, oid: &Oid) -> std::io::Result<ObjectType> {
+    Ok(self.object_info(oid)?.0)
+  }
+
+  pub fn object_info(&self, oid: &Oid) -> std::io::Result<(ObjectType, usize)> {
+    self.objects().object_info(oid)
+  }
+
+  pub fn verify_object(&self, oid: &Oid) -> std::io::Result<ObjectType> {
+    self.objects().verify_object(oid)
+  }
+
+  pub fn list_objects(&self) -> std::io::Result<Vec<Oid>> {
+    self.objects().list_objects()
+  }
+
+  fn verify_objects(&self) -> std::io::Result<bool> {
+    Ok(self.get_config("core.verifyObjects")?.as_deref() != Some("false"))
+  }
+
+  pub fn update_ref(&self, ref_value: &RefValue, deref: bool) -> std::io::Result<()> {
+    self.update_ref_with_message(ref_value, deref, "update")
+  }
+
+  // Like update_ref, describing the update with message in the reflog of the ref written. When a symbolic ref such as HEAD was
+  // followed to reach it, the update is logged for the symbolic ref as well.
+  pub fn update_ref_with_message(&self, ref_value: &RefValue, deref: bool, message: &str) -> std::io::Result<()> {
+    // Using get_ref here to drill down to the commit, in the case that ref_value.path contains a symbolic ref.
+    let path = match self.get_ref(&ref_value.path, deref) {
+      Ok(ref_value) => ref_value.path,
+      Err(err) => return Err(
+        Error::new(err.kind(), format!("While trying to update ref ['{}'|{:?}], an error occured: {}", ref_value.path.display(), ref_value.value, err)))
+    };
+
+    let value = match ref_value.value {
+      Some(RefTarget::Symbolic(ref name)) => format!("ref:{}", name),
+      Some(RefTarget::Oid(ref oid)) => oid.to_string(),
+      None => return Err(Error::new(ErrorKind::InvalidInput, format!("Tried to update ref with an empty ref: {:?}", ref_value)))
+    };
+
+    // The reflog records the OIDs the ref resolves to, so that a symbolic HEAD logs the commits it moves between
+    let zero_oid = Oid("0".repeat(self.object_format.oid_length()));
+    let resolve = |path: &Path| self.get_ref(path, true).ok().and_then(|ref_value| ref_value.oid().cloned()).unwrap_or_else(|| zero_oid.clone());
+    let old = resolve(&path);
+    self.update_ref_file(&path, &value)?;
+    let new = resolve(&path);
+    if self.has_reflog(&path) {
+      self.append_reflog(&path, &old, &new, message)?;
+    }
+
+    if ref_value.path != path && self.has_reflog(&ref_value.path) {
+      self.append_reflog(&ref_value.path, &old, &new, message)?;
+    }
+
+    Ok(())
+  }
+
+  // Appends an entry to the reflog of the ref at path, kept in .ugit/logs under the same name as the ref. The log is created by the
+  // first entry.
+  pub fn append_reflog(&self, path: &Path, old: &Oid, new: &Oid, message: &str) -> std::io::Result<()> {
+    let log_path = self.get_reflog_path(path)?;
+    fs::create_dir_all(log_path.parent().unwrap())?;
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+    // Entries are one line each, so the message is kept to a single line
+    let message = message.lines().collect::<Vec<_>>().join(" ");
+    let mut file = fs::OpenOptions::new().create(true).append(true).open(&log_path)?;
+    writeln!(file, "{} {} {} +0000\t{}", old, new, timestamp, message)
+  }
+
+  // Returns the entries of the reflog of the ref at path, oldest first. A ref which was never updated has none.
+  pub fn read_reflog(&self, path: &Path) -> std::io::Result<Vec<ReflogEntry>> {
+    let log_path = self.get_reflog_path(path)?;
+    if !log_path.is_file() {
+      return Ok(Vec::new());
+    }
+
+    let mut entries = Vec::new();
+    for line in fs::read_to_string(&log_path)?.lines() {
+      let (header, message) = match line.find('\t') {
+        Some(i) => (&line[..i], &line[i + 1..]),
+        None => (line, "")
+      };
+
+      let parts: Vec<_> = header.split(' ').collect();
+      let old = parts[0].parse().ok();
+      let new = parts.get(1).and_then(|new| new.parse().ok());
+      let timestamp = parts.get(2).and_then(|timestamp| timestamp.parse().ok());
+      match (old, new, timestamp) {
+        (Some(old), Some(new), Some(timestamp)) => entries.push(ReflogEntry { old, new, timestamp, message: String::from(message) }),
+        _ => return Err(Error::new(ErrorKind::InvalidData, format!("Malformed entry in reflog [{}]", log_path.display())))
+      };
+    }
+
+    Ok(entries)
+  }
+
+  // Reads the ref file at path, which is used as given: a relative path is relative to the current directory rather than to the
+  // repository, so paths should come from generate_path. Refs named by a RefVariant are read with get_ref_by_name instead.
+  pub fn get_ref(&self, path: &Path, deref: bool) -> std::io::Result<RefValue> {
+    match self.get_ref_file(path, deref) {
+      Some(maybe_ref_value) => maybe_ref_value,
+      None => Ok(RefValue { value: None, path: path.to_path_buf() })
+    }
+  }
+
+  // Reads a branch or tag by name, wherever the current directory is within the repository.
+  pub fn get_ref_by_name(&self, ref_variant: RefVariant, deref: bool) -> std::io::Result<RefValue> {
+    self.get_ref(&self.generate_path(PathVariant::Ref(ref_variant)), deref)
+  }
+
+  // Lists every ref by its name relative to .ugit, such as "refs/heads/main", along with its value, dereferenced when deref is set.
+  // HEAD comes first, if it exists, followed by the refs beneath .ugit/refs in sorted order.
+  pub fn iter_refs(&self, deref: bool) -> std::io::Result<Vec<(String, RefValue)>> {
+    let ugit = self.generate_path(PathVariant::Ugit);
+    let mut refs = Vec::new();
+    let head = self.generate_path(PathVariant::Head);
+    if head.is_file() {
+      refs.push((String::from("HEAD"), self.get_ref(&head, deref)?));
+    }
+
+    let mut paths = Vec::new();
+    collect_ref_paths(&self.generate_path(PathVariant::Refs), &mut paths)?;
+    paths.sort();
+    for path in paths {
+      let name = path.strip_prefix(&ugit).unwrap().components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/");
+
+      let ref_value = self.get_ref(&path, deref)?;
+      refs.push((name, ref_value));
+    }
+
+    Ok(refs)
+  }
+
+  // Removes the ref file at path. A symbolic ref is removed itself, leaving the ref it points to alone.
+  pub fn delete_ref(&self, path: &Path) -> std::io::Result<()> {
+    if !path.is_file() {
+      return Err(Error::new(ErrorKind::NotFound, format!("Ref does not exist [{}]", path.display())));
+    }
+
+    fs::remove_file(path)?;
+    // The reflog goes along with the ref, as git does
+    if self.has_reflog(path) {
+      let log_path = self.get_reflog_path(path)?;
+      if log_path.is_file() {
+        fs::remove_file(log_path)?;
+      }
+    }
+
+    Ok(())
+  }
+
+  // Points HEAD at either a commit OID or a ref such as "refs/heads/main". HEAD itself is overwritten, never the ref it may currently
+  // point to. The move is described by message in HEAD's reflog.
+  pub fn set_head(&self, target: RefTarget, message: &str) -> std::io::Result<()> {
+    let path = self.generate_path(PathVariant::Head);
+    let ref_value = RefValue { value: Some(target), path };
+    self.update_ref_with_message(&ref_value, false, message)
+  }
+
+  // Returns the OID of the commit HEAD resolves to, following a symbolic HEAD through to its branch.
+  pub fn get_head(&self) -> Option<std::io::Result<Oid>> {
+    let path = self.generate_path(PathVariant::Head);
+    match self.get_ref_file(&path, true) {
+      None => None,
+      Some(maybe_ref_value) => {
+        match maybe_ref_value {
+          Ok(ref_value) => match ref_value.value {
+            Some(RefTarget::Oid(oid)) => Some(Ok(oid)),
+            _ => None
+          },
+          Err(err) => Some(Err(Error::new(err.kind(), format!("Error while getting contents of HEAD -- {}", err))))
+        }
+      }
+    }
+  }
+
+  fn get_ref_file(&self, path: &Path, deref: bool) -> Option<std::io::Result<RefValue>> {
+    if !path.is_file() {
+      return None;
+    }
+
+    Some(self.recur_deref(path, deref, 0))
+  }
+
+  // When deref is set, symbolic refs are followed until a ref holding an OID is found. The returned RefValue carries the path of
+  // that final ref, which may not exist yet (e.g. HEAD pointing to a branch without any commits), in which case its value is None.
+  // A chain longer than MAX_SYMBOLIC_REF_DEPTH, which is what a cycle of symbolic refs becomes, is an InvalidData error.
+  fn recur_deref(&self, path: &Path, deref: bool, depth: usize) -> std::io::Result<RefValue> {
+    if depth > MAX_SYMBOLIC_REF_DEPTH {
+      return Err(Error::new(ErrorKind::InvalidData, format!("Symbolic refs are nested too deeply or form a cycle [{}]", path.display())));
+    }
+
+    match fs::read_to_string(&path) {
+      Err(err) => return Err(Error::new(err.kind(), format!("Error when reading from {} (recur_deref) -- {}", path.display(), err))),
+      Ok(contents) => {
+        // Ref files written by hand, with an editor or echo, usually end in a newline which is not part of the ref. git writes a
+        // space after "ref:", which is not part of the target either.
+        let contents = contents.trim();
+        if contents.starts_with("ref:") {
+          let content_parts: Vec<&str> = contents.splitn(2, ":").map(str::trim).collect();
+          if deref {
+            let mut target = self.generate_path(PathVariant::Ugit);
+            target.push(content_parts[1]);
+            if target.is_file() {
+              self.recur_deref(&target, deref, depth + 1)
+            }
+            else {
+              Ok(RefValue { value: None, path: target })
+            }
+          }
+          else {
+            Ok(RefValue { value: Some(RefTarget::Symbolic(String::from(content_parts[1]))), path: path.to_path_buf() })
+          }
+        }
+        else {
+          match contents.parse() {
+            Ok(oid) => Ok(RefValue { value: Some(RefTarget::Oid(oid)), path: path.to_path_buf() }),
+            Err(_) => Err(Error::new(ErrorKind::InvalidData, format!("Ref holds neither an OID nor another ref [{}]", path.display())))
+          }
+        }
+      }
+    }
+  }
+
+  // Only HEAD and the refs beneath .ugit/refs keep a reflog, not the likes of MERGE_HEAD
+  fn has_reflog(&self, path: &Path) -> bool {
+    path == self.generate_path(PathVariant::Head) || path.starts_with(self.generate_path(PathVariant::Refs))
+  }
+
+  fn get_reflog_path(&self, path: &Path) -> std::io::Result<PathBuf> {
+    match path.strip_prefix(self.generate_path(PathVariant::Ugit)) {
+      Ok(name) => Ok(self.generate_path(PathVariant::Logs).join(name)),
+      Err(_) => Err(Error::new(ErrorKind::InvalidInput, format!("Ref is outside of the repository [{}]", path.display())))
+    }
+  }
+
+  fn update_ref_file(&self, path: &Path, oid: &str) -> std::io::Result<()> {
+    if !self.validate_user_given_ref(oid) {
+      return Err(Error::new(ErrorKind::InvalidInput, format!("Tried to create a ref for something that is not a commit or another ref at {}", path.display())));
+    }
+
+    fs::write(&path, oid)?;
+    Ok(())
+  }
+
+  // Refs may only point to commits or to other refs. This function is meant to check inside a given OID to see if it contains either of those.
+  fn validate_user_given_ref(&self, oid: &str) -> bool {
+    // A symbolic ref names another ref, which does not need to exist yet
+    if oid.starts_with("ref:") {
+      return oid.splitn(2, ":").collect::<Vec<&str>>()[1].starts_with("refs/");
+    }
+
+    let contents = match oid.parse().and_then(|oid| self.objects().read_object(&oid)) {
+      Ok(contents) => contents,
+      Err(_) => return false
+    };
+
+    match String::from_utf8(contents.clone()) {
+      Ok(s) => {
+        // In this case, the given OID is a ref which is pointing to another OID.
+        if utils::is_hex(&s) {
+          return true;
+        }
+      },
+      Err(_) => ()
+    };
+
+    let content_parts: Vec<_> = contents
+      .splitn(2, |b| *b == b'\0')
+      .collect();
+
+    // An annotated tag's ref points to the tag object rather than to the commit
+    if content_parts[0] == b"commit" || content_parts[0] == b"tag" {
+      true
+    }
+    else {
+      false
+    }
+  }
+
+  // When peel is set, an annotated tag is followed down to the object it tags, so that a tag's name stands for its commit.
+  pub fn locate_ref_or_oid(&self, s: &str, peel: bool) -> Option<std::io::Result<Oid>> {
+    // Only refs which resolve to an OID count: a symbolic HEAD pointing to a branch without commits does not.
+    let get_ref_from_variant = |path_variant: PathVariant| match self.get_ref_file(&self.generate_path(path_variant), true) {
+      Some(Ok(ref_value)) => ref_value.oid().cloned(),
+      _ => None
+    };
+
+    let mut count_of_refs_located = 0;
+    let mut ret_oid = None;
+    if let Some(oid) = get_ref_from_variant(PathVariant::Ref(RefVariant::Tag(s))) {
+      count_of_refs_located += 1;
+      ret_oid = Some(oid);
+    }
+    if let Some(oid) = get_ref_from_variant(PathVariant::Ref(RefVariant::Head(s))) {
+      count_of_refs_located += 1;
+      ret_oid = Some(oid);
+    }
+    // An object is not a ref file, so its (compressed) contents are not read here: the given OID itself is the result.
+    if let Ok(oid) = s.parse::<Oid>() {
+      if self.objects().contains(&oid) {
+        count_of_refs_located += 1;
+        ret_oid = Some(oid);
+      }
+    }
+    if s == "HEAD" || s == "@" {
+      if let Some(oid) = get_ref_from_variant(PathVariant::Head) {
+        count_of_refs_located += 1;
+        ret_oid = Some(oid);
+      }
+    }
+
+    match ret_oid {
+      None => None,
+      Some(_) if count_of_refs_located > 1 => Some(Err(Error::new(ErrorKind::InvalidInput, format!("Ref '{}' is ambiguous", s)))),
+      Some(oid) if peel => Some(self.peel_tag(oid)),
+      Some(oid) => Some(Ok(oid))
+    }
+  }
+
+  // Follows tag objects down to the object they tag. Anything which is not a tag object, including an OID without an object, is
+  // returned as is.
+  pub fn peel_tag(&self, oid: Oid) -> std::io::Result<Oid> {
+    match self.get_object_type(&oid) {
+      Ok(ObjectType::Tag) => (),
+      Ok(_) => return Ok(oid),
+      Err(err) if err.kind() == ErrorKind::NotFound => return Ok(oid),
+      Err(err) => return Err(err)
+    };
+
+    let tag = String::from_utf8_lossy(&self.get_object(&oid, ObjectType::Tag)?).into_owned();
+    let object = tag
+      .lines()
+      .take_while(|line| !line.is_empty())
+      .find_map(|line| line.strip_prefix("object "));
+
+    match object {
+      Some(object) => self.peel_tag(object.parse()?),
+      None => Err(Error::new(ErrorKind::InvalidData, format!("Missing object row of tag [{}]", oid)))
+    }
+  }
+
+  // Finds the one object whose OID begins with the given prefix.
+  pub fn resolve_oid_prefix(&self, prefix: &str) -> std::io::Result<Oid> {
+    self.objects().resolve_oid_prefix(prefix)
+  }
+
+  // Looks up a value of .ugit/config as it was read when the repository was found.
+  pub fn get_config(&self, key: &str) -> std::io::Result<Option<String>> {
+    Ok(self.config.borrow().get(key).cloned())
+  }
+
+  // Reads .ugit/config, a file of "key = value" lines.
+  fn read_config(&self) -> std::io::Result<HashMap<String, String>> {
+    let path = self.generate_path(PathVariant::Config);
+    if !path.is_file() {
+      return Ok(HashMap::new());
+    }
+
+    Ok(parse_config(&fs::read_to_string(&path)?))
+  }
+
+  // Sets key to value in .ugit/config, replacing any value key already has and leaving every other line alone.
+  pub fn set_config(&self, key: &str, value: &str) -> std::io::Result<()> {
+    let key = key.trim();
+    let value = value.trim();
+    if key.is_empty() || key.contains('=') || key.contains('\n') || value.contains('\n') {
+      return Err(Error::new(ErrorKind::InvalidInput, format!("Cannot set config key [{}] to [{}]", key, value)));
+    }
+
+    let path = self.generate_path(PathVariant::Config);
+    let contents = if path.is_file() {
+      fs::read_to_string(&path)?
+    }
+    else {
+      String::new()
+    };
+
+    let mut found = false;
+    let mut lines = Vec::new();
+    for line in contents.lines() {
+      let parts: Vec<_> = line.splitn(2, "=").collect();
+      if parts.len() == 2 && parts[0].trim() == key {
+        if !found {
+          lines.push(format!("{} = {}", key, value));
+        }
+
+        found = true;
+      }
+      else {
+        lines.push(String::from(line));
+      }
+    }
+
+    if !found {
+      lines.push(format!("{} = {}", key, value));
+    }
+
+    let contents = format!("{}\n", lines.join("\n"));
+    fs::write(&path, &contents)?;
+    // The file was read anyway, so any lines changed since the config was read are taken up along with the new value
+    *self.config.borrow_mut() = parse_config(&contents);
+    Ok(())
+  }
+
+  // The staging index maps each staged path, relative to the repository root and separated by '/', to its blob OID. It is stored
+  // as a JSON object, and a missing index is the same as an empty one.
+  pub fn get_index(&self) -> std::io::Result<BTreeMap<String, Oid>> {
+    let path = self.generate_path(PathVariant::Index);
+    if !path.is_file() {
+      return Ok(BTreeMap::new());
+    }
+
+    match utils::parse_json_object(&fs::read_to_string(&path)?) {
+      Some(index) => index.into_iter().map(|(path, oid)| Ok((path, oid.parse()?))).collect(),
+      None => Err(Error::new(ErrorKind::InvalidData, format!("Index is not a JSON object of strings [{}]", path.display())))
+    }
   }
 
-  let mut root = env::current_dir().expect("Issue when getting cwd");
-  root.push(GIT_DIR);
-  fs::create_dir(&root)?;
-  // Create .ugit/objects
-  fs::create_dir(generate_path(PathVariant::Objects)?)?;
-  // Create .ugit/refs
-  fs::create_dir(generate_path(PathVariant::Refs)?)?;
-  // Create directories within .ugit/refs
-  fs::create_dir(generate_path(PathVariant::Heads)?)?;
-  fs::create_dir(generate_path(PathVariant::Tags)?)?;
+  pub fn write_index(&self, index: &BTreeMap<String, Oid>) -> std::io::Result<()> {
+    let path = self.generate_path(PathVariant::Index);
+    let index = index.iter().map(|(path, oid)| (path.clone(), oid.to_string())).collect();
+    fs::write(path, utils::to_json_object(&index))
+  }
 
-  return Ok(())
+  pub fn generate_path(&self, variant: PathVariant) -> PathBuf {
+    let mut path = self.ugit.clone();
+    match variant {
+      PathVariant::CherryPickHead => {
+        path.push("CHERRY_PICK_HEAD");
+        path
+      },
+      PathVariant::CommitMessage => {
+        path.push("COMMIT_EDITMSG");
+        path
+      },
+      PathVariant::Config => {
+        path.push("config");
+        path
+      },
+      PathVariant::Head => {
+        path.push("HEAD");
+        path
+      },
+      PathVariant::Heads => {
+        path.push("refs");
+        path.push("heads");
+        path
+      },
+      PathVariant::Index => {
+        path.push("index");
+        path
+      },
+      PathVariant::Logs => {
+        path.push("logs");
+        path
+      },
+      PathVariant::MergeHead => {
+        path.push("MERGE_HEAD");
+        path
+      },
+      PathVariant::Objects => {
+        path.push("objects");
+        path
+      },
+      #[cfg(test)]
+      PathVariant::OID(oid) => {
+        path.push("objects");
+        FsObjectStore::new(path).path(oid)
+      },
+      PathVariant::Ref(ref_variant) => {
+        match ref_variant {
+          RefVariant::Head(name) => {
+            path.push("refs");
+            path.push("heads");
+            path.push(name);
+          },
+          RefVariant::Tag(name) => {
+            path.push("refs");
+            path.push("tags");
+            path.push(name);
+          },
+        };
+
+        path
+      },
+      PathVariant::Refs => {
+        path.push("refs");
+        path
+      },
+      PathVariant::Root => self.root.clone(),
+      PathVariant::Stash => {
+        path.push("refs");
+        path.push("stash");
+        path
+      },
+      PathVariant::Tags => {
+        path.push("refs");
+        path.push("tags");
+        path
+      },
+      PathVariant::Ugit => path,
+    }
+  }
 }
 
-pub fn hash_object(file_contents: &[u8], object_type: ObjectType) -> std::io::Result<String> {
-  if !repository_initialized() {
-    return Err(Error::new(ErrorKind::NotFound, "A ugit repository does not exist"));
+pub fn init(object_format: ObjectFormat) -> std::io::Result<()> {
+  if repository_initialized() {
+    return Err(Error::new(ErrorKind::AlreadyExists, "A ugit repository already exists"));
   }
 
-  // ugit objects are their object type, followed by a null byte, and then the file contents
-  let mut contents = match object_type {
-    ObjectType::Blob => String::from("blob\0").into_bytes(),
-    ObjectType::Commit => String::from("commit\0").into_bytes(),
-    ObjectType::Tree => String::from("tree\0").into_bytes(),
-  };
+  Repository::init(&env::current_dir().expect("Issue when getting cwd"), object_format)?;
+  Ok(())
+}
+
+// Recreates any of the existing repository's directories, or its HEAD, which have gone missing, without touching its objects,
+// refs or config.
+pub fn reinit() -> std::io::Result<()> {
+  Repository::find()?.reinit()
+}
+
+pub fn hash_object(file_contents: &[u8], object_type: ObjectType) -> std::io::Result<Oid> {
+  Repository::find()?.hash_object(file_contents, object_type)
+}
 
+// ugit objects are their object type, followed by a null byte, and then the file contents
+fn frame_object(file_contents: &[u8], object_type: ObjectType) -> Vec<u8> {
+  let mut contents = type_header(object_type).as_bytes().to_vec();
+  contents.push(b'\0');
   contents.extend(file_contents);
+  contents
+}
+
+pub fn type_header(object_type: ObjectType) -> &'static str {
+  match object_type {
+    ObjectType::Blob => "blob",
+    ObjectType::Commit => "commit",
+    ObjectType::Tag => "tag",
+    ObjectType::Tree => "tree",
+  }
+}
+
+// Accepts the same names as are written into object headers, such as "blob".
+pub fn parse_object_type(name: &str) -> std::io::Result<ObjectType> {
+  parse_type_header(name.as_bytes())
+}
 
-  let mut hasher = Sha256::new();
-  hasher.update(&contents);
-  let object = hasher.finalize();
-  let oid = format!("{:x}", object);
-  let file_path = generate_path(PathVariant::OID(&oid)).unwrap();
-  fs::write(&file_path, &contents)?;
-  Ok(oid)
+fn parse_type_header(header: &[u8]) -> std::io::Result<ObjectType> {
+  match header {
+    b"blob" => Ok(ObjectType::Blob),
+    b"commit" => Ok(ObjectType::Commit),
+    b"tag" => Ok(ObjectType::Tag),
+    b"tree" => Ok(ObjectType::Tree),
+    _ => Err(Error::new(ErrorKind::InvalidData, format!("Object has an unknown type [{}]", String::from_utf8_lossy(header))))
+  }
 }
 
-// TODO: get_object should return Vec<u8>: if the ObjectType is a blob, it is possible that read_to_string will fail if the
-//       blob's contents contains any invalid utf-8 bytes.
-pub fn get_object(oid: &str, expected_type: ObjectType) -> std::io::Result<String> {
-  if !repository_initialized() {
-    return Err(Error::new(ErrorKind::NotFound, "A ugit repository does not exist"));
+pub fn object_format_name(object_format: ObjectFormat) -> &'static str {
+  match object_format {
+    ObjectFormat::Sha1 => "sha1",
+    ObjectFormat::Sha256 => "sha256",
   }
+}
 
-  let file_path = generate_path(PathVariant::OID(&oid)).unwrap();
-  if !&file_path.exists() {
-    return Err(Error::new(ErrorKind::NotFound, format!("A file with the given OID does not exist [{}]", &file_path.display()).as_str()));
+pub fn parse_object_format(name: &str) -> std::io::Result<ObjectFormat> {
+  match name {
+    "sha1" => Ok(ObjectFormat::Sha1),
+    "sha256" => Ok(ObjectFormat::Sha256),
+    _ => Err(Error::new(ErrorKind::InvalidData, format!("Unknown object format [{}]", name)))
   }
+}
 
-  let contents = fs::read_to_string(&file_path)?;
+pub fn get_object(oid: &Oid, expected_type: ObjectType) -> std::io::Result<Vec<u8>> {
+  Repository::find()?.get_object(oid, expected_type)
+}
+
+pub fn open_object(oid: &Oid, expected_type: ObjectType) -> std::io::Result<impl Read> {
+  Repository::find()?.open_object(oid, expected_type)
+}
+
+// Splits the type header off an object's contents, checking it is the expected type.
+fn split_object(oid: &str, contents: &[u8], expected_type: ObjectType) -> std::io::Result<Vec<u8>> {
   let content_parts: Vec<_> = contents
-    .splitn(2, char::from(0))
+    .splitn(2, |b| *b == b'\0')
     .collect();
 
-  if expected_type == ObjectType::Blob && content_parts[0] != "blob" {
-    return Err(Error::new(ErrorKind::InvalidData, format!("Object was expected to be a blob, but was a [{}]", content_parts[0])));
-  }
-  else if expected_type == ObjectType::Commit && content_parts[0] != "commit" {
-    return Err(Error::new(ErrorKind::InvalidData, format!("Object was expected to be a commit, but was a [{}]", content_parts[0])));
+  if content_parts.len() < 2 {
+    return Err(Error::new(ErrorKind::InvalidData, format!("Object is missing its type header [{}]", oid)));
   }
-  else if expected_type == ObjectType::Tree && content_parts[0] != "tree" {
-    return Err(Error::new(ErrorKind::InvalidData, format!("Object was expected to be a tree, but was a [{}]", content_parts[0])));
+
+  if parse_type_header(content_parts[0])? != expected_type {
+    let message = format!("Object was expected to be a {}, but was a [{}]", type_header(expected_type), String::from_utf8_lossy(content_parts[0]));
+    return Err(Error::new(ErrorKind::InvalidData, message));
   }
 
-  Ok(String::from(content_parts[1]))
+  Ok(content_parts[1].to_vec())
 }
 
-pub fn update_ref(ref_value: &RefValue, deref: bool) -> std::io::Result<()> {
-  // Using get_ref here to drill down to the commit, in the case that ref_value.path contains a symbolic ref.
-  let path = match get_ref(&ref_value.path, deref) {
-    Ok(ref_value) => ref_value.path,
-    Err(err) => return Err(
-      Error::new(err.kind(), format!("While trying to update ref ['{}'|{:?}], an error occured: {}", ref_value.path.display(), ref_value.value, err)))
-  };
 
-  if let Some(ref value) = ref_value.value {
-    let value = if ref_value.symbolic {
-      format!("ref:{}", value)
+pub fn get_object_type(oid: &Oid) -> std::io::Result<ObjectType> {
+  Repository::find()?.get_object_type(oid)
+}
+
+pub fn object_info(oid: &Oid) -> std::io::Result<(ObjectType, usize)> {
+  Repository::find()?.object_info(oid)
+}
+
+fn compress_object(contents: &[u8]) -> std::io::Result<Vec<u8>> {
+  let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+  encoder.write_all(contents)?;
+  encoder.finish()
+}
+
+// Reads an object's type header along with the '\0' which ends it, returning the header without it.
+fn read_type_header(reader: &mut impl Read, oid: &str) -> std::io::Result<Vec<u8>> {
+  let mut header = Vec::new();
+  let mut byte = [0; 1];
+  while header.len() <= MAX_TYPE_HEADER_LENGTH {
+    if reader.read(&mut byte)? == 0 {
+      break;
+    }
+
+    if byte[0] == b'\0' {
+      return Ok(header);
     }
-    else {
-      String::from(value)
-    };
 
-    update_ref_file(&path, &value)
+    header.push(byte[0]);
   }
-  else {
-    panic!("Tried to update ref with an empty ref: {:?}", ref_value);
+
+  Err(Error::new(ErrorKind::InvalidData, format!("Object is missing its type header [{}]", oid)))
+}
+
+// Returns the uncompressed contents of a stored object, including its type header.
+fn decompress_object(oid: &str, contents: Vec<u8>) -> std::io::Result<Vec<u8>> {
+  if contents.first() != Some(&ZLIB_MAGIC) {
+    return Ok(contents);
   }
+
+  let mut decoder = ZlibDecoder::new(&contents[..]);
+  let mut inflated = Vec::new();
+  if let Err(err) = decoder.read_to_end(&mut inflated) {
+    return Err(Error::new(ErrorKind::InvalidData, format!("Object [{}] could not be decompressed -- {}", oid, err)));
+  }
+
+  Ok(inflated)
+}
+
+pub fn update_ref(ref_value: &RefValue, deref: bool) -> std::io::Result<()> {
+  Repository::find()?.update_ref(ref_value, deref)
+}
+
+pub fn update_ref_with_message(ref_value: &RefValue, deref: bool, message: &str) -> std::io::Result<()> {
+  Repository::find()?.update_ref_with_message(ref_value, deref, message)
+}
+
+pub fn read_reflog(path: &Path) -> std::io::Result<Vec<ReflogEntry>> {
+  Repository::find()?.read_reflog(path)
 }
 
 pub fn get_ref(path: &Path, deref: bool) -> std::io::Result<RefValue> {
-  match get_ref_file(&path, deref) {
-    Some(maybe_ref_value) => maybe_ref_value,
-    None => Ok(RefValue { symbolic: false, value: None, path: path.clone().to_path_buf() })
+  Repository::find()?.get_ref(path, deref)
+}
+
+pub fn get_ref_by_name(ref_variant: RefVariant, deref: bool) -> std::io::Result<RefValue> {
+  Repository::find()?.get_ref_by_name(ref_variant, deref)
+}
+
+pub fn iter_refs(deref: bool) -> std::io::Result<Vec<(String, RefValue)>> {
+  Repository::find()?.iter_refs(deref)
+}
+
+fn collect_ref_paths(dir: &Path, paths: &mut Vec<PathBuf>) -> std::io::Result<()> {
+  for entry in fs::read_dir(dir)? {
+    let path = entry?.path();
+    if path.is_dir() {
+      collect_ref_paths(&path, paths)?;
+    }
+    else {
+      paths.push(path);
+    }
   }
+
+  Ok(())
 }
 
-pub fn set_head(oid: &str) -> std::io::Result<()> {
-  let path = match generate_path(PathVariant::Head) {
-    Ok(path) => path,
-    Err(err) => return Err(Error::new(err.kind(), format!("Error when setting contents of HEAD -- {}", err)))
-  };
+// Branch and tag names become file names beneath .ugit/refs, so a name which could nest or escape that directory is refused.
+pub fn validate_ref_name(name: &str) -> std::io::Result<()> {
+  if name.is_empty() || name.contains('/') || name.contains('\\') || name.contains("..") || name.starts_with('.') {
+    return Err(Error::new(ErrorKind::InvalidInput, format!("'{}' is not a valid ref name", name)));
+  }
 
-  update_ref_file(&path, oid)
+  Ok(())
 }
 
-pub fn get_head() -> Option<std::io::Result<String>> {
-  let path = match generate_path(PathVariant::Head) {
-    Ok(path) => path,
-    Err(err) => return Some(Err(Error::new(err.kind(), format!("Error when getting contents of HEAD -- {}", err))))
-  };
+pub fn delete_ref(path: &Path) -> std::io::Result<()> {
+  Repository::find()?.delete_ref(path)
+}
 
-  match get_ref_file(&path, false) {
-    None => None,
-    Some(maybe_ref_value) => {
-      match maybe_ref_value {
-        Ok(ref_value) => match ref_value.value {
-          Some(value) => Some(Ok(value)),
-          None => None
-        },
-        Err(err) => Some(Err(Error::new(err.kind(), format!("Error while getting contents of HEAD -- {}", err))))
-      }
-    }
+pub fn set_head(target: RefTarget, message: &str) -> std::io::Result<()> {
+  match Repository::find() {
+    Ok(repository) => repository.set_head(target, message),
+    Err(err) => Err(Error::new(err.kind(), format!("Error when setting contents of HEAD -- {}", err)))
   }
 }
 
-fn get_ref_file(path: &Path, deref: bool) -> Option<std::io::Result<RefValue>> {
-  if !repository_initialized() {
-    return Some(Err(Error::new(ErrorKind::NotFound, "A ugit repository does not exist")));
+pub fn get_head() -> Option<std::io::Result<Oid>> {
+  match Repository::find() {
+    Ok(repository) => repository.get_head(),
+    Err(err) => Some(Err(Error::new(err.kind(), format!("Error when getting contents of HEAD -- {}", err))))
   }
+}
 
-  if !path.is_file() {
-    return None;
+pub fn locate_ref_or_oid(s: &str, peel: bool) -> Option<std::io::Result<Oid>> {
+  match Repository::find() {
+    Ok(repository) => repository.locate_ref_or_oid(s, peel),
+    Err(err) => Some(Err(err))
   }
+}
 
-  let value = match recur_deref(path, deref) {
-    Ok(value) => value,
-    Err(err) => return Some(Err(err))
-  };
+pub fn peel_tag(oid: Oid) -> std::io::Result<Oid> {
+  Repository::find()?.peel_tag(oid)
+}
 
-  let symbolic = value.starts_with("ref:");
-  let ref_value = RefValue { symbolic, value: Some(value), path: path.clone().to_path_buf() };
-  Some(Ok(ref_value))
+pub fn resolve_oid_prefix(prefix: &str) -> std::io::Result<Oid> {
+  Repository::find()?.resolve_oid_prefix(prefix)
 }
 
-fn recur_deref(path: &Path, deref: bool) -> std::io::Result<String> {
-  match fs::read_to_string(&path) {
-    Err(err) => return Err(Error::new(err.kind(), format!("Error when reading from {} (recur_deref) -- {}", path.display(), err))),
-    Ok(contents) => {
-      if contents.starts_with("ref:") {
-        let content_parts: Vec<&str> = contents.splitn(2, ":").collect();
-        if deref {
-          let path = PathBuf::from(content_parts[1]);
-          recur_deref(&path, deref)
-        }
-        else {
-          Ok(String::from(content_parts[1]))
-        }
-      }
-      else {
-        Ok(contents)
-      }
-    }
-  }
+pub fn get_config(key: &str) -> std::io::Result<Option<String>> {
+  Repository::find()?.get_config(key)
 }
 
-fn update_ref_file(path: &Path, oid: &str) -> std::io::Result<()> {
-  if !repository_initialized() {
-    return Err(Error::new(ErrorKind::NotFound, "A ugit repository does not exist"));
-  }
-  else if !validate_user_given_ref(oid) {
-    panic!("Tried to create a ref for something that is not a commit or another ref at {}", path.display());
+// Parses the "key = value" lines of .ugit/config. Where a key is given more than once, its first value is used.
+fn parse_config(contents: &str) -> HashMap<String, String> {
+  let mut config = HashMap::new();
+  for line in contents.lines() {
+    let parts: Vec<_> = line.splitn(2, "=").collect();
+    if parts.len() == 2 {
+      config.entry(String::from(parts[0].trim())).or_insert_with(|| String::from(parts[1].trim()));
+    }
   }
 
-  fs::write(&path, oid)?;
-  Ok(())
+  config
 }
 
-// Refs may only point to commits or to other refs. This function is meant to check inside a given OID to see if it contains either of those.
-fn validate_user_given_ref(oid: &str) -> bool {
-  let oid = if oid.starts_with("ref:") {
-    oid.splitn(2, ":").collect::<Vec<&str>>()[1]
-  } else {
-    oid
-  };
-
-  let path = generate_path(PathVariant::OID(oid)).unwrap();
-  let contents = match fs::read(&path) {
-    Ok(contents) => contents,
-    Err(_) => return false
-  };
-
-  match String::from_utf8(contents.clone()) {
-    Ok(s) => {
-      // In this case, the given OID is a ref which is pointing to another OID.
-      if utils::is_hex(&s) {
-        return true;
-      }
-    },
-    Err(_) => ()
-  };
-
-  let content_parts: Vec<_> = contents
-    .splitn(2, |b| *b == b'\0')
-    .collect();
-
-  if content_parts[0] == b"commit" {
-    true
-  }
-  else {
-    false
-  }
+pub fn set_config(key: &str, value: &str) -> std::io::Result<()> {
+  Repository::find()?.set_config(key, value)
 }
 
-pub fn locate_ref_or_oid(s: &str) -> Option<std::io::Result<String>> {
-  if !repository_initialized() {
-    return Some(Err(Error::new(ErrorKind::NotFound, "A ugit repository does not exist")));
-  }
-
-  let get_ref_from_variant = |path_variant: PathVariant| get_ref_file(&generate_path(path_variant).unwrap(), false); 
-
-  let mut count_of_refs_located = 0;
-  let mut ret_ref_value = None;
-  if let Some(maybe_ref_value) = get_ref_from_variant(PathVariant::Ref(RefVariant::Tag(s))) {
-    if let Ok(ref_value) = maybe_ref_value {
-      count_of_refs_located += 1;
-      ret_ref_value = Some(ref_value);
-    }
-  }
-  if let Some(maybe_ref_value) = get_ref_from_variant(PathVariant::Ref(RefVariant::Head(s))) {
-    if let Ok(ref_value) = maybe_ref_value {
-      count_of_refs_located += 1;
-      ret_ref_value = Some(ref_value);
-    }
-  }
-  if let Some(maybe_ref_value) = get_ref_from_variant(PathVariant::OID(s)) {
-    if let Ok(ref_value) = maybe_ref_value {
-      count_of_refs_located += 1;
-      ret_ref_value = Some(ref_value);
-    }
-  }
-  if s == "HEAD" || s == "@" {
-    if let Some(maybe_ref_value) = get_ref_from_variant(PathVariant::Head) {
-      if let Ok(ref_value) = maybe_ref_value {
-        count_of_refs_located += 1;
-        ret_ref_value = Some(ref_value);
-      }
-    }
-  }
+pub fn get_index() -> std::io::Result<BTreeMap<String, Oid>> {
+  Repository::find()?.get_index()
+}
 
-  match ret_ref_value {
-    None => None,
-    Some(ref_value) => if count_of_refs_located > 1 {
-      Some(Err(Error::new(ErrorKind::InvalidInput, format!("Ref '{}' is ambiguous", s))))
-    }
-    else {
-      let oid = ref_value.value.unwrap();
-      Some(Ok(oid))
-    }
-  }
+pub fn write_index(index: &BTreeMap<String, Oid>) -> std::io::Result<()> {
+  Repository::find()?.write_index(index)
 }
 
 pub enum PathVariant<'a> {
+  CherryPickHead,
+  CommitMessage,
+  Config,
   Head,
   Heads,
+  Index,
+  Logs,
+  MergeHead,
   Objects,
+  // The file an object is stored in, which only tests look at directly rather than through the object store
+  #[cfg(test)]
   OID(&'a str),
   Ref(RefVariant<'a>),
   Refs,
   Root,
+  Stash,
   Tags,
-  #[cfg(test)]
   Ugit,
 }
 
@@ -308,69 +1333,67 @@ pub enum RefVariant<'a> {
   Tag(&'a str),
 }
 
+// What a ref holds: the OID of an object, or, for a symbolic ref, the name of the ref it points to such as "refs/heads/main"
+#[derive(Clone, Debug, PartialEq)]
+pub enum RefTarget {
+  Oid(Oid),
+  Symbolic(String),
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub struct RefValue {
-  pub symbolic: bool,
-  pub value: Option<String>,
+  // None when the ref does not exist, as with the branch HEAD points to before its first commit
+  pub value: Option<RefTarget>,
   pub path: PathBuf,
 }
 
+impl RefValue {
+  // The OID the ref holds, unless it is a symbolic ref or does not exist.
+  pub fn oid(&self) -> Option<&Oid> {
+    match self.value {
+      Some(RefTarget::Oid(ref oid)) => Some(oid),
+      _ => None
+    }
+  }
+
+  // The name of the ref a symbolic ref points to.
+  pub fn symbolic_target(&self) -> Option<&str> {
+    match self.value {
+      Some(RefTarget::Symbolic(ref name)) => Some(name),
+      _ => None
+    }
+  }
+}
+
+// An entry of a reflog: the OIDs a ref resolved to before and after an update, when it happened, and what it was
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReflogEntry {
+  pub old: Oid,
+  pub new: Oid,
+  pub timestamp: u64,
+  pub message: String,
+}
+
 pub fn generate_path(variant: PathVariant) -> std::io::Result<PathBuf> {
-  let mut path = match get_repository() {
-    Some(path) => path,
-    None => return Err(Error::new(ErrorKind::NotFound, "A ugit repository does not exist")),
-  };
-
-  let path = match variant {
-    PathVariant::Head => {
-      path.push("HEAD");
-      path
-    },
-    PathVariant::Heads => {
-      path.push("refs");
-      path.push("heads");
-      path
-    },
-    PathVariant::Objects => {
-      path.push("objects");
-      path
-    },
-    PathVariant::OID(oid) => {
-      path.push("objects");
-      path.push(oid);
-      path
-    },
-    PathVariant::Ref(ref_variant) => {
-      match ref_variant {
-        RefVariant::Head(name) => {
-          path.push("refs");
-          path.push("heads");
-          path.push(name);
-        },
-        RefVariant::Tag(name) => {
-          path.push("refs");
-          path.push("tags");
-          path.push(name);
-        },
-      };
+  Ok(Repository::find()?.generate_path(variant))
+}
+
+// The name of the repository directory, resolved from the UGIT_DIR environment variable each time so that it may be changed at
+// runtime. An unset or empty UGIT_DIR means the default of .ugit.
+pub fn git_dir() -> String {
+  match env::var("UGIT_DIR") {
+    Ok(name) if !name.is_empty() => name,
+    _ => String::from(DEFAULT_GIT_DIR)
+  }
+}
 
-      path
-    },
-    PathVariant::Refs => {
-      path.push("refs");
-      path
-    },
-    PathVariant::Root => path.parent().unwrap().to_path_buf(),
-    PathVariant::Tags => {
-      path.push("refs");
-      path.push("tags");
-      path
-    },
-    #[cfg(test)]
-    PathVariant::Ugit => path,
-  };
-
-  Ok(path)
+// Setting UGIT_NO_ANCESTOR_SEARCH to anything but an empty string limits the search for a repository to the current directory, for
+// scripts which expect to be run from the root of one.
+fn ancestor_search_enabled() -> bool {
+  match env::var("UGIT_NO_ANCESTOR_SEARCH") {
+    Ok(value) => value.is_empty(),
+    Err(_) => true
+  }
 }
 
 fn repository_initialized() -> bool {
@@ -380,23 +1403,36 @@ fn repository_initialized() -> bool {
   }
 }
 
+// The repository found from the current directory is remembered, so that the ancestors of the current directory are not walked
+// again on every call. The lookup is redone whenever the current directory or UGIT_DIR changes, or the remembered .ugit
+// disappears. Not finding a repository is never remembered, as one may be initialized at any time.
 fn get_repository() -> Option<PathBuf> {
   let cwd = env::current_dir().expect("Issue when getting cwd");
-  for path in cwd.ancestors() {
+  let git_dir = git_dir();
+  let search_ancestors = ancestor_search_enabled();
+  let cached = REPOSITORY.with(|repository| repository.borrow().clone());
+  if let Some((cached_cwd, path)) = cached {
+    if cached_cwd == cwd && path.ends_with(&git_dir) && path.exists() && (search_ancestors || path.parent() == Some(&cwd)) {
+      return Some(path);
+    }
+  }
+
+  for path in cwd.ancestors().take(if search_ancestors { usize::MAX } else { 1 }) {
     let mut path = path.clone().to_path_buf();
-    path.push(&GIT_DIR);
+    path.push(&git_dir);
     if path.exists() {
+      REPOSITORY.with(|repository| *repository.borrow_mut() = Some((cwd, path.clone())));
       return Some(path);
     }
   }
 
+  REPOSITORY.with(|repository| *repository.borrow_mut() = None);
   None
 }
 
 #[cfg(test)]
 mod tests {
 #![allow(non_snake_case)]
-  use std::panic;
   use std::path::Path;
   use serial_test::serial;
   use super::*;
@@ -413,6 +1449,59 @@ mod tests {
     delete_test_directory();
   }
 
+  #[test]
+  #[serial]
+  fn init_points_HEAD_at_a_main_branch_which_does_not_exist_yet() {
+    create_test_directory();
+    {
+      assert_eq!(fs::read_to_string(generate_path(PathVariant::Head).unwrap()).unwrap(), "ref:refs/heads/main");
+      assert!(!generate_path(PathVariant::Ref(RefVariant::Head("main"))).unwrap().exists());
+      assert!(get_head().is_none());
+
+      // Reinitializing recreates a missing HEAD from init.defaultBranch
+      fs::remove_file(generate_path(PathVariant::Head).unwrap()).unwrap();
+      set_config("init.defaultBranch", "trunk").unwrap();
+      reinit().unwrap();
+      assert_eq!(fs::read_to_string(generate_path(PathVariant::Head).unwrap()).unwrap(), "ref:refs/heads/trunk");
+      // An existing HEAD is left alone
+      set_config("init.defaultBranch", "other").unwrap();
+      reinit().unwrap();
+      assert_eq!(fs::read_to_string(generate_path(PathVariant::Head).unwrap()).unwrap(), "ref:refs/heads/trunk");
+    }
+    delete_test_directory();
+  }
+
+  #[test]
+  #[serial]
+  fn init_points_HEAD_at_the_branch_named_by_UGIT_DEFAULT_BRANCH() {
+    env::set_var("UGIT_DEFAULT_BRANCH", "trunk");
+    create_test_directory();
+    {
+      assert_eq!(fs::read_to_string(generate_path(PathVariant::Head).unwrap()).unwrap(), "ref:refs/heads/trunk");
+    }
+    delete_test_directory();
+
+    env::set_var("UGIT_DEFAULT_BRANCH", "../escape");
+    fs::create_dir("TEST").expect("Issue when creating test directory");
+    env::set_current_dir("TEST").expect("Issue when cding into test directory");
+    assert_eq!(init(ObjectFormat::Sha256).unwrap_err().kind(), ErrorKind::InvalidInput);
+    assert!(!Path::new(DEFAULT_GIT_DIR).exists());
+    env::remove_var("UGIT_DEFAULT_BRANCH");
+    delete_test_directory();
+  }
+
+  #[test]
+  fn oid_accepts_only_hex_of_an_object_format_length() {
+    let sha1 = "a".repeat(ObjectFormat::Sha1.oid_length());
+    let sha256 = "0123456789abcdef".repeat(4);
+    assert_eq!(sha1.parse::<Oid>().unwrap().to_string(), sha1);
+    assert_eq!(sha256.parse::<Oid>().unwrap().to_string(), sha256);
+    assert_eq!(sha256.to_uppercase().parse::<Oid>().unwrap().to_string(), sha256);
+    for invalid in &[String::new(), String::from("abc"), "g".repeat(40), format!("{}a", sha1)] {
+      assert_eq!(invalid.parse::<Oid>().unwrap_err().kind(), ErrorKind::InvalidData);
+    }
+  }
+
   #[test]
   #[serial]
   fn hash_object_subcommand_creates_copy_of_file_named_as_hash_of_same_file() {
@@ -423,24 +1512,336 @@ mod tests {
       let path_with_hash = generate_path(PathVariant::OID(test_text_as_hash)).unwrap();
       hash_object(test_text.as_bytes(), ObjectType::Blob).unwrap();
 
-      assert!(path_with_hash.is_file());
-      let contents = fs::read_to_string(path_with_hash).unwrap();
-      assert_eq!(contents, format!("blob\0{}", test_text));
+      assert!(path_with_hash.is_file());
+      let contents = decompress_object(test_text_as_hash, fs::read(&path_with_hash).unwrap()).unwrap();
+      assert_eq!(contents, format!("blob\0{}", test_text).into_bytes());
+    }
+    delete_test_directory();
+  }
+
+  #[test]
+  #[serial]
+  fn get_object_subcommand_returns_contents_of_file_with_specified_oid_hash() {
+    let test_text = "Excepturi velit rem modi. Ut non ipsa aut ad dignissimos et molestias placeat. Iste est perspiciatis ab et commodi.";
+    let test_text_as_hash = "bac94dbaf28c6916ef33cad50e4e1e88c3834f51dc7a5d40702a5cfdf324ab72";
+    create_test_directory();
+    {
+      hash_object(test_text.as_bytes(), ObjectType::Blob).unwrap();
+
+      let contents = get_object(&test_text_as_hash.parse().unwrap(), ObjectType::Blob).unwrap();
+      assert_eq!(contents, test_text.as_bytes());
+    }
+    delete_test_directory();
+  }
+
+  #[test]
+  #[serial]
+  fn get_oid_matches_hash_object_without_storing_the_object() {
+    let test_text = "Excepturi velit rem modi. Ut non ipsa aut ad dignissimos et molestias placeat. Iste est perspiciatis ab et commodi.";
+    let test_text_as_hash = "bac94dbaf28c6916ef33cad50e4e1e88c3834f51dc7a5d40702a5cfdf324ab72";
+    create_test_directory();
+    {
+      assert_eq!(Repository::find().unwrap().get_oid(test_text.as_bytes(), ObjectType::Blob), test_text_as_hash);
+      assert!(!generate_path(PathVariant::OID(test_text_as_hash)).unwrap().exists());
+    }
+    delete_test_directory();
+  }
+
+  #[test]
+  #[serial]
+  fn hash_object_stores_object_in_a_subdirectory_named_after_the_first_two_characters_of_its_oid() {
+    let test_text = "Excepturi velit rem modi. Ut non ipsa aut ad dignissimos et molestias placeat. Iste est perspiciatis ab et commodi.";
+    let test_text_as_hash = "bac94dbaf28c6916ef33cad50e4e1e88c3834f51dc7a5d40702a5cfdf324ab72";
+    create_test_directory();
+    {
+      hash_object(test_text.as_bytes(), ObjectType::Blob).unwrap();
+
+      let mut expected = generate_path(PathVariant::Objects).unwrap();
+      expected.push("ba");
+      expected.push("c94dbaf28c6916ef33cad50e4e1e88c3834f51dc7a5d40702a5cfdf324ab72");
+      assert!(expected.is_file());
+      assert_eq!(generate_path(PathVariant::OID(test_text_as_hash)).unwrap(), expected);
+    }
+    delete_test_directory();
+  }
+
+  #[test]
+  #[serial]
+  fn hash_object_compresses_the_stored_object() {
+    let test_bytes = vec![b'a'; 1024 * 1024];
+    create_test_directory();
+    {
+      let oid = hash_object(&test_bytes, ObjectType::Blob).unwrap();
+      let stored_size = fs::metadata(generate_path(PathVariant::OID(&oid)).unwrap()).unwrap().len();
+      assert!(stored_size < (test_bytes.len() / 100) as u64);
+
+      let contents = get_object(&oid, ObjectType::Blob).unwrap();
+      assert_eq!(contents, test_bytes);
+    }
+    delete_test_directory();
+  }
+
+  #[test]
+  #[serial]
+  fn get_object_reads_objects_stored_without_compression() {
+    let test_text = "Excepturi velit rem modi. Ut non ipsa aut ad dignissimos et molestias placeat. Iste est perspiciatis ab et commodi.";
+    let test_text_as_hash = "bac94dbaf28c6916ef33cad50e4e1e88c3834f51dc7a5d40702a5cfdf324ab72";
+    create_test_directory();
+    {
+      let path = generate_path(PathVariant::OID(test_text_as_hash)).unwrap();
+      fs::create_dir_all(path.parent().unwrap()).unwrap();
+      fs::write(&path, format!("blob\0{}", test_text)).unwrap();
+
+      let contents = get_object(&test_text_as_hash.parse().unwrap(), ObjectType::Blob).unwrap();
+      assert_eq!(contents, test_text.as_bytes());
+    }
+    delete_test_directory();
+  }
+
+  #[test]
+  #[serial]
+  fn get_object_returns_contents_of_a_blob_that_is_not_valid_utf8() {
+    let test_bytes = [0x89, b'P', b'N', b'G', 0x00, 0xff, 0xfe, 0x0a];
+    create_test_directory();
+    {
+      let oid = hash_object(&test_bytes, ObjectType::Blob).unwrap();
+
+      let contents = get_object(&oid, ObjectType::Blob).unwrap();
+      assert_eq!(contents, test_bytes);
+    }
+    delete_test_directory();
+  }
+
+  #[test]
+  #[serial]
+  fn get_object_returns_an_error_if_object_is_not_of_the_expected_type() {
+    let test_text = "Excepturi velit rem modi. Ut non ipsa aut ad dignissimos et molestias placeat. Iste est perspiciatis ab et commodi.";
+    create_test_directory();
+    {
+      let oid = hash_object(test_text.as_bytes(), ObjectType::Commit).unwrap();
+
+      let result = get_object(&oid, ObjectType::Blob);
+      assert_eq!(result.unwrap_err().kind(), ErrorKind::InvalidData);
+    }
+    delete_test_directory();
+  }
+
+  #[test]
+  #[serial]
+  fn get_object_type_reads_the_type_header() {
+    let test_text = "Excepturi velit rem modi. Ut non ipsa aut ad dignissimos et molestias placeat. Iste est perspiciatis ab et commodi.";
+    create_test_directory();
+    {
+      let blob_oid = hash_object(test_text.as_bytes(), ObjectType::Blob).unwrap();
+      let tree_oid = hash_object(test_text.as_bytes(), ObjectType::Tree).unwrap();
+      let commit_oid = hash_object(test_text.as_bytes(), ObjectType::Commit).unwrap();
+
+      assert_eq!(get_object_type(&blob_oid).unwrap(), ObjectType::Blob);
+      assert_eq!(get_object_type(&tree_oid).unwrap(), ObjectType::Tree);
+      assert_eq!(get_object_type(&commit_oid).unwrap(), ObjectType::Commit);
+      assert_eq!(get_object_type(&"a".repeat(ObjectFormat::Sha256.oid_length()).parse().unwrap()).unwrap_err().kind(), ErrorKind::NotFound);
+    }
+    delete_test_directory();
+  }
+
+  #[test]
+  #[serial]
+  fn hash_object_leaves_an_already_stored_object_alone() {
+    let test_text = "Excepturi velit rem modi. Ut non ipsa aut ad dignissimos et molestias placeat. Iste est perspiciatis ab et commodi.";
+    create_test_directory();
+    {
+      let repository = Repository::find().unwrap();
+      let (oid, status) = repository.hash_object_with_status(test_text.as_bytes(), ObjectType::Blob).unwrap();
+      assert_eq!(status, ObjectWrite::Written);
+
+      // Stored uncompressed, as objects were before compression, so that a rewrite would be noticed
+      let path = generate_path(PathVariant::OID(&oid)).unwrap();
+      fs::write(&path, format!("blob\0{}", test_text)).unwrap();
+      assert_eq!(repository.hash_object_with_status(test_text.as_bytes(), ObjectType::Blob).unwrap(), (oid.clone(), ObjectWrite::AlreadyStored));
+      assert_eq!(hash_object(test_text.as_bytes(), ObjectType::Blob).unwrap(), oid);
+      assert_eq!(fs::read(&path).unwrap(), format!("blob\0{}", test_text).into_bytes());
+    }
+    delete_test_directory();
+  }
+
+  #[test]
+  #[serial]
+  fn hash_object_of_a_stored_object_returns_the_same_oid() {
+    let test_text = "Excepturi velit rem modi. Ut non ipsa aut ad dignissimos et molestias placeat. Iste est perspiciatis ab et commodi.";
+    create_test_directory();
+    {
+      for object_type in &[ObjectType::Blob, ObjectType::Commit, ObjectType::Tag, ObjectType::Tree] {
+        let oid = hash_object(test_text.as_bytes(), *object_type).unwrap();
+        let contents = get_object(&oid, *object_type).unwrap();
+        assert_eq!(hash_object(&contents, *object_type).unwrap(), oid);
+        assert_eq!(parse_object_type(type_header(*object_type)).unwrap(), *object_type);
+      }
+
+      assert_eq!(parse_object_type("note").unwrap_err().kind(), ErrorKind::InvalidData);
+    }
+    delete_test_directory();
+  }
+
+  #[test]
+  #[serial]
+  fn object_info_returns_the_type_and_size_of_the_contents() {
+    let test_text = "Excepturi velit rem modi. Ut non ipsa aut ad dignissimos et molestias placeat. Iste est perspiciatis ab et commodi.";
+    create_test_directory();
+    {
+      let oid = hash_object(test_text.as_bytes(), ObjectType::Tree).unwrap();
+      assert_eq!(object_info(&oid).unwrap(), (ObjectType::Tree, test_text.len()));
+      let empty_oid = hash_object(b"", ObjectType::Blob).unwrap();
+      assert_eq!(object_info(&empty_oid).unwrap(), (ObjectType::Blob, 0));
+      assert_eq!(object_info(&"a".repeat(ObjectFormat::Sha256.oid_length()).parse().unwrap()).unwrap_err().kind(), ErrorKind::NotFound);
+    }
+    delete_test_directory();
+  }
+
+  #[test]
+  #[serial]
+  fn verify_object_detects_contents_which_do_not_match_the_oid() {
+    create_test_directory();
+    {
+      let repository = Repository::find().unwrap();
+      let oid = hash_object(b"Excepturi velit rem modi.", ObjectType::Blob).unwrap();
+      let other_oid = hash_object(b"Ut non ipsa aut ad dignissimos.", ObjectType::Commit).unwrap();
+      let mut expected = vec![oid.clone(), other_oid.clone()];
+      expected.sort();
+      assert_eq!(repository.list_objects().unwrap(), expected);
+      assert_eq!(repository.verify_object(&oid).unwrap(), ObjectType::Blob);
+      assert_eq!(repository.verify_object(&other_oid).unwrap(), ObjectType::Commit);
+
+      fs::copy(generate_path(PathVariant::OID(&other_oid)).unwrap(), generate_path(PathVariant::OID(&oid)).unwrap()).unwrap();
+      assert_eq!(repository.verify_object(&oid).unwrap_err().kind(), ErrorKind::InvalidData);
+      fs::write(generate_path(PathVariant::OID(&other_oid)).unwrap(), [ZLIB_MAGIC, 0]).unwrap();
+      assert_eq!(repository.verify_object(&other_oid).unwrap_err().kind(), ErrorKind::InvalidData);
+    }
+    delete_test_directory();
+  }
+
+  #[test]
+  #[serial]
+  fn get_object_rejects_contents_which_do_not_match_the_oid_unless_told_not_to() {
+    create_test_directory();
+    {
+      let repository = Repository::find().unwrap();
+      let oid = hash_object(b"Excepturi velit rem modi.", ObjectType::Blob).unwrap();
+      let other_oid = hash_object(b"Ut non ipsa aut ad dignissimos.", ObjectType::Blob).unwrap();
+      fs::copy(generate_path(PathVariant::OID(&other_oid)).unwrap(), generate_path(PathVariant::OID(&oid)).unwrap()).unwrap();
+
+      assert_eq!(get_object(&oid, ObjectType::Blob).unwrap_err().kind(), ErrorKind::InvalidData);
+      assert_eq!(repository.get_object_unchecked(&oid, ObjectType::Blob).unwrap(), b"Ut non ipsa aut ad dignissimos.");
+      set_config("core.verifyObjects", "false").unwrap();
+      assert_eq!(get_object(&oid, ObjectType::Blob).unwrap(), b"Ut non ipsa aut ad dignissimos.");
+    }
+    delete_test_directory();
+  }
+
+  #[test]
+  fn object_database_round_trips_objects_through_any_store() {
+    let objects = ObjectDatabase::new(MemoryObjectStore::default(), ObjectFormat::Sha1);
+    let (oid, write) = objects.hash_object_with_status(b"Excepturi velit rem modi.", ObjectType::Blob).unwrap();
+    assert_eq!(write, ObjectWrite::Written);
+    assert_eq!(oid, objects.get_oid(b"Excepturi velit rem modi.", ObjectType::Blob));
+    assert_eq!(objects.hash_object_with_status(b"Excepturi velit rem modi.", ObjectType::Blob).unwrap().1, ObjectWrite::AlreadyStored);
+
+    assert_eq!(objects.get_object(&oid, ObjectType::Blob, true).unwrap(), b"Excepturi velit rem modi.");
+    assert_eq!(objects.object_info(&oid).unwrap(), (ObjectType::Blob, 25));
+    assert_eq!(objects.resolve_oid_prefix(&oid[..6]).unwrap(), oid);
+    let mut contents = Vec::new();
+    objects.open_object(&oid, ObjectType::Blob, true).unwrap().read_to_end(&mut contents).unwrap();
+    assert_eq!(contents, b"Excepturi velit rem modi.");
+
+    // The store is given compressed bytes, which are checked against the OID on their way back out
+    let other_oid = objects.hash_object_with_status(b"Ut non ipsa aut ad dignissimos.", ObjectType::Blob).unwrap().0;
+    objects.store.write(&oid, &objects.store.read(&other_oid).unwrap()).unwrap();
+    assert_eq!(objects.verify_object(&oid).unwrap_err().kind(), ErrorKind::InvalidData);
+    assert_eq!(objects.get_object(&oid, ObjectType::Blob, false).unwrap(), b"Ut non ipsa aut ad dignissimos.");
+
+    objects.delete_object(&oid).unwrap();
+    assert!(!objects.contains(&oid));
+    assert_eq!(objects.list_objects().unwrap(), vec![other_oid]);
+  }
+
+  #[test]
+  #[serial]
+  fn open_object_streams_a_large_blob() {
+    let chunk: Vec<u8> = (0..=255).collect();
+    let chunk_count = 16 * 1024;
+    create_test_directory();
+    {
+      let oid = hash_object(&chunk.repeat(chunk_count), ObjectType::Blob).unwrap();
+
+      // Read back one chunk-sized piece at a time, never holding more than that of the blob
+      let mut reader = open_object(&oid, ObjectType::Blob).unwrap();
+      let mut buffer = vec![0; chunk.len()];
+      for _ in 0..chunk_count {
+        reader.read_exact(&mut buffer).unwrap();
+        assert_eq!(buffer, chunk);
+      }
+      assert_eq!(reader.read(&mut buffer).unwrap(), 0);
+
+      assert_eq!(open_object(&oid, ObjectType::Tree).err().map(|err| err.kind()), Some(ErrorKind::InvalidData));
+      assert_eq!(open_object(&"c".repeat(ObjectFormat::Sha256.oid_length()).parse().unwrap(), ObjectType::Blob).err().map(|err| err.kind()), Some(ErrorKind::NotFound));
+    }
+    delete_test_directory();
+  }
+
+  #[test]
+  #[serial]
+  fn open_object_rejects_contents_which_do_not_match_the_oid_once_read_to_the_end() {
+    create_test_directory();
+    {
+      let oid = hash_object(b"Excepturi velit rem modi.", ObjectType::Blob).unwrap();
+      let other_oid = hash_object(b"Ut non ipsa aut ad dignissimos.", ObjectType::Blob).unwrap();
+      fs::copy(generate_path(PathVariant::OID(&other_oid)).unwrap(), generate_path(PathVariant::OID(&oid)).unwrap()).unwrap();
+
+      let mut contents = Vec::new();
+      let result = open_object(&oid, ObjectType::Blob).unwrap().read_to_end(&mut contents);
+      assert_eq!(result.unwrap_err().kind(), ErrorKind::InvalidData);
+
+      // Objects stored before compression are read as they are
+      fs::write(generate_path(PathVariant::OID(&oid)).unwrap(), "blob\0Excepturi velit rem modi.").unwrap();
+      contents.clear();
+      open_object(&oid, ObjectType::Blob).unwrap().read_to_end(&mut contents).unwrap();
+      assert_eq!(contents, b"Excepturi velit rem modi.");
+
+      set_config("core.verifyObjects", "false").unwrap();
+      fs::copy(generate_path(PathVariant::OID(&other_oid)).unwrap(), generate_path(PathVariant::OID(&oid)).unwrap()).unwrap();
+      contents.clear();
+      open_object(&oid, ObjectType::Blob).unwrap().read_to_end(&mut contents).unwrap();
+      assert_eq!(contents, b"Ut non ipsa aut ad dignissimos.");
+    }
+    delete_test_directory();
+  }
+
+  #[test]
+  #[serial]
+  fn get_object_type_returns_an_error_for_an_unknown_type() {
+    create_test_directory();
+    {
+      let oid: Oid = "b".repeat(ObjectFormat::Sha256.oid_length()).parse().unwrap();
+      let path = generate_path(PathVariant::OID(&oid)).unwrap();
+      fs::create_dir_all(path.parent().unwrap()).unwrap();
+      fs::write(&path, "note\0Excepturi velit rem modi.").unwrap();
+
+      assert_eq!(get_object_type(&oid).unwrap_err().kind(), ErrorKind::InvalidData);
+      assert_eq!(get_object(&oid, ObjectType::Blob).unwrap_err().kind(), ErrorKind::InvalidData);
     }
     delete_test_directory();
   }
 
   #[test]
   #[serial]
-  fn get_object_subcommand_returns_contents_of_file_with_specified_oid_hash() {
-    let test_text = "Excepturi velit rem modi. Ut non ipsa aut ad dignissimos et molestias placeat. Iste est perspiciatis ab et commodi.";
-    let test_text_as_hash = "bac94dbaf28c6916ef33cad50e4e1e88c3834f51dc7a5d40702a5cfdf324ab72";
+  fn get_object_validates_tag_objects() {
+    let test_text = "object abc\ntype commit\ntag v1\n\nRelease";
     create_test_directory();
     {
-      hash_object(test_text.as_bytes(), ObjectType::Blob).unwrap();
+      let oid = hash_object(test_text.as_bytes(), ObjectType::Tag).unwrap();
 
-      let contents = get_object(test_text_as_hash, ObjectType::Blob).unwrap();
-      assert_eq!(contents, test_text);
+      assert_eq!(get_object(&oid, ObjectType::Tag).unwrap(), test_text.as_bytes());
+      assert_eq!(get_object(&oid, ObjectType::Commit).unwrap_err().kind(), ErrorKind::InvalidData);
+      assert!(Repository::find().unwrap().validate_user_given_ref(&oid));
     }
     delete_test_directory();
   }
@@ -453,7 +1854,7 @@ mod tests {
     {
       let commit_oid = hash_object(test_text.as_bytes(), ObjectType::Commit).expect("Issue when hashing a commit");
       let path = generate_path(PathVariant::Ref(RefVariant::Tag("Test tag"))).unwrap();
-      let ref_value = RefValue { symbolic: false, value: Some(commit_oid.clone()), path: path.clone() };
+      let ref_value = RefValue { value: Some(RefTarget::Oid(commit_oid.clone())), path: path.clone() };
       update_ref(&ref_value, true).expect("Issue when updating ref");
 
       let contents = fs::read_to_string(path).unwrap();
@@ -464,19 +1865,48 @@ mod tests {
 
   #[test]
   #[serial]
-  fn update_ref_creates_a_symbolic_ref_to_a_commit() {
+  fn update_ref_creates_a_symbolic_ref_to_a_branch() {
     let test_text = "Excepturi velit rem modi. Ut non ipsa aut ad dignissimos et molestias placeat. Iste est perspiciatis ab et commodi.";
     create_test_directory();
     {
       let commit_oid = hash_object(test_text.as_bytes(), ObjectType::Commit).expect("Issue when hashing a commit");
-      let path = generate_path(PathVariant::Ref(RefVariant::Head("Test branch"))).unwrap();
-      let ref_value = RefValue { symbolic: true, value: Some(commit_oid.clone()), path: path.clone() };
+      let branch_path = generate_path(PathVariant::Ref(RefVariant::Head("Test branch"))).unwrap();
+      let ref_value = RefValue { value: Some(RefTarget::Oid(commit_oid.clone())), path: branch_path.clone() };
       update_ref(&ref_value, true).expect("Issue when updating ref");
 
-      let contents = fs::read_to_string(path).unwrap();
+      let path = generate_path(PathVariant::Head).unwrap();
+      let ref_value = RefValue { value: Some(RefTarget::Symbolic(String::from("refs/heads/Test branch"))), path: path.clone() };
+      update_ref(&ref_value, false).expect("Issue when updating ref");
+
+      let contents = fs::read_to_string(&path).unwrap();
       let content_parts: Vec<_> = contents.splitn(2, ":").collect();
       assert_eq!(content_parts[0], "ref");
-      assert_eq!(content_parts[1], commit_oid);
+      assert_eq!(content_parts[1], "refs/heads/Test branch");
+
+      let expected = RefValue { value: Some(RefTarget::Symbolic(String::from("refs/heads/Test branch"))), path: path.clone() };
+      assert_eq!(get_ref(&path, false).unwrap(), expected);
+      let expected = RefValue { value: Some(RefTarget::Oid(commit_oid)), path: branch_path };
+      assert_eq!(get_ref(&path, true).unwrap(), expected);
+    }
+    delete_test_directory();
+  }
+
+  #[test]
+  #[serial]
+  fn update_ref_updates_the_ref_a_symbolic_ref_points_to_when_dereferencing() {
+    let test_text = "Excepturi velit rem modi. Ut non ipsa aut ad dignissimos et molestias placeat. Iste est perspiciatis ab et commodi.";
+    create_test_directory();
+    {
+      let commit_oid = hash_object(test_text.as_bytes(), ObjectType::Commit).expect("Issue when hashing a commit");
+      set_head(RefTarget::Symbolic(String::from("refs/heads/Test branch")), "checkout: moving to Test branch").expect("Issue when setting HEAD");
+
+      let path = generate_path(PathVariant::Head).unwrap();
+      let ref_value = RefValue { value: Some(RefTarget::Oid(commit_oid.clone())), path: path.clone() };
+      update_ref(&ref_value, true).expect("Issue when updating ref");
+
+      let branch_path = generate_path(PathVariant::Ref(RefVariant::Head("Test branch"))).unwrap();
+      assert_eq!(fs::read_to_string(branch_path).unwrap(), commit_oid);
+      assert_eq!(fs::read_to_string(path).unwrap(), "ref:refs/heads/Test branch");
     }
     delete_test_directory();
   }
@@ -492,14 +1922,14 @@ mod tests {
       // Create first ref
       let first_ref_oid = {
         let path = generate_path(PathVariant::Ref(RefVariant::Head(ref_name))).unwrap();
-        let ref_value = RefValue { symbolic: false, value: Some(commit_oid.clone()), path: path.clone() };
+        let ref_value = RefValue { value: Some(RefTarget::Oid(commit_oid.clone())), path: path.clone() };
         update_ref(&ref_value, true).expect("Issue when updating ref");
-        fs::read_to_string(path).unwrap()
+        fs::read_to_string(path).unwrap().parse::<Oid>().unwrap()
       };
 
       let path = generate_path(PathVariant::Ref(RefVariant::Tag("Tag to ref"))).unwrap();
       // Currently, cannot pass ref directly to update_ref: when using ugit, the CLI converts from ref down to the bare commit.
-      let ref_value = RefValue { symbolic: false, value: Some(first_ref_oid), path: path.clone() };
+      let ref_value = RefValue { value: Some(RefTarget::Oid(first_ref_oid)), path: path.clone() };
       update_ref(&ref_value, true).expect("Issue when updating ref");
 
       let contents = fs::read_to_string(path).unwrap();
@@ -510,44 +1940,120 @@ mod tests {
 
   #[test]
   #[serial]
-  #[should_panic(expected="empty ref")]
-  fn update_ref_panics_if_tried_to_create_ref_to_nothing() {
-    let result;
+  fn get_ref_returns_an_error_for_a_cycle_of_symbolic_refs() {
+    create_test_directory();
+    {
+      let a = generate_path(PathVariant::Ref(RefVariant::Head("A"))).unwrap();
+      let b = generate_path(PathVariant::Ref(RefVariant::Head("B"))).unwrap();
+      fs::write(&a, "ref:refs/heads/B").unwrap();
+      fs::write(&b, "ref:refs/heads/A").unwrap();
+
+      assert_eq!(get_ref(&a, true).unwrap_err().kind(), ErrorKind::InvalidData);
+      // Without dereferencing, the symbolic ref is read as it is
+      assert_eq!(get_ref(&a, false).unwrap().symbolic_target(), Some("refs/heads/B"));
+    }
+    delete_test_directory();
+  }
+
+  #[test]
+  #[serial]
+  fn get_ref_dereferences_a_symbolic_ref_from_a_nested_directory() {
+    let test_text = "Excepturi velit rem modi. Ut non ipsa aut ad dignissimos et molestias placeat. Iste est perspiciatis ab et commodi.";
+    create_test_directory();
+    {
+      let commit_oid = hash_object(test_text.as_bytes(), ObjectType::Commit).expect("Issue when hashing a commit");
+      let branch_path = generate_path(PathVariant::Ref(RefVariant::Head("Test branch"))).unwrap();
+      fs::write(&branch_path, commit_oid.as_bytes()).unwrap();
+      set_head(RefTarget::Symbolic(String::from("refs/heads/Test branch")), "checkout: moving to Test branch").expect("Issue when setting HEAD");
+
+      fs::create_dir_all("Nested/Deeper").unwrap();
+      env::set_current_dir("Nested/Deeper").unwrap();
+      let ref_value = get_ref(&generate_path(PathVariant::Head).unwrap(), true);
+      env::set_current_dir("../..").unwrap();
+
+      let ref_value = ref_value.expect("Issue when dereferencing HEAD");
+      assert_eq!(ref_value.oid(), Some(&commit_oid));
+      assert_eq!(ref_value.path, branch_path);
+    }
+    delete_test_directory();
+  }
+
+  #[test]
+  #[serial]
+  fn get_ref_ignores_trailing_whitespace_in_ref_files() {
+    let test_text = "Excepturi velit rem modi. Ut non ipsa aut ad dignissimos et molestias placeat. Iste est perspiciatis ab et commodi.";
     create_test_directory();
     {
-      let ref_value = RefValue { symbolic: false, value: None, path: PathBuf::from("New Ref") };
-      result = panic::catch_unwind(|| update_ref(&ref_value, true).unwrap());
+      let commit_oid = hash_object(test_text.as_bytes(), ObjectType::Commit).expect("Issue when hashing a commit");
+      let branch_path = generate_path(PathVariant::Ref(RefVariant::Head("Test branch"))).unwrap();
+      fs::write(&branch_path, format!("{}\n", commit_oid)).unwrap();
+      let head_path = generate_path(PathVariant::Head).unwrap();
+      fs::write(&head_path, "ref:refs/heads/Test branch\n").unwrap();
+
+      assert_eq!(get_ref(&branch_path, true).unwrap().oid(), Some(&commit_oid));
+      assert_eq!(get_ref(&head_path, false).unwrap().symbolic_target(), Some("refs/heads/Test branch"));
+      let ref_value = get_ref(&head_path, true).unwrap();
+      assert_eq!(ref_value.oid(), Some(&commit_oid));
+      assert_eq!(ref_value.path, branch_path);
+      assert_eq!(locate_ref_or_oid("Test branch", false).unwrap().unwrap(), commit_oid);
+
+      // As git writes a symbolic ref
+      fs::write(&head_path, "ref: refs/heads/Test branch\n").unwrap();
+      assert_eq!(get_ref(&head_path, false).unwrap().symbolic_target(), Some("refs/heads/Test branch"));
+      let ref_value = get_ref(&head_path, true).unwrap();
+      assert_eq!(ref_value.oid(), Some(&commit_oid));
+      assert_eq!(ref_value.path, branch_path);
     }
     delete_test_directory();
+  }
 
-    if let Err(err) = result {
-      panic::resume_unwind(err);
+  #[test]
+  #[serial]
+  fn update_ref_returns_an_error_if_tried_to_create_ref_to_nothing() {
+    create_test_directory();
+    {
+      let ref_value = RefValue { value: None, path: PathBuf::from("New Ref") };
+      let err = update_ref(&ref_value, true).unwrap_err();
+      assert_eq!(err.kind(), ErrorKind::InvalidInput);
+      assert!(err.to_string().contains("empty ref"));
     }
+    delete_test_directory();
   }
 
   #[test]
   #[serial]
-  #[should_panic(expected="not a commit or another ref")]
-  fn update_ref_panics_if_tried_to_create_ref_of_not_a_commit_or_another_ref() {
+  fn update_ref_returns_an_error_if_tried_to_create_ref_of_not_a_commit_or_another_ref() {
     let test_text = "Excepturi velit rem modi. Ut non ipsa aut ad dignissimos et molestias placeat. Iste est perspiciatis ab et commodi.";
-    let result;
     create_test_directory();
     {
       let oid = hash_object(&test_text.as_bytes(), ObjectType::Blob).unwrap();
-      let ref_value = RefValue { symbolic: false, value: Some(oid), path: PathBuf::from("New Ref") };
-      result = panic::catch_unwind(|| update_ref(&ref_value, true).unwrap());
+      let ref_value = RefValue { value: Some(RefTarget::Oid(oid)), path: PathBuf::from("New Ref") };
+      let err = update_ref(&ref_value, true).unwrap_err();
+      assert_eq!(err.kind(), ErrorKind::InvalidInput);
+      assert!(err.to_string().contains("not a commit or another ref"));
+      assert!(!Path::new("New Ref").exists());
     }
     delete_test_directory();
+  }
 
-    if let Err(err) = result {
-      panic::resume_unwind(err);
+  #[test]
+  #[serial]
+  fn get_ref_refuses_a_malformed_oid() {
+    create_test_directory();
+    {
+      let path = generate_path(PathVariant::Ref(RefVariant::Head("main"))).unwrap();
+      fs::write(&path, "abc").unwrap();
+      let err = get_ref(&path, true).unwrap_err();
+      assert_eq!(err.kind(), ErrorKind::InvalidData);
+      assert!(err.to_string().contains("neither an OID nor another ref"));
     }
+    delete_test_directory();
   }
 
   #[test]
   #[serial]
   fn update_ref_returns_an_error_if_repository_is_not_initialized() {
-    let ref_value = RefValue { symbolic: false, value: None, path: PathBuf::from("") };
+    let ref_value = RefValue { value: None, path: PathBuf::from("") };
     assert!(update_ref(&ref_value, true).is_err());
   }
 
@@ -557,7 +2063,7 @@ mod tests {
     create_test_directory();
     {
       let path = Path::new("Doesn't exist");
-      let expected = RefValue { symbolic: false, value: None, path: path.clone().to_path_buf() };
+      let expected = RefValue { value: None, path: path.clone().to_path_buf() };
       let result = get_ref(&path, true).expect("Issue when getting ref");
       assert_eq!(result, expected);
     }
@@ -572,10 +2078,10 @@ mod tests {
     {
       let commit_oid = hash_object(test_text.as_bytes(), ObjectType::Commit).expect("Issue when hashing a commit");
       let path = generate_path(PathVariant::Ref(RefVariant::Head("Test branch"))).unwrap();
-      let ref_value = RefValue { symbolic: false, value: Some(commit_oid.clone()), path: path.clone() };
+      let ref_value = RefValue { value: Some(RefTarget::Oid(commit_oid.clone())), path: path.clone() };
       update_ref(&ref_value, true).expect("Issue when updating ref");
 
-      let expected = RefValue { symbolic: false, value: Some(commit_oid), path: path.clone().to_path_buf() };
+      let expected = RefValue { value: Some(RefTarget::Oid(commit_oid)), path: path.clone().to_path_buf() };
       let result = get_ref(&path, true).expect("Issue when getting ref");
       assert_eq!(result, expected);
     }
@@ -597,6 +2103,32 @@ mod tests {
     delete_test_directory();
   }
 
+  #[test]
+  #[serial]
+  fn get_ref_reads_paths_as_given_while_get_ref_by_name_reads_from_the_repository() {
+    let test_text = "Excepturi velit rem modi. Ut non ipsa aut ad dignissimos et molestias placeat. Iste est perspiciatis ab et commodi.";
+    create_test_directory();
+    {
+      let commit_oid = hash_object(test_text.as_bytes(), ObjectType::Commit).expect("Issue when hashing a commit");
+      let path = generate_path(PathVariant::Ref(RefVariant::Head("Test branch"))).unwrap();
+      fs::write(&path, commit_oid.as_bytes()).unwrap();
+
+      fs::create_dir("Nested").unwrap();
+      env::set_current_dir("Nested").unwrap();
+      let by_name = get_ref_by_name(RefVariant::Head("Test branch"), true);
+      let by_relative_path = get_ref(Path::new(".ugit/refs/heads/Test branch"), true);
+      env::set_current_dir("..").unwrap();
+
+      let by_name = by_name.expect("Issue when getting ref");
+      assert_eq!(by_name.oid(), Some(&commit_oid));
+      assert_eq!(by_name.path, path);
+      // Relative to Nested, where there is no such file
+      assert!(by_relative_path.expect("Issue when getting ref").value.is_none());
+      assert!(get_ref_by_name(RefVariant::Tag("Test branch"), true).unwrap().value.is_none());
+    }
+    delete_test_directory();
+  }
+
   #[test]
   #[serial]
   fn get_ref_returns_an_error_if_repository_is_not_initialized() {
@@ -611,7 +2143,7 @@ mod tests {
     create_test_directory();
     {
       let commit_oid = hash_object(test_text.as_bytes(), ObjectType::Commit).expect("Issue when hashing a commit");
-      set_head(&commit_oid).expect("Issue when updating ref");
+      set_head(RefTarget::Oid(commit_oid.clone()), "checkout").expect("Issue when updating ref");
 
       let path = generate_path(PathVariant::Head).unwrap();
       let contents = fs::read_to_string(path).unwrap();
@@ -623,7 +2155,7 @@ mod tests {
   #[test]
   #[serial]
   fn set_head_returns_an_error_if_repository_is_not_initialized() {
-    assert!(set_head("").is_err());
+    assert!(set_head(RefTarget::Symbolic(String::from("refs/heads/main")), "checkout").is_err());
   }
 
   #[test]
@@ -636,13 +2168,13 @@ mod tests {
       // Create first ref
       let ref_oid = {
         let path = generate_path(PathVariant::Ref(RefVariant::Head("Test Branch"))).unwrap();
-        let ref_value = RefValue { symbolic: false, value: Some(commit_oid.clone()), path: path.clone() };
+        let ref_value = RefValue { value: Some(RefTarget::Oid(commit_oid.clone())), path: path.clone() };
         update_ref(&ref_value, true).expect("Issue when updating ref");
-        fs::read_to_string(path).unwrap()
+        fs::read_to_string(path).unwrap().parse::<Oid>().unwrap()
       };
 
       // Currently, cannot pass ref directly to set_head: when using ugit, the CLI converts from ref down to the bare commit.
-      set_head(&ref_oid).expect("Issue when updating ref");
+      set_head(RefTarget::Oid(ref_oid.clone()), "checkout").expect("Issue when updating ref");
 
       let path = generate_path(PathVariant::Head).unwrap();
       let contents = fs::read_to_string(path).unwrap();
@@ -653,20 +2185,16 @@ mod tests {
 
   #[test]
   #[serial]
-  #[should_panic(expected="commit or another ref")]
-  fn set_head_panics_if_set_to_not_a_commit_or_another_ref() {
+  fn set_head_returns_an_error_if_set_to_not_a_commit_or_another_ref() {
     let test_text = "Excepturi velit rem modi. Ut non ipsa aut ad dignissimos et molestias placeat. Iste est perspiciatis ab et commodi.";
-    let result;
     create_test_directory();
     {
       let oid = hash_object(&test_text.as_bytes(), ObjectType::Blob).unwrap();
-      result = panic::catch_unwind(|| set_head(&oid).unwrap());
+      let err = set_head(RefTarget::Oid(oid.clone()), "checkout").unwrap_err();
+      assert_eq!(err.kind(), ErrorKind::InvalidInput);
+      assert!(err.to_string().contains("commit or another ref"));
     }
     delete_test_directory();
-
-    if let Err(err) = result {
-      panic::resume_unwind(err);
-    }
   }
 
   #[test]
@@ -691,11 +2219,31 @@ mod tests {
     let test_text = "Excepturi velit rem modi. Ut non ipsa aut ad dignissimos et molestias placeat. Iste est perspiciatis ab et commodi.";
     create_test_directory();
     {
+      let oid = hash_object(test_text.as_bytes(), ObjectType::Commit).unwrap();
       let path = Path::new(".ugit/HEAD");
-      fs::write(&path, test_text).unwrap();
+      fs::write(&path, oid.to_string()).unwrap();
 
       let result = get_head().unwrap().unwrap();
-      assert_eq!(result, test_text);
+      assert_eq!(result, oid);
+    }
+    delete_test_directory();
+  }
+
+  #[test]
+  #[serial]
+  fn get_head_returns_oid_of_the_branch_a_symbolic_HEAD_points_to() {
+    let test_text = "Excepturi velit rem modi. Ut non ipsa aut ad dignissimos et molestias placeat. Iste est perspiciatis ab et commodi.";
+    create_test_directory();
+    {
+      set_head(RefTarget::Symbolic(String::from("refs/heads/Test branch")), "checkout: moving to Test branch").expect("Issue when setting HEAD");
+      assert!(get_head().is_none());
+
+      let commit_oid = hash_object(test_text.as_bytes(), ObjectType::Commit).expect("Issue when hashing a commit");
+      let path = generate_path(PathVariant::Ref(RefVariant::Head("Test branch"))).unwrap();
+      let ref_value = RefValue { value: Some(RefTarget::Oid(commit_oid.clone())), path };
+      update_ref(&ref_value, true).expect("Issue when updating ref");
+
+      assert_eq!(get_head().unwrap().unwrap(), commit_oid);
     }
     delete_test_directory();
   }
@@ -711,7 +2259,7 @@ mod tests {
   fn validate_user_given_ref_returns_false_if_given_oid_does_not_exist() {
     create_test_directory();
     {
-      let result = validate_user_given_ref("Nothin'");
+      let result = Repository::find().unwrap().validate_user_given_ref("Nothin'");
       assert_eq!(result, false);
     }
     delete_test_directory();
@@ -724,7 +2272,7 @@ mod tests {
     create_test_directory();
     {
       let oid = hash_object(&test_text.as_bytes(), ObjectType::Blob).unwrap();
-      let result = validate_user_given_ref(&oid);
+      let result = Repository::find().unwrap().validate_user_given_ref(&oid);
       assert_eq!(result, false);
     }
     delete_test_directory();
@@ -737,7 +2285,7 @@ mod tests {
     create_test_directory();
     {
       let oid = hash_object(&test_text.as_bytes(), ObjectType::Commit).unwrap();
-      let result = validate_user_given_ref(&oid);
+      let result = Repository::find().unwrap().validate_user_given_ref(&oid);
       assert_eq!(result, true);
     }
     delete_test_directory();
@@ -753,18 +2301,45 @@ mod tests {
       let ref_oid = {
         let commit_oid = hash_object(test_text.as_bytes(), ObjectType::Commit).expect("Issue when hashing a commit");
         let path = generate_path(PathVariant::Ref(RefVariant::Tag(tag_name))).unwrap();
-        let ref_value = RefValue { symbolic: false, value: Some(commit_oid.clone()), path: path.clone() };
+        let ref_value = RefValue { value: Some(RefTarget::Oid(commit_oid.clone())), path: path.clone() };
         update_ref(&ref_value, true).expect("Issue when updating ref");
-        fs::read_to_string(path).unwrap()
+        fs::read_to_string(path).unwrap().parse::<Oid>().unwrap()
       };
 
-      let result = locate_ref_or_oid(tag_name).unwrap().unwrap();
+      let result = locate_ref_or_oid(tag_name, true).unwrap().unwrap();
       assert_eq!(result, ref_oid);
     }
     delete_test_directory();
   }
 
 
+  #[test]
+  #[serial]
+  fn locate_ref_or_oid_peels_an_annotated_tag_down_to_its_commit() {
+    let test_text = "Excepturi velit rem modi. Ut non ipsa aut ad dignissimos et molestias placeat. Iste est perspiciatis ab et commodi.";
+    create_test_directory();
+    {
+      let commit_oid = hash_object(test_text.as_bytes(), ObjectType::Commit).unwrap();
+      let tag = format!("object {}\ntype commit\ntag v1\ntagger Jane Doe <jane@example.com> 0 +0000\n\nobject in message", commit_oid);
+      let tag_oid = hash_object(tag.as_bytes(), ObjectType::Tag).unwrap();
+      // A tag of a tag is peeled all the way down
+      let outer_tag = format!("object {}\ntype tag\ntag v1-outer\n\n", tag_oid);
+      let outer_tag_oid = hash_object(outer_tag.as_bytes(), ObjectType::Tag).unwrap();
+      let path = generate_path(PathVariant::Ref(RefVariant::Tag("v1"))).unwrap();
+      update_ref(&RefValue { value: Some(RefTarget::Oid(outer_tag_oid.clone())), path }, true).unwrap();
+
+      assert_eq!(locate_ref_or_oid("v1", true).unwrap().unwrap(), commit_oid);
+      assert_eq!(locate_ref_or_oid("v1", false).unwrap().unwrap(), outer_tag_oid);
+      assert_eq!(locate_ref_or_oid(&tag_oid, true).unwrap().unwrap(), commit_oid);
+      assert_eq!(locate_ref_or_oid(&commit_oid, true).unwrap().unwrap(), commit_oid);
+
+      let path = generate_path(PathVariant::Ref(RefVariant::Head("v1"))).unwrap();
+      update_ref(&RefValue { value: Some(RefTarget::Oid(commit_oid.clone())), path }, true).unwrap();
+      assert_eq!(locate_ref_or_oid("v1", true).unwrap().unwrap_err().kind(), ErrorKind::InvalidInput);
+    }
+    delete_test_directory();
+  }
+
   #[test]
   #[serial]
   fn locate_ref_or_oid_returns_commit_oid_that_branch_points_to_given_only_name() {
@@ -775,12 +2350,12 @@ mod tests {
       let ref_oid = {
         let commit_oid = hash_object(test_text.as_bytes(), ObjectType::Commit).expect("Issue when hashing a commit");
         let path = generate_path(PathVariant::Ref(RefVariant::Head(head_name))).unwrap();
-        let ref_value = RefValue { symbolic: false, value: Some(commit_oid.clone()), path: path.clone() };
+        let ref_value = RefValue { value: Some(RefTarget::Oid(commit_oid.clone())), path: path.clone() };
         update_ref(&ref_value, true).expect("Issue when updating ref");
-        fs::read_to_string(path).unwrap()
+        fs::read_to_string(path).unwrap().parse::<Oid>().unwrap()
       };
 
-      let result = locate_ref_or_oid(head_name).unwrap().unwrap();
+      let result = locate_ref_or_oid(head_name, true).unwrap().unwrap();
       assert_eq!(result, ref_oid);
     }
     delete_test_directory();
@@ -788,13 +2363,30 @@ mod tests {
 
   #[test]
   #[serial]
-  fn locate_ref_or_oid_returns_contents_of_given_oid() {
+  fn locate_ref_or_oid_returns_given_oid_when_object_exists() {
+    let test_text = "Excepturi velit rem modi. Ut non ipsa aut ad dignissimos et molestias placeat. Iste est perspiciatis ab et commodi.";
+    create_test_directory();
+    {
+      let commit_oid = hash_object(test_text.as_bytes(), ObjectType::Commit).expect("Issue when hashing a commit");
+      let result = locate_ref_or_oid(&commit_oid, true).unwrap().unwrap();
+      assert_eq!(result, commit_oid);
+    }
+    delete_test_directory();
+  }
+
+  #[test]
+  #[serial]
+  fn locate_ref_or_oid_returns_the_lowercase_oid_given_one_in_uppercase() {
     let test_text = "Excepturi velit rem modi. Ut non ipsa aut ad dignissimos et molestias placeat. Iste est perspiciatis ab et commodi.";
     create_test_directory();
     {
       let commit_oid = hash_object(test_text.as_bytes(), ObjectType::Commit).expect("Issue when hashing a commit");
-      let result = locate_ref_or_oid(&commit_oid).unwrap().unwrap();
-      assert!(result.contains(test_text));
+      let result = locate_ref_or_oid(&commit_oid.to_uppercase(), true).unwrap().unwrap();
+      assert_eq!(result, commit_oid);
+      // The object is still only stored under its lowercase OID
+      let shard = generate_path(PathVariant::OID(&commit_oid)).unwrap().parent().unwrap().to_path_buf();
+      assert_eq!(shard.file_name().unwrap().to_str().unwrap(), &commit_oid[..2]);
+      assert!(fs::read_dir(&shard).unwrap().all(|entry| entry.unwrap().file_name().to_str().unwrap() == &commit_oid[2..]));
     }
     delete_test_directory();
   }
@@ -805,12 +2397,13 @@ mod tests {
     let test_text = "Excepturi velit rem modi. Ut non ipsa aut ad dignissimos et molestias placeat. Iste est perspiciatis ab et commodi.";
     create_test_directory();
     {
+      let oid = hash_object(test_text.as_bytes(), ObjectType::Commit).unwrap();
       let path = Path::new(".ugit/HEAD");
-      fs::write(&path, test_text).unwrap();
-      let result1 = locate_ref_or_oid("@").unwrap().unwrap();
-      let result2 = locate_ref_or_oid("HEAD").unwrap().unwrap();
-      assert!(result1.contains(test_text));
-      assert!(result2.contains(test_text));
+      fs::write(&path, oid.to_string()).unwrap();
+      let result1 = locate_ref_or_oid("@", true).unwrap().unwrap();
+      let result2 = locate_ref_or_oid("HEAD", true).unwrap().unwrap();
+      assert_eq!(result1, oid);
+      assert_eq!(result2, oid);
     }
     delete_test_directory();
   }
@@ -825,20 +2418,20 @@ mod tests {
       {
         let commit_oid = hash_object(test_text.as_bytes(), ObjectType::Commit).expect("Issue when hashing a commit");
         let path = generate_path(PathVariant::Ref(RefVariant::Tag(ref_name))).unwrap();
-        let ref_value = RefValue { symbolic: false, value: Some(commit_oid.clone()), path: path.clone() };
+        let ref_value = RefValue { value: Some(RefTarget::Oid(commit_oid.clone())), path: path.clone() };
         update_ref(&ref_value, true).expect("Issue when updating ref");
-        fs::read_to_string(path).unwrap()
+        fs::read_to_string(path).unwrap().parse::<Oid>().unwrap()
       };
 
       {
         let commit_oid = hash_object(test_text.as_bytes(), ObjectType::Commit).expect("Issue when hashing a commit");
         let path = generate_path(PathVariant::Ref(RefVariant::Head(ref_name))).unwrap();
-        let ref_value = RefValue { symbolic: false, value: Some(commit_oid.clone()), path: path.clone() };
+        let ref_value = RefValue { value: Some(RefTarget::Oid(commit_oid.clone())), path: path.clone() };
         update_ref(&ref_value, true).expect("Issue when updating ref");
-        fs::read_to_string(path).unwrap()
+        fs::read_to_string(path).unwrap().parse::<Oid>().unwrap()
       };
 
-      let result = locate_ref_or_oid(ref_name).unwrap();
+      let result = locate_ref_or_oid(ref_name, true).unwrap();
       assert!(result.is_err());
     }
     delete_test_directory();
@@ -847,7 +2440,7 @@ mod tests {
   #[test]
   #[serial]
   fn locate_ref_or_oid_returns_an_error_if_repository_is_not_initialized() {
-    let result = locate_ref_or_oid("").unwrap();
+    let result = locate_ref_or_oid("", true).unwrap();
     assert!(result.is_err());
   }
 
@@ -856,16 +2449,358 @@ mod tests {
   fn locate_ref_or_oid_returns_none_if_a_ref_or_id_is_not_found() {
     create_test_directory();
     {
-      let result = locate_ref_or_oid("Good Ref Name");
+      let result = locate_ref_or_oid("Good Ref Name", true);
       assert!(result.is_none());
     }
     delete_test_directory();
   }
 
+  #[test]
+  #[serial]
+  fn resolve_oid_prefix_returns_the_full_oid_matching_a_prefix() {
+    let test_text = "Excepturi velit rem modi. Ut non ipsa aut ad dignissimos et molestias placeat. Iste est perspiciatis ab et commodi.";
+    create_test_directory();
+    {
+      let oid = hash_object(test_text.as_bytes(), ObjectType::Blob).unwrap();
+      assert_eq!(resolve_oid_prefix(&oid[..4]).unwrap(), oid);
+      assert_eq!(resolve_oid_prefix(&oid[..10]).unwrap(), oid);
+    }
+    delete_test_directory();
+  }
+
+  #[test]
+  #[serial]
+  fn resolve_oid_prefix_ignores_the_case_of_the_prefix() {
+    let test_text = "Excepturi velit rem modi. Ut non ipsa aut ad dignissimos et molestias placeat. Iste est perspiciatis ab et commodi.";
+    create_test_directory();
+    {
+      let oid = hash_object(test_text.as_bytes(), ObjectType::Blob).unwrap();
+      let mixed_case: String = oid[..10].chars().enumerate().map(|(i, c)| if i % 2 == 0 { c.to_ascii_uppercase() } else { c }).collect();
+      assert_eq!(resolve_oid_prefix(&oid[..10].to_uppercase()).unwrap(), oid);
+      assert_eq!(resolve_oid_prefix(&mixed_case).unwrap(), oid);
+    }
+    delete_test_directory();
+  }
+
+  #[test]
+  #[serial]
+  fn resolve_oid_prefix_returns_an_error_if_prefix_is_ambiguous() {
+    create_test_directory();
+    {
+      let mut shard = generate_path(PathVariant::Objects).unwrap();
+      shard.push("ab");
+      fs::create_dir(&shard).unwrap();
+      // Objects are stored under the OID less its first two characters
+      let rest_length = ObjectFormat::Sha256.oid_length() - 2;
+      fs::write(shard.join(format!("{:0<1$}", "cd0000", rest_length)), "").unwrap();
+      fs::write(shard.join(format!("{:0<1$}", "cd1111", rest_length)), "").unwrap();
+
+      let result = resolve_oid_prefix("abcd");
+      assert_eq!(result.unwrap_err().kind(), ErrorKind::InvalidInput);
+      assert_eq!(resolve_oid_prefix("abcd1").unwrap(), format!("{:0<1$}", "abcd1111", rest_length + 2));
+    }
+    delete_test_directory();
+  }
+
+  #[test]
+  #[serial]
+  fn resolve_oid_prefix_returns_an_error_if_nothing_matches() {
+    create_test_directory();
+    {
+      let result = resolve_oid_prefix("abcdef");
+      assert_eq!(result.unwrap_err().kind(), ErrorKind::NotFound);
+    }
+    delete_test_directory();
+  }
+
+  #[test]
+  #[serial]
+  fn resolve_oid_prefix_returns_an_error_if_prefix_is_too_short() {
+    let test_text = "Excepturi velit rem modi. Ut non ipsa aut ad dignissimos et molestias placeat. Iste est perspiciatis ab et commodi.";
+    create_test_directory();
+    {
+      let oid = hash_object(test_text.as_bytes(), ObjectType::Blob).unwrap();
+      let result = resolve_oid_prefix(&oid[..3]);
+      assert_eq!(result.unwrap_err().kind(), ErrorKind::InvalidInput);
+    }
+    delete_test_directory();
+  }
+
+  #[test]
+  #[serial]
+  fn iter_refs_returns_HEAD_branches_and_tags() {
+    let test_text = "Excepturi velit rem modi. Ut non ipsa aut ad dignissimos et molestias placeat. Iste est perspiciatis ab et commodi.";
+    create_test_directory();
+    {
+      let oid = hash_object(test_text.as_bytes(), ObjectType::Commit).unwrap();
+      for variant in &[RefVariant::Head("main"), RefVariant::Head("feature"), RefVariant::Tag("v1")] {
+        let ref_value = RefValue { value: Some(RefTarget::Oid(oid.clone())), path: generate_path(PathVariant::Ref(*variant)).unwrap() };
+        update_ref(&ref_value, false).expect("Issue when updating ref");
+      }
+
+      set_head(RefTarget::Symbolic(String::from("refs/heads/main")), "checkout: moving to main").expect("Issue when setting HEAD");
+
+      let refs = iter_refs(true).unwrap();
+      let names: Vec<_> = refs.iter().map(|(name, _)| name.as_str()).collect();
+      assert_eq!(names, vec!["HEAD", "refs/heads/feature", "refs/heads/main", "refs/tags/v1"]);
+      assert!(refs.iter().all(|(_, ref_value)| ref_value.oid() == Some(&oid)));
+
+      let head = &iter_refs(false).unwrap()[0].1;
+      assert_eq!(head.symbolic_target(), Some("refs/heads/main"));
+    }
+    delete_test_directory();
+  }
+
+  #[test]
+  #[serial]
+  fn delete_ref_removes_a_symbolic_ref_but_not_its_target() {
+    let test_text = "Excepturi velit rem modi. Ut non ipsa aut ad dignissimos et molestias placeat. Iste est perspiciatis ab et commodi.";
+    create_test_directory();
+    {
+      let oid = hash_object(test_text.as_bytes(), ObjectType::Commit).unwrap();
+      let target = generate_path(PathVariant::Ref(RefVariant::Head("main"))).unwrap();
+      update_ref(&RefValue { value: Some(RefTarget::Oid(oid)), path: target.clone() }, false).expect("Issue when updating ref");
+      let symbolic = generate_path(PathVariant::Ref(RefVariant::Tag("alias"))).unwrap();
+      update_ref(&RefValue { value: Some(RefTarget::Symbolic(String::from("refs/heads/main"))), path: symbolic.clone() }, false)
+        .expect("Issue when updating ref");
+
+      delete_ref(&symbolic).expect("Issue when deleting ref");
+      assert!(!symbolic.exists());
+      assert!(target.is_file());
+      assert_eq!(delete_ref(&symbolic).unwrap_err().kind(), ErrorKind::NotFound);
+    }
+    delete_test_directory();
+  }
+
+  #[test]
+  #[serial]
+  fn repository_works_from_its_root_rather_than_the_current_directory() {
+    let root = env::current_dir().unwrap().join("TEST");
+    let repository = Repository::init(&root, ObjectFormat::Sha256).expect("Issue when initializing repository");
+    {
+      assert_eq!(Repository::init(&root, ObjectFormat::Sha256).unwrap_err().kind(), ErrorKind::AlreadyExists);
+
+      let oid = repository.hash_object(b"Excepturi velit rem modi.", ObjectType::Blob).unwrap();
+      assert!(root.join(".ugit").join("objects").join(&oid[..2]).join(&oid[2..]).is_file());
+      assert_eq!(repository.get_object(&oid, ObjectType::Blob).unwrap(), b"Excepturi velit rem modi.");
+      // The current directory is not within the repository
+      assert_eq!(get_object(&oid, ObjectType::Blob).unwrap_err().kind(), ErrorKind::NotFound);
+    }
+    fs::remove_dir_all(&root).expect("Issue when deleting test directory");
+  }
+
+  #[test]
+  #[serial]
+  fn sha1_repository_names_objects_by_their_sha1_hash() {
+    fs::create_dir("TEST").expect("Issue when creating test directory");
+    env::set_current_dir("TEST").expect("Issue when cding into test directory");
+    init(ObjectFormat::Sha1).expect("Issue when initing test .ugit repository");
+    {
+      let repository = Repository::find().unwrap();
+      assert_eq!(repository.object_format, ObjectFormat::Sha1);
+      assert_eq!(get_config("core.objectFormat").unwrap(), Some(String::from("sha1")));
+
+      let oid = hash_object(b"hello\n", ObjectType::Blob).unwrap();
+      assert_eq!(oid, "a921a1ed31bcddeb5a51085e5d7dbdc7cf86b905");
+      assert_eq!(oid.len(), ObjectFormat::Sha1.oid_length());
+      assert_eq!(get_object(&oid, ObjectType::Blob).unwrap(), b"hello\n");
+      assert_eq!(resolve_oid_prefix("a921a1").unwrap(), oid);
+
+      set_config("core.objectFormat", "md5").unwrap();
+      assert_eq!(Repository::open(&env::current_dir().unwrap()).unwrap_err().kind(), ErrorKind::InvalidData);
+    }
+    delete_test_directory();
+  }
+
+  #[test]
+  #[serial]
+  fn get_repository_notices_a_removed_repository_and_a_changed_directory() {
+    create_test_directory();
+    {
+      let repository = get_repository().expect("Repository should be found");
+      assert_eq!(get_repository(), Some(repository.clone()));
+
+      fs::create_dir("Nested").unwrap();
+      env::set_current_dir("Nested").unwrap();
+      assert_eq!(get_repository(), Some(repository.clone()));
+      env::set_current_dir("..").unwrap();
+
+      fs::remove_dir_all(&repository).unwrap();
+      assert_eq!(get_repository(), None);
+      init(ObjectFormat::Sha256).expect("Issue when initializing repository");
+      assert_eq!(get_repository(), Some(repository));
+    }
+    delete_test_directory();
+  }
+
+  #[test]
+  #[serial]
+  fn get_repository_only_searches_the_current_directory_when_told_not_to_search_ancestors() {
+    create_test_directory();
+    {
+      let repository = get_repository().expect("Repository should be found");
+      fs::create_dir("Nested").unwrap();
+      env::set_current_dir("Nested").unwrap();
+      assert_eq!(get_repository(), Some(repository.clone()));
+
+      env::set_var("UGIT_NO_ANCESTOR_SEARCH", "1");
+      let nested = get_repository();
+      let found = Repository::find();
+      env::remove_var("UGIT_NO_ANCESTOR_SEARCH");
+      assert_eq!(nested, None);
+      assert_eq!(found.unwrap_err().kind(), ErrorKind::NotFound);
+      assert_eq!(get_repository(), Some(repository.clone()));
+
+      assert_eq!(Repository::open(Path::new(".")).unwrap_err().kind(), ErrorKind::NotFound);
+      assert_eq!(Repository::open(repository.parent().unwrap()).unwrap().root, repository.parent().unwrap());
+      env::set_current_dir("..").unwrap();
+    }
+    delete_test_directory();
+  }
+
+  #[test]
+  #[serial]
+  fn reinit_recreates_missing_directories_without_touching_existing_files() {
+    create_test_directory();
+    {
+      let oid = hash_object(b"Excepturi velit rem modi.", ObjectType::Blob).unwrap();
+      set_config("core.objectFormat", "sha1").unwrap();
+      let tags = generate_path(PathVariant::Tags).unwrap();
+      fs::remove_dir(&tags).unwrap();
+
+      assert_eq!(init(ObjectFormat::Sha256).unwrap_err().kind(), ErrorKind::AlreadyExists);
+      assert!(!tags.exists());
+      reinit().unwrap();
+      assert!(tags.is_dir());
+      assert_eq!(get_config("core.objectFormat").unwrap().as_deref(), Some("sha1"));
+      assert!(generate_path(PathVariant::OID(&oid)).unwrap().exists());
+      // Reinitializing a repository with nothing missing changes nothing
+      reinit().unwrap();
+      assert!(tags.is_dir());
+    }
+    delete_test_directory();
+  }
+
+  #[test]
+  #[serial]
+  fn init_creates_the_directory_named_by_UGIT_DIR() {
+    fs::create_dir("TEST").expect("Issue when creating test directory");
+    env::set_current_dir("TEST").expect("Issue when cding into test directory");
+    {
+      env::set_var("UGIT_DIR", ".other_ugit");
+      let result = init(ObjectFormat::Sha256);
+      let repository = get_repository();
+      env::remove_var("UGIT_DIR");
+
+      result.expect("Issue when initializing repository");
+      assert!(Path::new(".other_ugit/objects").is_dir());
+      assert!(Path::new(".other_ugit/refs/heads").is_dir());
+      assert!(!Path::new(".ugit").exists());
+      assert_eq!(repository, Some(env::current_dir().unwrap().join(".other_ugit")));
+      // Without UGIT_DIR, the repository is no longer found
+      assert_eq!(get_repository(), None);
+    }
+    delete_test_directory();
+  }
+
+  #[test]
+  fn validate_ref_name_refuses_names_which_could_escape_refs() {
+    for name in &["", "../../etc/evil", "a/b", "a\\b", "one..two", ".hidden"] {
+      assert_eq!(validate_ref_name(name).unwrap_err().kind(), ErrorKind::InvalidInput, "{}", name);
+    }
+
+    assert!(validate_ref_name("v1.0").is_ok());
+    assert!(validate_ref_name("feature-branch").is_ok());
+  }
+
+  #[test]
+  #[serial]
+  fn write_index_and_get_index_round_trip() {
+    create_test_directory();
+    {
+      assert!(get_index().unwrap().is_empty());
+
+      let mut index = BTreeMap::new();
+      index.insert(String::from("index.html"), "a".repeat(ObjectFormat::Sha256.oid_length()).parse().unwrap());
+      index.insert(String::from("One/\"quoted\" name.txt"), "b".repeat(ObjectFormat::Sha256.oid_length()).parse().unwrap());
+      write_index(&index).expect("Issue when writing index");
+      assert_eq!(get_index().unwrap(), index);
+
+      fs::write(generate_path(PathVariant::Index).unwrap(), "[]").unwrap();
+      assert_eq!(get_index().unwrap_err().kind(), ErrorKind::InvalidData);
+    }
+    delete_test_directory();
+  }
+
+  #[test]
+  #[serial]
+  fn get_config_returns_value_of_given_key() {
+    create_test_directory();
+    {
+      assert_eq!(get_config("user.name").unwrap(), None);
+
+      fs::write(generate_path(PathVariant::Config).unwrap(), "user.name = Jane Doe\nuser.email=jane@example.com\n").unwrap();
+      // Written behind the repository's back, so it is opened again for the file to be read afresh
+      Repository::open(&env::current_dir().unwrap()).unwrap();
+      assert_eq!(get_config("user.name").unwrap(), Some(String::from("Jane Doe")));
+      assert_eq!(get_config("user.email").unwrap(), Some(String::from("jane@example.com")));
+      assert_eq!(get_config("user").unwrap(), None);
+    }
+    delete_test_directory();
+  }
+
+  #[test]
+  #[serial]
+  fn set_config_round_trips_and_replaces_existing_values() {
+    create_test_directory();
+    {
+      set_config("user.name", "Jane Doe").unwrap();
+      assert_eq!(get_config("user.name").unwrap(), Some(String::from("Jane Doe")));
+
+      fs::write(generate_path(PathVariant::Config).unwrap(), "# Comment\nuser.name=Jane Doe\nuser.email = jane@example.com\n").unwrap();
+      set_config("user.name", "John Doe").unwrap();
+      set_config("core.branch", "main").unwrap();
+      assert_eq!(get_config("user.name").unwrap(), Some(String::from("John Doe")));
+      assert_eq!(get_config("user.email").unwrap(), Some(String::from("jane@example.com")));
+      assert_eq!(get_config("core.branch").unwrap(), Some(String::from("main")));
+      let contents = fs::read_to_string(generate_path(PathVariant::Config).unwrap()).unwrap();
+      assert_eq!(contents, "# Comment\nuser.name = John Doe\nuser.email = jane@example.com\ncore.branch = main\n");
+
+      assert_eq!(set_config("bad=key", "value").unwrap_err().kind(), ErrorKind::InvalidInput);
+      assert_eq!(set_config("key", "two\nlines").unwrap_err().kind(), ErrorKind::InvalidInput);
+    }
+    delete_test_directory();
+  }
+
+  #[test]
+  #[serial]
+  fn repository_reads_its_config_once_and_keeps_it_in_step_with_set_config() {
+    create_test_directory();
+    {
+      fs::write(generate_path(PathVariant::Config).unwrap(), "core.verifyObjects = false\n").unwrap();
+      let repository = Repository::open(&env::current_dir().unwrap()).unwrap();
+      fs::remove_file(generate_path(PathVariant::Config).unwrap()).unwrap();
+      assert_eq!(repository.get_config("core.verifyObjects").unwrap().as_deref(), Some("false"));
+      // Finding the repository again reuses the one opened, rather than reading the config on every call
+      assert_eq!(Repository::find().unwrap().get_config("core.verifyObjects").unwrap().as_deref(), Some("false"));
+      assert_eq!(get_config("core.verifyObjects").unwrap().as_deref(), Some("false"));
+
+      repository.set_config("user.name", "Jane Doe").unwrap();
+      assert_eq!(repository.get_config("user.name").unwrap().as_deref(), Some("Jane Doe"));
+      assert_eq!(repository.clone().get_config("user.name").unwrap().as_deref(), Some("Jane Doe"));
+      assert_eq!(get_config("user.name").unwrap().as_deref(), Some("Jane Doe"));
+      // Opening the repository reads the config afresh
+      let reopened = Repository::open(&env::current_dir().unwrap()).unwrap();
+      assert_eq!(reopened.get_config("core.verifyObjects").unwrap(), None);
+      assert_eq!(get_config("core.verifyObjects").unwrap(), None);
+    }
+    delete_test_directory();
+  }
+
   fn create_test_directory() {
     fs::create_dir("TEST").expect("Issue when creating test directory");
     env::set_current_dir("TEST").expect("Issue when cding into test directory");
-    init().expect("Issue when initing test .ugit repository");
+    init(ObjectFormat::Sha256).expect("Issue when initing test .ugit repository");
   }
 
   fn delete_test_directory() {
@@ -0,0 +1,178 @@
+use std::path::Path;
+
+// Number of unchanged lines shown around each group of changes
+static CONTEXT_LINES: usize = 3;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LineChange<'a> {
+  Unchanged(&'a str),
+  Removed(&'a str),
+  Added(&'a str),
+}
+
+// Renders the difference between two versions of a file. A missing version stands for a file which was added or removed.
+pub fn diff_blobs(path: &Path, from: Option<&[u8]>, to: Option<&[u8]>) -> String {
+  let from_name = match from {
+    Some(_) => format!("a/{}", path.display()),
+    None => String::from("/dev/null")
+  };
+
+  let to_name = match to {
+    Some(_) => format!("b/{}", path.display()),
+    None => String::from("/dev/null")
+  };
+
+  let mut output = format!("diff a/{0} b/{0}\n--- {1}\n+++ {2}\n", path.display(), from_name, to_name);
+  match (as_text(from.unwrap_or(&[])), as_text(to.unwrap_or(&[]))) {
+    (Some(from), Some(to)) => output.push_str(&format_hunks(&diff_lines(from, to))),
+    _ => output.push_str("Binary files differ\n")
+  };
+
+  output
+}
+
+// Computes the changes turning from into to, based on the longest common subsequence of their lines.
+pub fn diff_lines<'a>(from: &'a str, to: &'a str) -> Vec<LineChange<'a>> {
+  let from: Vec<&str> = from.lines().collect();
+  let to: Vec<&str> = to.lines().collect();
+
+  // lcs[i][j] is the length of the longest common subsequence of from[i..] and to[j..]
+  let mut lcs = vec![vec![0; to.len() + 1]; from.len() + 1];
+  for i in (0..from.len()).rev() {
+    for j in (0..to.len()).rev() {
+      lcs[i][j] = if from[i] == to[j] {
+        lcs[i + 1][j + 1] + 1
+      }
+      else {
+        lcs[i + 1][j].max(lcs[i][j + 1])
+      };
+    }
+  }
+
+  let mut changes = Vec::new();
+  let (mut i, mut j) = (0, 0);
+  while i < from.len() && j < to.len() {
+    if from[i] == to[j] {
+      changes.push(LineChange::Unchanged(from[i]));
+      i += 1;
+      j += 1;
+    }
+    else if lcs[i + 1][j] >= lcs[i][j + 1] {
+      changes.push(LineChange::Removed(from[i]));
+      i += 1;
+    }
+    else {
+      changes.push(LineChange::Added(to[j]));
+      j += 1;
+    }
+  }
+
+  changes.extend(from[i..].iter().map(|line| LineChange::Removed(line)));
+  changes.extend(to[j..].iter().map(|line| LineChange::Added(line)));
+  changes
+}
+
+// Groups changes into unified diff hunks, each surrounded by up to CONTEXT_LINES unchanged lines.
+pub fn format_hunks(changes: &[LineChange]) -> String {
+  let changed: Vec<usize> = changes
+    .iter()
+    .enumerate()
+    .filter(|(_, change)| !matches!(change, LineChange::Unchanged(_)))
+    .map(|(i, _)| i)
+    .collect();
+
+  let mut output = String::new();
+  let mut k = 0;
+  while k < changed.len() {
+    let start = changed[k].saturating_sub(CONTEXT_LINES);
+    let mut last = changed[k];
+    k += 1;
+    // Changes close enough to share their context lines belong to the same hunk
+    while k < changed.len() && changed[k] - last <= 2 * CONTEXT_LINES + 1 {
+      last = changed[k];
+      k += 1;
+    }
+
+    let end = (last + CONTEXT_LINES + 1).min(changes.len());
+    let is_old = |change: &&LineChange| !matches!(change, LineChange::Added(_));
+    let is_new = |change: &&LineChange| !matches!(change, LineChange::Removed(_));
+    let old_start = changes[..start].iter().filter(is_old).count();
+    let new_start = changes[..start].iter().filter(is_new).count();
+    let old_len = changes[start..end].iter().filter(is_old).count();
+    let new_len = changes[start..end].iter().filter(is_new).count();
+    output.push_str(&format!("@@ -{} +{} @@\n", format_range(old_start, old_len), format_range(new_start, new_len)));
+
+    for change in &changes[start..end] {
+      let line = match change {
+        LineChange::Unchanged(line) => format!(" {}\n", line),
+        LineChange::Removed(line) => format!("-{}\n", line),
+        LineChange::Added(line) => format!("+{}\n", line),
+      };
+
+      output.push_str(&line);
+    }
+  }
+
+  output
+}
+
+// Line numbers in hunk headers are 1-based, except for an empty range, which names the line it follows.
+fn format_range(lines_before: usize, len: usize) -> String {
+  match len {
+    0 => format!("{},0", lines_before),
+    1 => format!("{}", lines_before + 1),
+    _ => format!("{},{}", lines_before + 1, len)
+  }
+}
+
+fn as_text(contents: &[u8]) -> Option<&str> {
+  if contents.contains(&0) {
+    return None;
+  }
+
+  std::str::from_utf8(contents).ok()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn diff_lines_finds_removed_and_added_lines() {
+    let changes = diff_lines("a\nb\nc\n", "a\nc\nd\n");
+    assert_eq!(changes, vec![
+      LineChange::Unchanged("a"),
+      LineChange::Removed("b"),
+      LineChange::Unchanged("c"),
+      LineChange::Added("d"),
+    ]);
+  }
+
+  #[test]
+  fn format_hunks_limits_context_around_changes() {
+    let from = "1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n";
+    let to = "1\n2\n3\n4\n5\n6\n7\n8\nnine\n10\n";
+    let hunks = format_hunks(&diff_lines(from, to));
+    assert_eq!(hunks, "@@ -6,5 +6,5 @@\n 6\n 7\n 8\n-9\n+nine\n 10\n");
+  }
+
+  #[test]
+  fn format_hunks_splits_distant_changes_into_separate_hunks() {
+    let from = "1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n11\n12\n";
+    let to = "one\n2\n3\n4\n5\n6\n7\n8\n9\n10\n11\ntwelve\n";
+    let hunks = format_hunks(&diff_lines(from, to));
+    assert_eq!(hunks.matches("@@ -").count(), 2);
+  }
+
+  #[test]
+  fn diff_blobs_treats_a_missing_version_as_an_empty_file() {
+    let output = diff_blobs(Path::new("new.txt"), None, Some(b"hello\n"));
+    assert_eq!(output, "diff a/new.txt b/new.txt\n--- /dev/null\n+++ b/new.txt\n@@ -0,0 +1 @@\n+hello\n");
+  }
+
+  #[test]
+  fn diff_blobs_does_not_diff_binary_files_line_by_line() {
+    let output = diff_blobs(Path::new("image.png"), Some(&[0x89, 0x00, 0xff]), Some(&[0x89, 0x00, 0xfe]));
+    assert!(output.ends_with("Binary files differ\n"));
+  }
+}
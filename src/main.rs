@@ -4,11 +4,24 @@ extern crate sha2;
 mod base;
 mod cli;
 mod data;
+mod diff;
+mod store;
 mod utils;
 
+use std::io::ErrorKind;
+
 fn main() {
-  match cli::cli() {
-    Err(err) => println!("{}", err),
-    Ok(_) => ()
-  };
+  if let Err(err) = cli::cli() {
+    eprintln!("{}", err);
+    std::process::exit(exit_code(err.kind()));
+  }
+}
+
+// Lets scripts tell a missing ref or object, and a bad argument, apart from other failures
+fn exit_code(kind: ErrorKind) -> i32 {
+  match kind {
+    ErrorKind::NotFound => 2,
+    ErrorKind::InvalidInput => 3,
+    _ => 1
+  }
 }
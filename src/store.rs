@@ -0,0 +1,308 @@
+#[cfg(test)]
+use std::cell::RefCell;
+#[cfg(test)]
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::fs;
+use std::io::{Cursor, Error, ErrorKind, Read};
+use std::path::PathBuf;
+use std::process;
+use std::rc::Rc;
+
+// Objects are written to a file of this prefix before being renamed into place. No OID starts with it, so a file left behind by
+// an interrupted write is never taken for an object.
+const TEMP_PREFIX: &str = ".tmp-";
+
+// Somewhere the bytes of objects are kept, named by their OID. A store neither hashes nor compresses anything: it holds whichever
+// bytes it is given, which data::ObjectDatabase has already framed and compressed, so that objects can be kept somewhere other
+// than .ugit/objects by implementing only these.
+pub trait ObjectStore: Debug {
+  // Stores bytes under oid, replacing whatever was stored there before.
+  fn write(&self, oid: &str, bytes: &[u8]) -> std::io::Result<()>;
+
+  // Returns a NotFound error if nothing is stored under oid.
+  fn read(&self, oid: &str) -> std::io::Result<Vec<u8>>;
+
+  fn exists(&self, oid: &str) -> bool;
+
+  // Lists the OID of every stored object, in no particular order.
+  fn iter_oids(&self) -> std::io::Result<Vec<String>>;
+
+  // Returns a NotFound error if nothing is stored under oid.
+  fn delete(&self, oid: &str) -> std::io::Result<()>;
+
+  // How many bytes are stored under oid. Stores which can tell without reading the bytes override this.
+  fn size(&self, oid: &str) -> std::io::Result<u64> {
+    Ok(self.read(oid)?.len() as u64)
+  }
+
+  // Stores which can hand out an object a piece at a time override this, so that a large object is never read whole.
+  fn open(&self, oid: &str) -> std::io::Result<Box<dyn Read>> {
+    Ok(Box::new(Cursor::new(self.read(oid)?)))
+  }
+
+  // Stores which can find the objects sharing a prefix without listing every object override this.
+  fn oids_with_prefix(&self, prefix: &str) -> std::io::Result<Vec<String>> {
+    Ok(self.iter_oids()?.into_iter().filter(|oid| oid.starts_with(prefix)).collect())
+  }
+}
+
+// A store shared between the repositories and object databases using it
+impl<S: ObjectStore + ?Sized> ObjectStore for Rc<S> {
+  fn write(&self, oid: &str, bytes: &[u8]) -> std::io::Result<()> {
+    (**self).write(oid, bytes)
+  }
+
+  fn read(&self, oid: &str) -> std::io::Result<Vec<u8>> {
+    (**self).read(oid)
+  }
+
+  fn exists(&self, oid: &str) -> bool {
+    (**self).exists(oid)
+  }
+
+  fn iter_oids(&self) -> std::io::Result<Vec<String>> {
+    (**self).iter_oids()
+  }
+
+  fn delete(&self, oid: &str) -> std::io::Result<()> {
+    (**self).delete(oid)
+  }
+
+  fn size(&self, oid: &str) -> std::io::Result<u64> {
+    (**self).size(oid)
+  }
+
+  fn open(&self, oid: &str) -> std::io::Result<Box<dyn Read>> {
+    (**self).open(oid)
+  }
+
+  fn oids_with_prefix(&self, prefix: &str) -> std::io::Result<Vec<String>> {
+    (**self).oids_with_prefix(prefix)
+  }
+}
+
+// Keeps each object in a file of its own under a directory, .ugit/objects for a repository
+#[derive(Debug)]
+pub struct FsObjectStore {
+  objects: PathBuf,
+}
+
+impl FsObjectStore {
+  pub fn new(objects: PathBuf) -> Self {
+    Self { objects }
+  }
+
+  // Objects are stored under their lowercase OID, which an OID given in uppercase names too. They are sharded into subdirectories
+  // named after the first two characters of their OID.
+  pub fn path(&self, oid: &str) -> PathBuf {
+    let oid = oid.to_ascii_lowercase();
+    if oid.len() > 2 && oid.is_char_boundary(2) {
+      self.objects.join(&oid[..2]).join(&oid[2..])
+    }
+    else {
+      self.objects.join(oid)
+    }
+  }
+
+  fn not_found(&self, oid: &str) -> Error {
+    Error::new(ErrorKind::NotFound, format!("A file with the given OID does not exist [{}]", self.path(oid).display()))
+  }
+}
+
+impl ObjectStore for FsObjectStore {
+  // Written to a temporary file in the same shard, then renamed over the object's path, so that the object is never seen partly
+  // written
+  fn write(&self, oid: &str, bytes: &[u8]) -> std::io::Result<()> {
+    let path = self.path(oid);
+    let shard = path.parent().unwrap();
+    fs::create_dir_all(shard)?;
+    let temp_path = shard.join(format!("{}{}-{}", TEMP_PREFIX, path.file_name().unwrap().to_string_lossy(), process::id()));
+    fs::write(&temp_path, bytes)?;
+    if let Err(err) = fs::rename(&temp_path, &path) {
+      let _ = fs::remove_file(&temp_path);
+      return Err(err);
+    }
+
+    Ok(())
+  }
+
+  fn read(&self, oid: &str) -> std::io::Result<Vec<u8>> {
+    match fs::read(self.path(oid)) {
+      Err(err) if err.kind() == ErrorKind::NotFound => Err(self.not_found(oid)),
+      result => result
+    }
+  }
+
+  fn exists(&self, oid: &str) -> bool {
+    self.path(oid).is_file()
+  }
+
+  fn iter_oids(&self) -> std::io::Result<Vec<String>> {
+    let mut oids = Vec::new();
+    for shard in fs::read_dir(&self.objects)? {
+      let shard = shard?;
+      if !shard.file_type()?.is_dir() {
+        continue;
+      }
+
+      let prefix = shard.file_name().to_string_lossy().into_owned();
+      for entry in fs::read_dir(shard.path())? {
+        let name = entry?.file_name().to_string_lossy().into_owned();
+        if !name.starts_with(TEMP_PREFIX) {
+          oids.push(format!("{}{}", prefix, name));
+        }
+      }
+    }
+
+    Ok(oids)
+  }
+
+  fn delete(&self, oid: &str) -> std::io::Result<()> {
+    let path = self.path(oid);
+    match fs::remove_file(&path) {
+      Err(err) if err.kind() == ErrorKind::NotFound => return Err(self.not_found(oid)),
+      result => result?
+    };
+
+    // The shard directory is only removed once empty, so failing to remove it is expected
+    let _ = fs::remove_dir(path.parent().unwrap());
+    Ok(())
+  }
+
+  fn size(&self, oid: &str) -> std::io::Result<u64> {
+    match fs::metadata(self.path(oid)) {
+      Ok(metadata) => Ok(metadata.len()),
+      Err(err) if err.kind() == ErrorKind::NotFound => Err(self.not_found(oid)),
+      Err(err) => Err(err)
+    }
+  }
+
+  fn open(&self, oid: &str) -> std::io::Result<Box<dyn Read>> {
+    match fs::File::open(self.path(oid)) {
+      Ok(file) => Ok(Box::new(file)),
+      Err(err) if err.kind() == ErrorKind::NotFound => Err(self.not_found(oid)),
+      Err(err) => Err(err)
+    }
+  }
+
+  // Every object sharing the prefix lives in the same shard, so only that shard is listed
+  fn oids_with_prefix(&self, prefix: &str) -> std::io::Result<Vec<String>> {
+    let prefix = prefix.to_ascii_lowercase();
+    if prefix.len() <= 2 || !prefix.is_char_boundary(2) {
+      return Ok(self.iter_oids()?.into_iter().filter(|oid| oid.starts_with(&prefix)).collect());
+    }
+
+    let shard = self.path(&prefix).parent().unwrap().to_path_buf();
+    let mut oids = Vec::new();
+    if shard.is_dir() {
+      for entry in fs::read_dir(&shard)? {
+        let name = entry?.file_name().to_string_lossy().into_owned();
+        let oid = format!("{}{}", &prefix[..2], name);
+        if !name.starts_with(TEMP_PREFIX) && oid.starts_with(&prefix) {
+          oids.push(oid);
+        }
+      }
+    }
+
+    Ok(oids)
+  }
+}
+
+// Keeps objects in memory, so that tests can work on objects without writing them to disk. Like FsObjectStore, it names an object
+// by its lowercase OID.
+#[cfg(test)]
+#[derive(Debug, Default)]
+pub struct MemoryObjectStore {
+  objects: RefCell<HashMap<String, Vec<u8>>>,
+}
+
+#[cfg(test)]
+impl MemoryObjectStore {
+  fn not_found(oid: &str) -> Error {
+    Error::new(ErrorKind::NotFound, format!("No object is stored under the given OID [{}]", oid))
+  }
+}
+
+#[cfg(test)]
+impl ObjectStore for MemoryObjectStore {
+  fn write(&self, oid: &str, bytes: &[u8]) -> std::io::Result<()> {
+    self.objects.borrow_mut().insert(oid.to_ascii_lowercase(), bytes.to_vec());
+    Ok(())
+  }
+
+  fn read(&self, oid: &str) -> std::io::Result<Vec<u8>> {
+    match self.objects.borrow().get(&oid.to_ascii_lowercase()) {
+      Some(bytes) => Ok(bytes.clone()),
+      None => Err(Self::not_found(oid))
+    }
+  }
+
+  fn exists(&self, oid: &str) -> bool {
+    self.objects.borrow().contains_key(&oid.to_ascii_lowercase())
+  }
+
+  fn iter_oids(&self) -> std::io::Result<Vec<String>> {
+    Ok(self.objects.borrow().keys().cloned().collect())
+  }
+
+  fn delete(&self, oid: &str) -> std::io::Result<()> {
+    match self.objects.borrow_mut().remove(&oid.to_ascii_lowercase()) {
+      Some(_) => Ok(()),
+      None => Err(Self::not_found(oid))
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::env;
+  use super::*;
+
+  #[test]
+  fn fs_object_store_shards_objects_by_the_start_of_their_oid() {
+    let objects = env::temp_dir().join(format!("ugit-fs-object-store-{}", process::id()));
+    let store = FsObjectStore::new(objects.clone());
+    let oid = "ab".repeat(20);
+    let other_oid = format!("abcd{}", "0".repeat(36));
+
+    store.write(&oid, b"first").unwrap();
+    store.write(&other_oid, b"second").unwrap();
+    assert!(objects.join("ab").join(&oid[2..]).is_file());
+    assert!(store.exists(&oid.to_uppercase()));
+    assert_eq!(store.read(&oid).unwrap(), b"first");
+    assert_eq!(store.size(&oid).unwrap(), 5);
+    let mut oids = store.iter_oids().unwrap();
+    oids.sort();
+    assert_eq!(oids, vec![oid.clone(), other_oid.clone()]);
+    assert_eq!(store.oids_with_prefix("ABCD").unwrap(), vec![other_oid.clone()]);
+
+    store.delete(&oid).unwrap();
+    assert_eq!(store.read(&oid).unwrap_err().kind(), ErrorKind::NotFound);
+    assert_eq!(store.delete(&oid).unwrap_err().kind(), ErrorKind::NotFound);
+    store.delete(&other_oid).unwrap();
+    // The emptied shard goes along with its last object
+    assert!(!objects.join("ab").exists());
+    fs::remove_dir_all(&objects).unwrap();
+  }
+
+  #[test]
+  fn fs_object_store_replaces_objects_through_a_temporary_file() {
+    let objects = env::temp_dir().join(format!("ugit-fs-object-store-temp-{}", process::id()));
+    let store = FsObjectStore::new(objects.clone());
+    let oid = "cd".repeat(20);
+
+    store.write(&oid, b"first").unwrap();
+    store.write(&oid, b"second").unwrap();
+    assert_eq!(store.read(&oid).unwrap(), b"second");
+    // Nothing but the object is left in its shard
+    let names: Vec<_> = fs::read_dir(objects.join("cd")).unwrap().map(|entry| entry.unwrap().file_name()).collect();
+    assert_eq!(names, vec![std::ffi::OsString::from(&oid[2..])]);
+
+    // A temporary file left behind by an interrupted write is not listed as an object
+    fs::write(objects.join("cd").join(format!("{}{}", TEMP_PREFIX, &oid[2..])), b"partial").unwrap();
+    assert_eq!(store.iter_oids().unwrap(), vec![oid.clone()]);
+    assert_eq!(store.oids_with_prefix("cdcd").unwrap(), vec![oid.clone()]);
+    fs::remove_dir_all(&objects).unwrap();
+  }
+}
@@ -1,9 +1,387 @@
+use std::collections::BTreeMap;
+use std::iter::Peekable;
+use std::str::Chars;
+
+// Whether s is a non-empty run of hex digits. Uppercase A-F are accepted, so that an OID pasted in uppercase is recognized, though
+// OIDs are always written in lowercase.
 pub fn is_hex(s: &str) -> bool {
-  s.chars().all(|c| match c {
+  !s.is_empty() && s.chars().all(|c| match c {
     // 0-9
     '\u{0030}'..='\u{0039}' => true,
+    // A-F
+    '\u{0041}'..='\u{0046}' => true,
     // a-f
     '\u{0061}'..='\u{0066}' => true,
     _ => false
   })
 }
+
+// Matches text against a pattern in which '*' stands for any run of characters other than '/', and '?' for any one character
+// other than '/'.
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+  let pattern: Vec<char> = pattern.chars().collect();
+  let text: Vec<char> = text.chars().collect();
+  // After a mismatch, the last '*' seen takes one more character and matching resumes right after it. Retrying only the last '*'
+  // is enough, which keeps the time taken to the pattern's length times the text's.
+  let mut star: Option<(usize, usize)> = None;
+  let (mut p, mut t) = (0, 0);
+  while t < text.len() {
+    match pattern.get(p) {
+      Some('*') => {
+        star = Some((p + 1, t));
+        p += 1;
+      },
+      Some('?') if text[t] != '/' => {
+        p += 1;
+        t += 1;
+      },
+      Some(&c) if c != '?' && c == text[t] => {
+        p += 1;
+        t += 1;
+      },
+      _ => match star {
+        Some((star_p, star_t)) if text[star_t] != '/' => {
+          star = Some((star_p, star_t + 1));
+          p = star_p;
+          t = star_t + 1;
+        },
+        _ => return false
+      }
+    }
+  }
+
+  pattern[p..].iter().all(|&c| c == '*')
+}
+
+// Tidies a commit message written by hand: trailing whitespace is stripped from every line, blank lines are dropped from the start
+// and end, and runs of blank lines within are collapsed into one. With strip_comments set, lines starting with '#' are dropped
+// first, as they are in the template an editor is opened on.
+pub fn clean_message(message: &str, strip_comments: bool) -> String {
+  let mut lines: Vec<&str> = Vec::new();
+  for line in message.lines() {
+    if strip_comments && line.starts_with('#') {
+      continue;
+    }
+
+    let line = line.trim_end();
+    if line.is_empty() && matches!(lines.last(), None | Some(&"")) {
+      continue;
+    }
+
+    lines.push(line);
+  }
+
+  while lines.last() == Some(&"") {
+    lines.pop();
+  }
+
+  lines.join("\n")
+}
+
+// Formats seconds since the Unix epoch as a UTC date, e.g. "2021-04-16 09:30:00 +0000".
+pub fn format_timestamp(timestamp: u64) -> String {
+  let days = (timestamp / 86400) as i64;
+  let seconds = timestamp % 86400;
+
+  // Converts days since the epoch to a civil date, see http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+  let z = days + 719468;
+  let era = z.div_euclid(146097);
+  let day_of_era = z.rem_euclid(146097);
+  let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+  let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+  let shifted_month = (5 * day_of_year + 2) / 153;
+  let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+  let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 };
+  let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+  format!("{:04}-{:02}-{:02} {:02}:{:02}:{:02} +0000", year, month, day, seconds / 3600, seconds % 3600 / 60, seconds % 60)
+}
+
+// Splits a signature formatted as "Name <email> timestamp timezone" into the identity and the timestamp. The identity runs up to
+// the closing '>', so that the whole name is kept when the timestamp or timezone is missing. Without an email, the whole
+// signature is the identity.
+pub fn parse_signature(signature: &str) -> (&str, Option<u64>) {
+  match signature.rfind('>') {
+    Some(end) => {
+      let timestamp = signature[end + 1..].split_whitespace().next().and_then(|timestamp| timestamp.parse().ok());
+      (&signature[..=end], timestamp)
+    },
+    None => (signature, None)
+  }
+}
+
+// Writes a map of strings as a JSON object, one entry per line.
+pub fn to_json_object(map: &BTreeMap<String, String>) -> String {
+  if map.is_empty() {
+    return String::from("{}\n");
+  }
+
+  let entries: Vec<_> = map
+    .iter()
+    .map(|(key, value)| format!("  {}: {}", to_json_string(key), to_json_string(value)))
+    .collect();
+
+  format!("{{\n{}\n}}\n", entries.join(",\n"))
+}
+
+// Reads a JSON object whose values are all strings, as written by to_json_object. Returns None for anything else.
+pub fn parse_json_object(text: &str) -> Option<BTreeMap<String, String>> {
+  let mut chars = text.chars().peekable();
+  let mut map = BTreeMap::new();
+  skip_whitespace(&mut chars);
+  if chars.next() != Some('{') {
+    return None;
+  }
+
+  skip_whitespace(&mut chars);
+  if chars.peek() == Some(&'}') {
+    chars.next();
+  }
+  else {
+    loop {
+      skip_whitespace(&mut chars);
+      let key = parse_json_string(&mut chars)?;
+      skip_whitespace(&mut chars);
+      if chars.next() != Some(':') {
+        return None;
+      }
+
+      skip_whitespace(&mut chars);
+      let value = parse_json_string(&mut chars)?;
+      map.insert(key, value);
+      skip_whitespace(&mut chars);
+      match chars.next() {
+        Some(',') => continue,
+        Some('}') => break,
+        _ => return None
+      };
+    }
+  }
+
+  skip_whitespace(&mut chars);
+  match chars.next() {
+    None => Some(map),
+    Some(_) => None
+  }
+}
+
+fn to_json_string(s: &str) -> String {
+  let mut result = String::from("\"");
+  for c in s.chars() {
+    match c {
+      '"' => result.push_str("\\\""),
+      '\\' => result.push_str("\\\\"),
+      '\n' => result.push_str("\\n"),
+      '\r' => result.push_str("\\r"),
+      '\t' => result.push_str("\\t"),
+      c if (c as u32) < 0x20 => result.push_str(&format!("\\u{:04x}", c as u32)),
+      c => result.push(c)
+    };
+  }
+
+  result.push('"');
+  result
+}
+
+fn parse_json_string(chars: &mut Peekable<Chars>) -> Option<String> {
+  if chars.next() != Some('"') {
+    return None;
+  }
+
+  let mut result = String::new();
+  loop {
+    match chars.next()? {
+      '"' => return Some(result),
+      '\\' => match chars.next()? {
+        '"' => result.push('"'),
+        '\\' => result.push('\\'),
+        '/' => result.push('/'),
+        'b' => result.push('\u{0008}'),
+        'f' => result.push('\u{000c}'),
+        'n' => result.push('\n'),
+        'r' => result.push('\r'),
+        't' => result.push('\t'),
+        'u' => {
+          // A character outside the Basic Multilingual Plane is escaped as a pair of surrogates, the high one first. Neither
+          // surrogate is a character on its own.
+          let code = parse_json_code_unit(chars)?;
+          let code = if (0xd800..0xdc00).contains(&code) {
+            if chars.next() != Some('\\') || chars.next() != Some('u') {
+              return None;
+            }
+
+            let low = parse_json_code_unit(chars)?;
+            if !(0xdc00..0xe000).contains(&low) {
+              return None;
+            }
+
+            0x10000 + ((code - 0xd800) << 10) + (low - 0xdc00)
+          }
+          else {
+            code
+          };
+
+          result.push(std::char::from_u32(code)?);
+        },
+        _ => return None
+      },
+      // Control characters must be escaped
+      c if (c as u32) < 0x20 => return None,
+      c => result.push(c)
+    };
+  }
+}
+
+// Reads the four hex digits of a \u escape as a UTF-16 code unit.
+fn parse_json_code_unit(chars: &mut Peekable<Chars>) -> Option<u32> {
+  let code: String = chars.by_ref().take(4).collect();
+  if code.len() != 4 || !is_hex(&code) {
+    return None;
+  }
+
+  u32::from_str_radix(&code, 16).ok()
+}
+
+fn skip_whitespace(chars: &mut Peekable<Chars>) {
+  while let Some(c) = chars.peek() {
+    if !c.is_whitespace() {
+      break;
+    }
+
+    chars.next();
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn clean_message_strips_comments_and_surrounding_blank_lines() {
+    let message = "\n# A comment\nSubject  \n\n\n\nBody\n# Another\n\n";
+    assert_eq!(clean_message(message, true), "Subject\n\nBody");
+    assert_eq!(clean_message(message, false), "# A comment\nSubject\n\nBody\n# Another");
+    assert_eq!(clean_message("# Only a comment\n", true), "");
+  }
+
+  #[test]
+  fn is_hex_rejects_the_empty_string() {
+    assert!(!is_hex(""));
+  }
+
+  #[test]
+  fn is_hex_accepts_lowercase_hex() {
+    assert!(is_hex("0123456789abcdef"));
+  }
+
+  #[test]
+  fn is_hex_accepts_uppercase_and_mixed_case_hex() {
+    assert!(is_hex("0123456789ABCDEF"));
+    assert!(is_hex("abcDef"));
+  }
+
+  #[test]
+  fn is_hex_rejects_a_non_hex_character() {
+    assert!(!is_hex("abcdeg"));
+    assert!(!is_hex("ABCDEG"));
+    assert!(!is_hex("abc def"));
+  }
+
+  #[test]
+  fn glob_match_matches_literal_characters_and_question_marks() {
+    assert!(glob_match("index.html", "index.html"));
+    assert!(!glob_match("index.html", "index.htm"));
+    assert!(!glob_match("index.htm", "index.html"));
+    assert!(glob_match("file?.txt", "file1.txt"));
+    assert!(!glob_match("file?.txt", "file.txt"));
+    assert!(!glob_match("a?b", "a/b"));
+    assert!(glob_match("", ""));
+    assert!(!glob_match("", "a"));
+  }
+
+  #[test]
+  fn glob_match_lets_a_star_match_any_run_of_characters_within_a_path_component() {
+    assert!(glob_match("*", ""));
+    assert!(glob_match("*.log", "debug.log"));
+    assert!(glob_match("*.log", ".log"));
+    assert!(!glob_match("*.log", "debug.log.old"));
+    assert!(glob_match("a*b*c", "aXbYbZc"));
+    assert!(glob_match("a**", "abc"));
+    assert!(glob_match("build/*", "build/output"));
+    assert!(!glob_match("build/*", "build/output/a.o"));
+    assert!(!glob_match("*.o", "build/a.o"));
+    assert!(glob_match("*/*.o", "build/a.o"));
+  }
+
+  #[test]
+  fn glob_match_with_many_stars_does_not_backtrack_exponentially() {
+    let text = "a".repeat(60);
+    assert!(!glob_match("*a*a*a*a*a*a*a*a*a*a*b", &text));
+    assert!(glob_match("*a*a*a*a*a*a*a*a*a*a*", &text));
+  }
+
+  #[test]
+  fn json_object_round_trips_escapes_control_characters_and_non_bmp_characters() {
+    let mut map = BTreeMap::new();
+    map.insert(String::from("quote \" and \\ backslash"), String::from("line\nbreak\ttab\rreturn"));
+    map.insert(String::from("control \u{1}\u{1f}"), String::from("emoji \u{1f600} and é"));
+    map.insert(String::new(), String::new());
+    let text = to_json_object(&map);
+    assert!(text.contains("\\u0001\\u001f"));
+    assert_eq!(parse_json_object(&text), Some(map));
+    assert_eq!(to_json_object(&BTreeMap::new()), "{}\n");
+    assert_eq!(parse_json_object("{}"), Some(BTreeMap::new()));
+  }
+
+  #[test]
+  fn parse_json_object_reads_unicode_escapes_and_surrogate_pairs() {
+    let parsed = parse_json_object(r#"{"a": "\u00e9\u00E9", "b": "\ud83d\ude00", "c": "\/\b\f"}"#).unwrap();
+    assert_eq!(parsed["a"], "éé");
+    assert_eq!(parsed["b"], "\u{1f600}");
+    assert_eq!(parsed["c"], "/\u{8}\u{c}");
+  }
+
+  #[test]
+  fn parse_json_object_rejects_malformed_unicode_escapes() {
+    // Only exactly four hex digits are an escape
+    assert_eq!(parse_json_object(r#"{"a": "\u+041"}"#), None);
+    assert_eq!(parse_json_object(r#"{"a": "\u41"}"#), None);
+    assert_eq!(parse_json_object(r#"{"a": "\u00g1"}"#), None);
+    // A surrogate is only accepted as part of a high-low pair
+    assert_eq!(parse_json_object(r#"{"a": "\ud83d"}"#), None);
+    assert_eq!(parse_json_object(r#"{"a": "\ud83dx"}"#), None);
+    assert_eq!(parse_json_object(r#"{"a": "\ud83d\u0041"}"#), None);
+    assert_eq!(parse_json_object(r#"{"a": "\ude00\ud83d"}"#), None);
+  }
+
+  #[test]
+  fn parse_json_object_rejects_malformed_input() {
+    assert_eq!(parse_json_object(""), None);
+    assert_eq!(parse_json_object("[]"), None);
+    assert_eq!(parse_json_object(r#"{"a" "b"}"#), None);
+    assert_eq!(parse_json_object(r#"{"a": 1}"#), None);
+    assert_eq!(parse_json_object(r#"{"a": "b",}"#), None);
+    assert_eq!(parse_json_object(r#"{"a": "b""#), None);
+    assert_eq!(parse_json_object(r#"{"a": "b} "#), None);
+    assert_eq!(parse_json_object(r#"{"a": "b"} x"#), None);
+    assert_eq!(parse_json_object(r#"{"a": "\x"}"#), None);
+    assert_eq!(parse_json_object("{\"a\": \"raw\nnewline\"}"), None);
+  }
+
+  #[test]
+  fn format_timestamp_formats_fixed_dates() {
+    assert_eq!(format_timestamp(0), "1970-01-01 00:00:00 +0000");
+    assert_eq!(format_timestamp(1618565400), "2021-04-16 09:30:00 +0000");
+    assert_eq!(format_timestamp(951827696), "2000-02-29 12:34:56 +0000");
+    assert_eq!(format_timestamp(1582934400), "2020-02-29 00:00:00 +0000");
+    assert_eq!(format_timestamp(4107542399), "2100-02-28 23:59:59 +0000");
+  }
+
+  #[test]
+  fn parse_signature_splits_at_the_end_of_the_email() {
+    assert_eq!(parse_signature("Jane Doe <jane@example.com> 1618565400 +0000"), ("Jane Doe <jane@example.com>", Some(1618565400)));
+    assert_eq!(parse_signature("Jane Q. Doe <jane@example.com>"), ("Jane Q. Doe <jane@example.com>", None));
+    assert_eq!(parse_signature("Jane Doe <jane@example.com> soon"), ("Jane Doe <jane@example.com>", None));
+    assert_eq!(parse_signature("Jane Doe"), ("Jane Doe", None));
+  }
+}